@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS FeedStates
+(
+    feed_url      TEXT    NOT NULL PRIMARY KEY,
+    etag          TEXT,
+    last_modified TEXT,
+    last_fetch_ms INTEGER NOT NULL
+)
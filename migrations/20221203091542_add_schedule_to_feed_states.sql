@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE FeedStates ADD COLUMN interval_ms INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE FeedStates ADD COLUMN next_fetch_ms INTEGER NOT NULL DEFAULT 0;
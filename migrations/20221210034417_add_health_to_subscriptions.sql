@@ -0,0 +1,8 @@
+-- Add migration script here
+ALTER TABLE Subscriptions ADD COLUMN last_success_ms INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE Subscriptions ADD COLUMN last_error TEXT;
+ALTER TABLE Subscriptions ADD COLUMN last_http_status INTEGER;
+ALTER TABLE Subscriptions ADD COLUMN consecutive_failures INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE Subscriptions ADD COLUMN disabled BOOL NOT NULL DEFAULT false;
+UPDATE Subscriptions SET last_success_ms = last_fetch_ms;
+ALTER TABLE FeedStates ADD COLUMN consecutive_failures INTEGER NOT NULL DEFAULT 0;
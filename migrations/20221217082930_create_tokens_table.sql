@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS Tokens
+(
+    id            TEXT    NOT NULL PRIMARY KEY,
+    user_id       TEXT    NOT NULL,
+    name          TEXT    NOT NULL,
+    token         TEXT    NOT NULL,
+    created_at_ms INTEGER NOT NULL,
+    expire_at     INTEGER NOT NULL,
+    UNIQUE (token)
+)
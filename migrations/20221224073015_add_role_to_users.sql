@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE Users ADD COLUMN role TEXT NOT NULL DEFAULT 'user';
+ALTER TABLE Users ADD COLUMN disabled BOOL NOT NULL DEFAULT false;
@@ -0,0 +1,3 @@
+-- Add migration script here
+CREATE VIRTUAL TABLE IF NOT EXISTS ItemsFts USING fts5(title, content, author);
+INSERT INTO ItemsFts (rowid, title, content, author) SELECT id, title, content, author FROM Items;
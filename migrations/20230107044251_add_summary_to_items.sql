@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE Items ADD COLUMN summary TEXT NOT NULL DEFAULT '';
+UPDATE Items SET summary = content;
+ALTER TABLE Subscriptions ADD COLUMN extract_content BOOL NOT NULL DEFAULT false;
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS Retentions
+(
+    user_id         TEXT    NOT NULL,
+    -- Empty for the retention of all the subscriptions of the user.
+    subscription_id TEXT    NOT NULL,
+    max_age_days    INTEGER,
+    max_items       INTEGER,
+    keep_starred    BOOL,
+    PRIMARY KEY (user_id, subscription_id)
+)
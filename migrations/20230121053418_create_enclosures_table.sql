@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS Enclosures
+(
+    item_id          INTEGER NOT NULL,
+    url              TEXT    NOT NULL,
+    mime_type        TEXT    NOT NULL DEFAULT '',
+    -- Bytes
+    length           INTEGER,
+    duration_seconds INTEGER,
+    PRIMARY KEY (item_id, url)
+)
@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS ItemTags
+(
+    user_id TEXT    NOT NULL,
+    item_id INTEGER NOT NULL,
+    tag     TEXT    NOT NULL,
+    PRIMARY KEY (item_id, tag)
+)
@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS Rules
+(
+    id            TEXT    NOT NULL PRIMARY KEY,
+    user_id       TEXT    NOT NULL,
+    name          TEXT    NOT NULL,
+    field         TEXT    NOT NULL,
+    match_type    TEXT    NOT NULL,
+    pattern       TEXT    NOT NULL,
+    action        TEXT    NOT NULL,
+    -- The tag added by the tag action.
+    tag           TEXT,
+    created_at_ms INTEGER NOT NULL
+)
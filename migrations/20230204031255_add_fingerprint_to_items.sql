@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Existing items keep an empty fingerprint, which matches no other item.
+ALTER TABLE Items ADD COLUMN fingerprint TEXT NOT NULL DEFAULT '';
+CREATE INDEX IF NOT EXISTS ItemsFingerprint ON Items (user_id, fingerprint);
@@ -0,0 +1,94 @@
+-- Add migration script here
+-- Items of the same feed are stored once in Entries, and the users only keep
+-- their state of them in ItemStates. Items stays as a view of both, so items
+-- keep their ids. Enclosures belong to the entries too.
+CREATE TABLE IF NOT EXISTS Feeds
+(
+    id              INTEGER PRIMARY KEY,
+    -- Subscriptions of all users to the feed have this id.
+    subscription_id TEXT    NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS Entries
+(
+    id            INTEGER PRIMARY KEY,
+    feed_id       INTEGER NOT NULL,
+    external_id   TEXT    NOT NULL,
+    title         TEXT    NOT NULL,
+    content       TEXT    NOT NULL,
+    summary       TEXT    NOT NULL DEFAULT '',
+    author        TEXT    NOT NULL,
+    url           TEXT    NOT NULL,
+    created_at_ms INTEGER NOT NULL,
+    fetched_at_ms INTEGER NOT NULL,
+    fingerprint   TEXT    NOT NULL DEFAULT '',
+    UNIQUE (feed_id, external_id)
+);
+CREATE INDEX IF NOT EXISTS EntriesFingerprint ON Entries (fingerprint);
+
+CREATE TABLE IF NOT EXISTS ItemStates
+(
+    id       INTEGER PRIMARY KEY,
+    user_id  TEXT    NOT NULL,
+    entry_id INTEGER NOT NULL,
+    starred  BOOL    NOT NULL,
+    read     BOOL    NOT NULL,
+    -- Content extracted for this user only, which replaces the content of the entry.
+    content_override TEXT,
+    UNIQUE (user_id, entry_id)
+);
+CREATE INDEX IF NOT EXISTS ItemStatesEntry ON ItemStates (entry_id);
+-- Indexes the items with a content override, by the ids of their states.
+CREATE VIRTUAL TABLE IF NOT EXISTS ItemStatesFts USING fts5(title, content, author);
+
+INSERT INTO Feeds (subscription_id) SELECT DISTINCT subscription_id FROM Items;
+
+-- The oldest copy of an item becomes the entry, and keeps its search index.
+INSERT INTO Entries (id, feed_id, external_id, title, content, summary, author, url, created_at_ms, fetched_at_ms, fingerprint)
+SELECT Items.id, Feeds.id, external_id, title, content, summary, author, url, created_at_ms, fetched_at_ms, fingerprint
+FROM Items JOIN Feeds ON Feeds.subscription_id = Items.subscription_id
+WHERE Items.id IN (SELECT MIN(id) FROM Items GROUP BY subscription_id, external_id);
+
+-- Copies with another content keep it as their own.
+INSERT INTO ItemStates (id, user_id, entry_id, starred, read, content_override)
+SELECT Items.id, Items.user_id, Entries.id, Items.starred, Items.read,
+       CASE WHEN Items.content != Entries.content THEN Items.content END
+FROM Items JOIN Feeds ON Feeds.subscription_id = Items.subscription_id
+JOIN Entries ON Entries.feed_id = Feeds.id AND Entries.external_id = Items.external_id;
+
+INSERT INTO ItemStatesFts (rowid, title, content, author)
+SELECT Items.id, Items.title, Items.content, Items.author
+FROM Items JOIN ItemStates ON ItemStates.id = Items.id
+WHERE ItemStates.content_override IS NOT NULL;
+
+DELETE FROM ItemsFts WHERE rowid NOT IN (SELECT id FROM Entries);
+
+CREATE TABLE IF NOT EXISTS EntryEnclosures
+(
+    entry_id         INTEGER NOT NULL,
+    url              TEXT    NOT NULL,
+    mime_type        TEXT    NOT NULL DEFAULT '',
+    -- Bytes
+    length           INTEGER,
+    duration_seconds INTEGER,
+    PRIMARY KEY (entry_id, url)
+);
+INSERT OR IGNORE INTO EntryEnclosures (entry_id, url, mime_type, length, duration_seconds)
+SELECT ItemStates.entry_id, url, mime_type, length, duration_seconds
+FROM Enclosures JOIN ItemStates ON ItemStates.id = Enclosures.item_id
+ORDER BY Enclosures.rowid;
+DROP TABLE Enclosures;
+ALTER TABLE EntryEnclosures RENAME TO Enclosures;
+
+DROP TABLE Items;
+
+CREATE VIEW Items AS
+SELECT ItemStates.id AS id, ItemStates.user_id AS user_id, Feeds.subscription_id AS subscription_id,
+       Entries.external_id AS external_id, Entries.title AS title,
+       COALESCE(ItemStates.content_override, Entries.content) AS content,
+       Entries.summary AS summary, Entries.author AS author, Entries.url AS url,
+       Entries.created_at_ms AS created_at_ms, Entries.fetched_at_ms AS fetched_at_ms,
+       ItemStates.starred AS starred, ItemStates.read AS read, Entries.fingerprint AS fingerprint,
+       Entries.id AS entry_id
+FROM ItemStates JOIN Entries ON Entries.id = ItemStates.entry_id
+JOIN Feeds ON Feeds.id = Entries.feed_id;
@@ -1,4 +1,7 @@
-use std::fmt::{Debug, Display};
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt::Display;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,19 +10,173 @@ pub enum Errors {
     NonExistUser { email: String },
     #[error("Invalid token {token:?}")]
     InvalidToken { token: String },
+    #[error("User[id={id:?}] doesn't exist.")]
+    UserNotFound { id: String },
+    #[error("User[email={email:?}] already exists.")]
+    UserExists { email: String },
+    #[error("User[email={email:?}] is disabled.")]
+    DisabledUser { email: String },
     #[error("Wrong password.")]
     WrongPassword,
+    #[error("Token[id={id:?}] doesn't exist.")]
+    TokenNotFound { id: String },
     #[error("Subscription is not found")]
     SubscriptionNotFound,
+    #[error("Invalid stream {stream_id:?}")]
+    InvalidStream { stream_id: String },
+    #[error("Rule[id={id:?}] doesn't exist.")]
+    RuleNotFound { id: String },
+    #[error("Invalid rule: {reason}")]
+    InvalidRule { reason: String },
+    #[error("Invalid signature {signature:?}")]
+    InvalidSignature { signature: String },
+    #[error("Url {url:?} can't be proxied.")]
+    InvalidMediaUrl { url: String },
+    #[error("Media {url:?} has unsupported type {content_type:?}.")]
+    UnsupportedMedia { url: String, content_type: String },
+    #[error("Media {url:?} is larger than {max_size_bytes} bytes.")]
+    MediaTooLarge { url: String, max_size_bytes: u64 },
+    #[error("Feed {url:?} responded with status {status}.")]
+    UnexpectedStatus {
+        url: String,
+        status: u16,
+        retry_after: Option<String>,
+    },
+    #[error("Invalid request: {reason}")]
+    InvalidRequest { reason: String },
+    #[error("Failed to fetch: {reason}")]
+    FetchFailed { reason: String },
+    #[error("Database error: {reason}")]
+    Database { reason: String },
+    #[error("Internal error: {reason}")]
+    Internal { reason: String },
+}
+
+impl Errors {
+    pub fn invalid_request<T: Display>(reason: T) -> Errors {
+        Errors::InvalidRequest {
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Classifies the errors of services, so routes can respond with them.
+impl From<anyhow::Error> for Errors {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<Errors>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+            return if e.is_builder() {
+                Errors::InvalidRequest {
+                    reason: e.to_string(),
+                }
+            } else {
+                Errors::FetchFailed {
+                    reason: e.to_string(),
+                }
+            };
+        }
+        if let Some(e) = error.downcast_ref::<url::ParseError>() {
+            return Errors::InvalidRequest {
+                reason: e.to_string(),
+            };
+        }
+        if let Some(e) = error.downcast_ref::<feed_rs::parser::ParseFeedError>() {
+            return Errors::FetchFailed {
+                reason: e.to_string(),
+            };
+        }
+        if let Some(e) = error.downcast_ref::<sqlx::Error>() {
+            return Errors::Database {
+                reason: e.to_string(),
+            };
+        }
+        Errors::Internal {
+            reason: error.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail {
+    code: u16,
+    message: String,
+}
+
+impl ResponseError for Errors {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Errors::NonExistUser { .. }
+            | Errors::InvalidToken { .. }
+            | Errors::DisabledUser { .. }
+            | Errors::WrongPassword
+            | Errors::InvalidSignature { .. } => StatusCode::FORBIDDEN,
+            Errors::UserNotFound { .. }
+            | Errors::TokenNotFound { .. }
+            | Errors::SubscriptionNotFound
+            | Errors::RuleNotFound { .. } => StatusCode::NOT_FOUND,
+            Errors::UserExists { .. }
+            | Errors::InvalidStream { .. }
+            | Errors::InvalidRule { .. }
+            | Errors::InvalidMediaUrl { .. }
+            | Errors::InvalidRequest { .. } => StatusCode::BAD_REQUEST,
+            Errors::UnsupportedMedia { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Errors::MediaTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Errors::UnexpectedStatus { .. } | Errors::FetchFailed { .. } => StatusCode::BAD_GATEWAY,
+            Errors::Database { .. } | Errors::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Server errors are logged, their details are not for the clients.
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let message = if status == StatusCode::INTERNAL_SERVER_ERROR {
+            tracing::error!("{}", self);
+            "Internal error".to_string()
+        } else {
+            self.to_string()
+        };
+        HttpResponse::build(status).json(ErrorBody {
+            error: ErrorDetail {
+                code: status.as_u16(),
+                message,
+            },
+        })
+    }
 }
 
 unsafe impl Send for Errors {}
 
 unsafe impl Sync for Errors {}
 
-pub fn to_internal_error<T>(e: T) -> actix_web::Error
-where
-    T: Debug + Display + 'static,
-{
-    actix_web::error::ErrorInternalServerError(e)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Error;
+
+    #[test]
+    fn errors_should_be_kept_when_converted() {
+        let error = Errors::from(Error::new(Errors::RuleNotFound {
+            id: "1".to_string(),
+        }));
+        assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(error.to_string(), "Rule[id=\"1\"] doesn't exist.");
+    }
+
+    #[test]
+    fn other_errors_should_be_classified() {
+        let error = Errors::from(Error::new(url::Url::parse("not a url").unwrap_err()));
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+        let error = Errors::from(Error::new(sqlx::Error::RowNotFound));
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        let error = Errors::from(anyhow::anyhow!("oops"));
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
 }
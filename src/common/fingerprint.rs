@@ -0,0 +1,85 @@
+use sha2::{Digest, Sha256};
+use url::Url;
+
+// Query parameters which only track where readers come from.
+const TRACKING_PARAMETERS: [&str; 5] = ["fbclid", "gclid", "mc_cid", "mc_eid", "ref"];
+
+fn is_tracking_parameter(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMETERS.contains(&name)
+}
+
+/// Normalizes a url, so the links to an article from different feeds are the
+/// same. The scheme, "www.", the fragment, tracking parameters and trailing
+/// slashes are dropped, and the other parameters are sorted.
+pub fn normalize_url(url: &str) -> String {
+    let url = match Url::parse(url.trim()) {
+        Ok(url) => url,
+        Err(_) => return url.trim().to_string(),
+    };
+    let host = url.host_str().unwrap_or("");
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    let mut parameters = url
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_parameter(name))
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<String>>();
+    parameters.sort();
+    let mut normalized = format!("{}{}", host, url.path().trim_end_matches('/'));
+    if !parameters.is_empty() {
+        normalized = format!("{}?{}", normalized, parameters.join("&"));
+    }
+    normalized
+}
+
+/// Identifies the content of an item by its normalized url and the hash of
+/// its title. Items without a url or a title have no fingerprint, which is
+/// empty.
+pub fn fingerprint(url: &str, title: &str) -> String {
+    let url = normalize_url(url);
+    let title = title
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase();
+    if url.is_empty() || title.is_empty() {
+        return String::new();
+    }
+    format!("{}#{}", url, hex::encode(Sha256::digest(title.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_should_be_normalized() {
+        assert_eq!(
+            normalize_url("https://www.blog.com/posts/1/?utm_source=rss&b=2&a=1#comments"),
+            "blog.com/posts/1?a=1&b=2"
+        );
+        assert_eq!(
+            normalize_url("http://blog.com/posts/1"),
+            normalize_url("https://blog.com/posts/1/")
+        );
+        assert_eq!(normalize_url("http://blog.com:8080/"), "blog.com:8080");
+        assert_eq!(normalize_url(" not a url "), "not a url");
+    }
+
+    #[test]
+    fn fingerprints_should_ignore_case_and_spaces_of_titles() {
+        assert_eq!(
+            fingerprint("https://blog.com/1?utm_medium=feed", "Hello  World"),
+            fingerprint("http://www.blog.com/1", "hello world")
+        );
+        assert_ne!(
+            fingerprint("https://blog.com/1", "Hello"),
+            fingerprint("https://blog.com/2", "Hello")
+        );
+        assert_eq!(fingerprint("https://blog.com/1", " "), "");
+        assert_eq!(fingerprint("", "Hello"), "");
+    }
+}
@@ -1,7 +1,9 @@
 pub mod error;
+pub mod fingerprint;
 pub mod telemetry;
 pub mod token;
 
+use crate::services::rules::RuleService;
 use crate::services::stream::StreamService;
 use crate::services::subscriptions::SubscriptionService;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -44,6 +46,18 @@ impl<T, OT> Page<T, OT> {
             next_page_offset: self.next_page_offset,
         }
     }
+
+    /// Converts the items, and drops those which can't be converted. The
+    /// page keeps its offset, so the next page is the same.
+    pub fn filter_map<R, F>(self, f: F) -> Page<R, OT>
+    where
+        F: FnMut(T) -> Option<R>,
+    {
+        Page::<R, OT> {
+            items: self.items.into_iter().filter_map(f).collect::<Vec<R>>(),
+            next_page_offset: self.next_page_offset,
+        }
+    }
 }
 
 use rand::distributions::Alphanumeric;
@@ -72,11 +86,8 @@ pub fn current_time_s() -> i64 {
         .as_secs() as i64
 }
 
-pub fn oldest_allowed_time_ms() -> i64 {
-    current_time_ms() - 14 * 24 * 60 * 60 * 1000
-}
-
 pub struct Services {
     pub subscription_service: Box<dyn SubscriptionService + Send + Sync>,
     pub stream_service: Box<dyn StreamService + Send + Sync>,
+    pub rule_service: Box<dyn RuleService + Send + Sync>,
 }
@@ -1,5 +1,8 @@
 use crate::common::current_time_s;
 
+// Lifetime of the token returned by ClientLogin.
+const LOGIN_TOKEN_LIFETIME_S: i64 = 14 * 24 * 60 * 60;
+
 pub struct Token {
     pub id: String,
     pub expire_at: i64,
@@ -8,9 +11,13 @@ pub struct Token {
 
 impl Token {
     pub fn new(id: &str) -> Token {
+        Token::expire_at(id, current_time_s() + LOGIN_TOKEN_LIFETIME_S)
+    }
+
+    pub fn expire_at(id: &str, expire_at: i64) -> Token {
         Token {
             id: id.to_string(),
-            expire_at: current_time_s() + 14 * 24 * 60 * 60,
+            expire_at,
             sid: super::new_id(20),
         }
     }
@@ -28,13 +35,33 @@ impl Token {
             Err(_) => None,
             Ok(expire_at) => Some(Token {
                 id: parts[2].to_string(),
-                expire_at: expire_at,
+                expire_at,
                 sid: parts[0].to_string(),
             }),
         }
     }
 
+    pub fn is_expired(&self) -> bool {
+        self.expire_at <= current_time_s()
+    }
+
     pub fn is_valid(token: &str) -> bool {
-        Token::parse(token).is_some()
+        Token::parse(token).is_some_and(|t| !t.is_expired())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_should_be_valid_until_expired() {
+        let token = Token::new("id").to_string();
+        assert!(Token::is_valid(&token));
+        assert_eq!(Token::parse(&token).unwrap().id, "id");
+
+        let expired = Token::expire_at("id", current_time_s() - 1).to_string();
+        assert!(!Token::is_valid(&expired));
+        assert!(!Token::is_valid("invalid"));
     }
 }
@@ -1,4 +1,6 @@
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 use sqlx::sqlite::SqliteConnectOptions;
 use std::str::FromStr;
 
@@ -6,6 +8,12 @@ use std::str::FromStr;
 pub struct Configuration {
     pub application: ApplicationConfiguration,
     pub database: DatabaseConfiguration,
+    #[serde(default)]
+    pub retention: RetentionConfiguration,
+    #[serde(default)]
+    pub proxy: ProxyConfiguration,
+    #[serde(default)]
+    pub dedup: DedupConfiguration,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -15,11 +23,127 @@ pub struct ApplicationConfiguration {
     pub port: u16,
     pub email: String,
     pub password: String,
+    // Whether anyone can sign up through /accounts/register.
+    #[serde(default)]
+    pub allow_registration: bool,
+}
+
+// How long items are kept. Users and subscriptions can override each of them.
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RetentionConfiguration {
+    // Items older than this are deleted. Items are kept forever if it's empty.
+    #[serde(
+        default = "default_max_age_days",
+        deserialize_with = "deserialize_option_number_from_string"
+    )]
+    pub max_age_days: Option<i64>,
+    // Each subscription keeps at most this many of its latest items.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub max_items: Option<i64>,
+    // Whether starred items are kept, however old they are.
+    #[serde(default = "default_keep_starred")]
+    pub keep_starred: bool,
+}
+
+fn default_max_age_days() -> Option<i64> {
+    Some(14)
+}
+
+fn default_keep_starred() -> bool {
+    true
+}
+
+impl Default for RetentionConfiguration {
+    fn default() -> Self {
+        RetentionConfiguration {
+            max_age_days: default_max_age_days(),
+            max_items: None,
+            keep_starred: default_keep_starred(),
+        }
+    }
+}
+
+// Serves the images of items through this server, so readers don't load them
+// from other hosts.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct ProxyConfiguration {
+    // Whether images in items are rewritten to the proxy.
+    #[serde(default)]
+    pub enabled: bool,
+    // Key to sign proxied urls. If it's empty, a random key is used, and the
+    // urls served before a restart stop working.
+    #[serde(default)]
+    pub secret: String,
+    #[serde(
+        default = "default_max_size_bytes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_size_bytes: u64,
+    // Directory to cache proxied images in, nothing is cached if it's empty.
+    #[serde(default)]
+    pub cache_path: Option<String>,
+    // The oldest images are removed when the cache is larger than this.
+    #[serde(
+        default = "default_cache_max_bytes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub cache_max_bytes: u64,
+}
+
+fn default_max_size_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_cache_max_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+impl Default for ProxyConfiguration {
+    fn default() -> Self {
+        ProxyConfiguration {
+            enabled: false,
+            secret: String::new(),
+            max_size_bytes: default_max_size_bytes(),
+            cache_path: None,
+            cache_max_bytes: default_cache_max_bytes(),
+        }
+    }
+}
+
+// What happens to an item with the same url and title as another item of the
+// user, like an article syndicated through two feeds.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupPolicy {
+    // Duplicates are kept as they are.
+    Off,
+    // Duplicates of existing items are not stored.
+    Hide,
+    // Duplicates are kept, and all copies are read when one of them is read.
+    MarkRead,
+}
+
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DedupConfiguration {
+    #[serde(default = "default_dedup_policy")]
+    pub policy: DedupPolicy,
+}
+
+fn default_dedup_policy() -> DedupPolicy {
+    DedupPolicy::Off
+}
+
+impl Default for DedupConfiguration {
+    fn default() -> Self {
+        DedupConfiguration {
+            policy: default_dedup_policy(),
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct DatabaseConfiguration {
-    path: String,
+    pub path: String,
 }
 
 pub fn get_configuration() -> Result<Configuration, config::ConfigError> {
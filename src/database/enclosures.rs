@@ -0,0 +1,174 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+/// Media attached to an item, like the episode of a podcast. It's stored once
+/// for the entry of the item, which the items of all users share.
+#[derive(sqlx::FromRow, PartialEq, Eq, Debug, Clone)]
+pub struct Enclosure {
+    pub item_id: i64,
+    pub url: String,
+    pub mime_type: String,
+    // Bytes
+    pub length: Option<i64>,
+    pub duration_seconds: Option<i64>,
+}
+
+#[async_trait]
+pub trait EnclosureRepository {
+    async fn insert_enclosures(&self, enclosures: Vec<Enclosure>) -> Result<()>;
+
+    async fn get_enclosures(&self, item_ids: &Vec<i64>) -> Result<Vec<Enclosure>>;
+}
+
+struct EnclosureRepositorySqlite {
+    pool: SqlitePool,
+}
+
+unsafe impl Send for EnclosureRepositorySqlite {}
+unsafe impl Sync for EnclosureRepositorySqlite {}
+
+impl EnclosureRepositorySqlite {
+    pub async fn new(pool: SqlitePool) -> Result<EnclosureRepositorySqlite> {
+        Ok(EnclosureRepositorySqlite { pool })
+    }
+}
+
+#[async_trait]
+impl EnclosureRepository for EnclosureRepositorySqlite {
+    async fn insert_enclosures(&self, enclosures: Vec<Enclosure>) -> Result<()> {
+        if enclosures.is_empty() {
+            return Ok(());
+        }
+        // The WHERE keeps the ON CONFLICT from being parsed as a join constraint.
+        let query_str = format!(
+            "WITH New (item_id, url, mime_type, length, duration_seconds) AS (VALUES {})
+      INSERT INTO Enclosures (entry_id, url, mime_type, length, duration_seconds)
+      SELECT ItemStates.entry_id, New.url, New.mime_type, New.length, New.duration_seconds
+      FROM New JOIN ItemStates ON ItemStates.id = New.item_id WHERE true
+      ON CONFLICT(entry_id, url) DO NOTHING",
+            vec!["(?,?,?,?,?)"; enclosures.len()].join(",")
+        );
+        let mut query = sqlx::query(&query_str);
+        for enclosure in enclosures.iter() {
+            query = query
+                .bind(enclosure.item_id)
+                .bind(&enclosure.url)
+                .bind(&enclosure.mime_type)
+                .bind(enclosure.length)
+                .bind(enclosure.duration_seconds);
+        }
+        query.execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn get_enclosures(&self, item_ids: &Vec<i64>) -> Result<Vec<Enclosure>> {
+        if item_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let query_str = format!(
+            "SELECT ItemStates.id AS item_id, url, mime_type, length, duration_seconds
+      FROM Enclosures JOIN ItemStates ON ItemStates.entry_id = Enclosures.entry_id
+      WHERE ItemStates.id IN ({}) ORDER BY item_id, Enclosures.rowid",
+            vec!["?"; item_ids.len()].join(", ")
+        );
+        let mut query = sqlx::query_as::<_, Enclosure>(&query_str);
+        for id in item_ids {
+            query = query.bind(id);
+        }
+        Ok(query.fetch_all(&self.pool).await?)
+    }
+}
+
+pub async fn new_enclosure_repository(
+    pool: SqlitePool,
+) -> Result<Box<dyn EnclosureRepository + Send + Sync>> {
+    let repository = EnclosureRepositorySqlite::new(pool).await?;
+    Ok(Box::new(repository))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::in_memory_pool;
+    use super::*;
+    use crate::database::items::{new_item_repository, Item};
+
+    fn new_enclosure(item_id: i64, url: &str) -> Enclosure {
+        Enclosure {
+            item_id,
+            url: url.to_string(),
+            mime_type: "audio/mpeg".to_string(),
+            length: Some(1024),
+            duration_seconds: None,
+        }
+    }
+
+    // Returns the ids of the items of one user.
+    async fn insert_items(pool: &SqlitePool, user_id: &str, external_ids: &[&str]) -> Vec<i64> {
+        let items = external_ids
+            .iter()
+            .map(|id| Item::new_item(user_id, "feed/a", id, "Title", "", "", "", "url", 1))
+            .collect();
+        let item_repository = new_item_repository(pool.clone()).await.unwrap();
+        let mut inserted = item_repository.insert_items(items).await.unwrap();
+        inserted.sort_by(|a, b| a.external_id.cmp(&b.external_id));
+        inserted.iter().map(|item| item.id).collect()
+    }
+
+    #[tokio::test]
+    pub async fn insert_and_get_enclosures_should_succeed() {
+        let pool = in_memory_pool().await;
+        let ids = insert_items(&pool, "user_id", &["1", "2"]).await;
+        let repository = new_enclosure_repository(pool).await.unwrap();
+        let enclosures = vec![
+            new_enclosure(ids[0], "https://podcast.com/1.mp3"),
+            new_enclosure(ids[0], "https://podcast.com/1.ogg"),
+            new_enclosure(ids[1], "https://podcast.com/2.mp3"),
+        ];
+        repository
+            .insert_enclosures(enclosures.clone())
+            .await
+            .unwrap();
+        // Enclosures which exist are skipped.
+        repository
+            .insert_enclosures(vec![new_enclosure(ids[0], "https://podcast.com/1.mp3")])
+            .await
+            .unwrap();
+
+        assert_eq!(repository.get_enclosures(&ids).await.unwrap(), enclosures);
+        assert_eq!(
+            repository
+                .get_enclosures(&vec![ids[1], ids[1] + 100])
+                .await
+                .unwrap(),
+            vec![new_enclosure(ids[1], "https://podcast.com/2.mp3")]
+        );
+        assert!(repository.get_enclosures(&vec![]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn enclosures_should_be_shared_by_users() {
+        let pool = in_memory_pool().await;
+        let id = insert_items(&pool, "user_id", &["1"]).await[0];
+        let other_id = insert_items(&pool, "user_id_2", &["1"]).await[0];
+        let repository = new_enclosure_repository(pool.clone()).await.unwrap();
+        repository
+            .insert_enclosures(vec![new_enclosure(id, "https://podcast.com/1.mp3")])
+            .await
+            .unwrap();
+        repository
+            .insert_enclosures(vec![new_enclosure(other_id, "https://podcast.com/1.mp3")])
+            .await
+            .unwrap();
+
+        let count = sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM Enclosures")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count.0, 1);
+        assert_eq!(
+            repository.get_enclosures(&vec![other_id]).await.unwrap(),
+            vec![new_enclosure(other_id, "https://podcast.com/1.mp3")]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+
+#[derive(sqlx::FromRow, PartialEq, Eq, Debug, Clone)]
+pub struct FeedState {
+    pub feed_url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_fetch_ms: i64,
+    pub interval_ms: i64,
+    pub next_fetch_ms: i64,
+    pub consecutive_failures: i64,
+}
+
+impl FeedState {
+    pub fn new(feed_url: &str) -> FeedState {
+        FeedState {
+            feed_url: feed_url.to_owned(),
+            etag: None,
+            last_modified: None,
+            last_fetch_ms: 0,
+            interval_ms: 0,
+            next_fetch_ms: 0,
+            consecutive_failures: 0,
+        }
+    }
+}
+
+#[async_trait]
+pub trait FeedStateRepository {
+    async fn upsert_feed_state(&self, feed_state: FeedState) -> Result<()>;
+    async fn list_feed_states(&self) -> Result<HashMap<String, FeedState>>;
+}
+
+struct FeedStateRepositorySqlite {
+    pool: SqlitePool,
+}
+
+unsafe impl Send for FeedStateRepositorySqlite {}
+unsafe impl Sync for FeedStateRepositorySqlite {}
+
+impl FeedStateRepositorySqlite {
+    pub async fn new(pool: SqlitePool) -> Result<FeedStateRepositorySqlite> {
+        Ok(FeedStateRepositorySqlite { pool })
+    }
+}
+
+#[async_trait]
+impl FeedStateRepository for FeedStateRepositorySqlite {
+    async fn upsert_feed_state(&self, feed_state: FeedState) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO FeedStates
+      (feed_url, etag, last_modified, last_fetch_ms, interval_ms, next_fetch_ms,
+       consecutive_failures)
+      VALUES (?,?,?,?,?,?,?)
+      ON CONFLICT(feed_url) DO UPDATE SET
+        etag = excluded.etag,
+        last_modified = excluded.last_modified,
+        last_fetch_ms = excluded.last_fetch_ms,
+        interval_ms = excluded.interval_ms,
+        next_fetch_ms = excluded.next_fetch_ms,
+        consecutive_failures = excluded.consecutive_failures",
+        )
+        .bind(&feed_state.feed_url)
+        .bind(&feed_state.etag)
+        .bind(&feed_state.last_modified)
+        .bind(feed_state.last_fetch_ms)
+        .bind(feed_state.interval_ms)
+        .bind(feed_state.next_fetch_ms)
+        .bind(feed_state.consecutive_failures)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_feed_states(&self) -> Result<HashMap<String, FeedState>> {
+        let feed_states = sqlx::query_as::<_, FeedState>("SELECT * FROM FeedStates")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(feed_states
+            .into_iter()
+            .map(|state| (state.feed_url.clone(), state))
+            .collect())
+    }
+}
+
+pub async fn new_feed_state_repository(
+    pool: SqlitePool,
+) -> Result<Box<dyn FeedStateRepository + Send + Sync>> {
+    let repository = FeedStateRepositorySqlite::new(pool).await?;
+    Ok(Box::new(repository))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::in_memory_pool;
+    use super::*;
+
+    #[tokio::test]
+    pub async fn upsert_and_list_feed_states_should_succeed() {
+        let repository = new_feed_state_repository(in_memory_pool().await)
+            .await
+            .unwrap();
+        let mut feed_state = FeedState {
+            feed_url: "feed_url".to_string(),
+            etag: Some("etag".to_string()),
+            last_modified: None,
+            last_fetch_ms: 1,
+            interval_ms: 600000,
+            next_fetch_ms: 600001,
+            consecutive_failures: 0,
+        };
+        repository
+            .upsert_feed_state(feed_state.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.list_feed_states().await.unwrap()["feed_url"],
+            feed_state
+        );
+
+        feed_state.etag = None;
+        feed_state.last_modified = Some("last_modified".to_string());
+        feed_state.last_fetch_ms = 2;
+        feed_state.next_fetch_ms = 600002;
+        feed_state.consecutive_failures = 1;
+        repository
+            .upsert_feed_state(feed_state.clone())
+            .await
+            .unwrap();
+        let feed_states = repository.list_feed_states().await.unwrap();
+        assert_eq!(feed_states.len(), 1);
+        assert_eq!(feed_states["feed_url"], feed_state);
+    }
+}
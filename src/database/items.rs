@@ -1,7 +1,10 @@
+use crate::common::error::Errors;
+use crate::common::fingerprint::fingerprint;
 use crate::common::{current_time_ms, Page, PageOption};
-use anyhow::Result;
+use anyhow::{Error, Result};
 use async_trait::async_trait;
-use sqlx::SqlitePool;
+use sqlx::{Row, Sqlite, SqlitePool, Transaction};
+use std::collections::{HashMap, HashSet};
 
 #[derive(sqlx::FromRow, PartialEq, Eq, Debug)]
 pub struct ItemId {
@@ -18,12 +21,16 @@ pub struct Item {
     pub id: i64,
     pub title: String,
     pub content: String,
+    // The summary from the feed, content may be the full article instead.
+    pub summary: String,
     pub author: String,
     pub url: String,
     pub created_at_ms: i64,
     pub fetched_at_ms: i64,
     pub starred: bool,
     pub read: bool,
+    // Same for the copies of an article, see common::fingerprint.
+    pub fingerprint: String,
 }
 
 impl PartialEq for Item {
@@ -37,12 +44,17 @@ impl PartialEq for Item {
 impl Eq for Item {}
 
 impl Item {
-    fn parse_offset(offset: &str) -> (i64, i64) {
-        let parts: Vec<&str> = offset.split("-").collect();
-        (
-            parts[0].parse::<i64>().unwrap(),
-            parts[1].parse::<i64>().unwrap(),
-        )
+    // Offsets come from clients, so they may be anything.
+    pub(crate) fn parse_offset(offset: &str) -> Result<(i64, i64)> {
+        offset
+            .split_once('-')
+            .and_then(|(created_at_ms, id)| Some((created_at_ms.parse().ok()?, id.parse().ok()?)))
+            .ok_or_else(|| {
+                Error::new(Errors::invalid_request(format!(
+                    "Invalid offset {:?}",
+                    offset
+                )))
+            })
     }
 
     fn as_offset(&self) -> String {
@@ -55,6 +67,7 @@ impl Item {
         external_id: &str,
         title: &str,
         content: &str,
+        summary: &str,
         author: &str,
         url: &str,
         created_at_ms: i64,
@@ -66,12 +79,14 @@ impl Item {
             id: 0,
             title: title.to_owned(),
             content: content.to_owned(),
+            summary: summary.to_owned(),
             author: author.to_owned(),
             url: url.to_owned(),
             created_at_ms: created_at_ms,
             fetched_at_ms: current_time_ms(),
             starred: false,
             read: false,
+            fingerprint: fingerprint(url, title),
         }
     }
 
@@ -84,6 +99,70 @@ impl Item {
     }
 }
 
+#[derive(sqlx::FromRow, PartialEq, Eq, Debug, Clone)]
+pub struct UnreadCount {
+    pub subscription_id: String,
+    pub count: i64,
+    pub newest_created_at_ms: i64,
+}
+
+/// Items with a label, from their subscription or from their own tags.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct LabelFilter {
+    pub label: String,
+    // The subscriptions with the label.
+    pub subscription_ids: Vec<String>,
+}
+
+impl LabelFilter {
+    fn condition(&self) -> String {
+        let tagged = "id IN (SELECT item_id FROM ItemTags WHERE tag = ?)";
+        if self.subscription_ids.is_empty() {
+            return tagged.to_string();
+        }
+        format!(
+            "(subscription_id IN ({}) OR {})",
+            vec!["?"; self.subscription_ids.len()].join(", "),
+            tagged
+        )
+    }
+}
+
+/// Restricts the items returned by get_filtered_items. None means no restriction.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ItemFilter {
+    pub subscription_ids: Option<Vec<String>>,
+    pub excluded_subscription_ids: Vec<String>,
+    // Items must have all the labels, and none of the excluded labels.
+    pub labels: Vec<LabelFilter>,
+    pub excluded_labels: Vec<LabelFilter>,
+    pub read: Option<bool>,
+    pub starred: Option<bool>,
+    pub newer_than_ms: Option<i64>,
+    pub older_than_ms: Option<i64>,
+    // Words to search in the title, content and author.
+    pub search: Option<String>,
+}
+
+/// Items of a subscription to delete by delete_items.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct CleanUp {
+    // Items created at or before this are deleted.
+    pub older_than_ms: Option<i64>,
+    // Items after the latest max_items are deleted.
+    pub max_items: Option<i64>,
+    pub keep_starred: bool,
+}
+
+// Quotes every word, so the words are never read as FTS5 query syntax.
+fn fts_query(search: &str) -> String {
+    search
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
 pub enum State {
     STARRED,
     UNSTARRED,
@@ -135,9 +214,57 @@ pub trait ItemRepository {
         page_option: PageOption<String>,
     ) -> Result<Page<Item, String>>;
 
-    async fn insert_items(&self, mut items: Vec<Item>) -> Result<()>;
+    async fn get_filtered_items(
+        &self,
+        user_id: &str,
+        filter: &ItemFilter,
+        page_option: PageOption<String>,
+    ) -> Result<Page<Item, String>>;
+
+    /// Returns the items which are new, with their ids.
+    async fn insert_items(&self, mut items: Vec<Item>) -> Result<Vec<Item>>;
+
+    async fn update_item_content(&self, id: i64, content: &str) -> Result<()>;
+
+    /// Adds tags to items, by the ids of the items.
+    async fn add_item_tags(&self, user_id: &str, item_tags: Vec<(i64, String)>) -> Result<()>;
+
+    async fn tag_items(&self, user_id: &str, ids: &Vec<&str>, tag: &str) -> Result<()>;
+
+    async fn untag_items(&self, user_id: &str, ids: &Vec<&str>, tag: &str) -> Result<()>;
+
+    /// Returns the tags of the items, with the ids of the items.
+    async fn get_item_tags(&self, item_ids: &Vec<i64>) -> Result<Vec<(i64, String)>>;
+
+    async fn list_tags(&self, user_id: &str) -> Result<Vec<String>>;
+
+    async fn rename_tag(&self, user_id: &str, from: &str, to: &str) -> Result<()>;
+
+    async fn remove_tag(&self, user_id: &str, tag: &str) -> Result<()>;
+
+    /// Returns the fingerprints which items of the current subscriptions of
+    /// the user have, with whether one of the items is read.
+    async fn find_fingerprints(
+        &self,
+        user_id: &str,
+        fingerprints: &Vec<&str>,
+    ) -> Result<Vec<(String, bool)>>;
+
+    /// Marks the other items with the fingerprints of the items.
+    async fn mark_copies_as(&self, user_id: &str, ids: &Vec<&str>, state: State) -> Result<()>;
+
+    /// Marks the unread copies of read items as read.
+    async fn mark_copies_of_read_items(&self, user_id: &str) -> Result<()>;
+
+    async fn delete_items(
+        &self,
+        user_id: &str,
+        subscription_id: &str,
+        clean_up: &CleanUp,
+    ) -> Result<()>;
 
-    async fn delete_items(&self, earlier_than: i64) -> Result<()>;
+    /// Deletes the items of subscriptions which are removed, if they are old enough.
+    async fn delete_unsubscribed_items(&self, older_than_ms: i64) -> Result<()>;
 
     async fn mark_as(&self, item_id: ItemId, state: State) -> Result<()>;
 
@@ -146,6 +273,22 @@ pub trait ItemRepository {
     async fn mark_all_as_read(&self, user_id: &str) -> Result<()>;
 
     async fn mark_older_as_read(&self, user_id: &str, older_than: i64) -> Result<()>;
+
+    async fn mark_subscriptions_as_read(
+        &self,
+        user_id: &str,
+        subscription_ids: &Vec<&str>,
+        older_than: Option<i64>,
+    ) -> Result<()>;
+
+    async fn mark_tagged_as_read(
+        &self,
+        user_id: &str,
+        tag: &str,
+        older_than: Option<i64>,
+    ) -> Result<()>;
+
+    async fn count_unread_items(&self, user_id: &str) -> Result<Vec<UnreadCount>>;
 }
 
 fn id_str_to_i64(id: &str) -> i64 {
@@ -157,6 +300,48 @@ fn id_str_to_i64(id: &str) -> i64 {
     }
 }
 
+/// Deletes the entries which are no user's items any more, with their
+/// enclosures and feeds.
+pub async fn delete_orphan_entries(tx: &mut Transaction<'_, Sqlite>) -> Result<()> {
+    let orphan = "NOT EXISTS (SELECT 1 FROM ItemStates WHERE entry_id = Entries.id)";
+    for query_str in [
+        format!(
+            "DELETE FROM ItemsFts WHERE rowid IN (SELECT id FROM Entries WHERE {})",
+            orphan
+        ),
+        format!(
+            "DELETE FROM Enclosures WHERE entry_id IN (SELECT id FROM Entries WHERE {})",
+            orphan
+        ),
+        format!("DELETE FROM Entries WHERE {}", orphan),
+        "DELETE FROM Feeds WHERE NOT EXISTS (SELECT 1 FROM Entries WHERE feed_id = Feeds.id)"
+            .to_string(),
+    ] {
+        sqlx::query(&query_str).execute(&mut *tx).await?;
+    }
+    Ok(())
+}
+
+/// Fingerprints the entries which were stored before fingerprints, so the
+/// copies of new items are found among them too.
+pub async fn fill_fingerprints(pool: &SqlitePool) -> Result<()> {
+    let entries = sqlx::query_as::<_, (i64, String, String)>(
+        "SELECT id, url, title FROM Entries WHERE fingerprint = '' AND url != '' AND title != ''",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut tx = pool.begin().await?;
+    for (id, url, title) in entries {
+        sqlx::query("UPDATE Entries SET fingerprint = ? WHERE id = ?")
+            .bind(fingerprint(&url, &title))
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
 struct ItemRepositorySqlite {
     pool: SqlitePool,
 }
@@ -169,11 +354,11 @@ impl ItemRepositorySqlite {
         Ok(ItemRepositorySqlite { pool })
     }
 
-    fn build_page_query(page_option: &PageOption<String>) -> String {
+    fn build_page_query(page_option: &PageOption<String>) -> Result<String> {
         let operator = if page_option.desc { "<=" } else { ">=" };
         let pagination = match page_option.offset {
             Some(ref offset) => {
-                let (created_at_ms, id) = Item::parse_offset(offset);
+                let (created_at_ms, id) = Item::parse_offset(offset)?;
                 format!(
                     "AND created_at_ms {} {} AND id {} \"{}\"",
                     operator, created_at_ms, operator, id
@@ -187,7 +372,7 @@ impl ItemRepositorySqlite {
             if page_option.desc { "DESC" } else { "" },
             page_option.limit + 1
         );
-        format!("{} {}", pagination, order_and_limit)
+        Ok(format!("{} {}", pagination, order_and_limit))
     }
 
     async fn get_items_with_query(
@@ -196,20 +381,123 @@ impl ItemRepositorySqlite {
         query: String,
         page_option: &PageOption<String>,
     ) -> Result<Page<Item, String>> {
-        let mut items = sqlx::query_as::<_, Item>(&query)
+        let items = sqlx::query_as::<_, Item>(&query)
             .bind(user_id)
             .fetch_all(&self.pool)
             .await?;
+        Ok(Self::to_page(items, page_option))
+    }
+
+    // Returns the ids of the feeds of the items, by their subscription ids.
+    async fn insert_feeds(
+        tx: &mut Transaction<'_, Sqlite>,
+        items: &Vec<Item>,
+    ) -> Result<HashMap<String, i64>> {
+        let subscription_ids = items
+            .iter()
+            .map(|item| item.subscription_id.as_str())
+            .collect::<HashSet<&str>>();
+        let placeholders = |value: &str| vec![value; subscription_ids.len()].join(", ");
+        let insert_str = format!(
+            "INSERT INTO Feeds (subscription_id) VALUES {} ON CONFLICT(subscription_id) DO NOTHING",
+            placeholders("(?)")
+        );
+        let select_str = format!(
+            "SELECT subscription_id, id FROM Feeds WHERE subscription_id IN ({})",
+            placeholders("?")
+        );
+        let mut insert = sqlx::query(&insert_str);
+        let mut select = sqlx::query_as::<_, (String, i64)>(&select_str);
+        for subscription_id in subscription_ids.iter() {
+            insert = insert.bind(*subscription_id);
+            select = select.bind(*subscription_id);
+        }
+        insert.execute(&mut *tx).await?;
+        Ok(select.fetch_all(&mut *tx).await?.into_iter().collect())
+    }
+
+    // Stores the entries which no user has yet, and returns the ids of the
+    // entries of the items, by their feed ids and external ids.
+    async fn insert_entries(
+        tx: &mut Transaction<'_, Sqlite>,
+        items: &Vec<Item>,
+        feed_ids: &HashMap<String, i64>,
+    ) -> Result<HashMap<(i64, String), i64>> {
+        let mut seen = HashSet::new();
+        let entries = items
+            .iter()
+            .filter_map(|item| {
+                let feed_id = *feed_ids.get(&item.subscription_id)?;
+                seen.insert((feed_id, item.external_id.as_str()))
+                    .then_some((feed_id, item))
+            })
+            .collect::<Vec<(i64, &Item)>>();
+        let insert_str = format!(
+            "INSERT INTO Entries
+      (feed_id, external_id, title, content, summary, author, url, created_at_ms, fetched_at_ms, fingerprint)
+      VALUES {} ON CONFLICT(feed_id, external_id) DO NOTHING RETURNING id",
+            vec!["(?,?,?,?,?,?,?,?,?,?)"; entries.len()].join(",")
+        );
+        let mut insert = sqlx::query(&insert_str);
+        for (feed_id, item) in entries.iter() {
+            insert = insert
+                .bind(feed_id)
+                .bind(&item.external_id)
+                .bind(&item.title)
+                .bind(item.content.trim().to_owned())
+                .bind(item.summary.trim().to_owned())
+                .bind(&item.author)
+                .bind(&item.url)
+                .bind(item.created_at_ms)
+                .bind(item.fetched_at_ms)
+                .bind(&item.fingerprint);
+        }
+        let inserted_ids = insert
+            .fetch_all(&mut *tx)
+            .await?
+            .iter()
+            .map(|row| row.get::<i64, _>("id"))
+            .collect::<Vec<i64>>();
+        if !inserted_ids.is_empty() {
+            let index_str = format!(
+                "INSERT INTO ItemsFts (rowid, title, content, author)
+       SELECT id, title, content, author FROM Entries WHERE id IN ({})",
+                vec!["?"; inserted_ids.len()].join(", ")
+            );
+            let mut index_query = sqlx::query(&index_str);
+            for id in inserted_ids.iter() {
+                index_query = index_query.bind(id);
+            }
+            index_query.execute(&mut *tx).await?;
+        }
+
+        let select_str = format!(
+            "SELECT feed_id, external_id, id FROM Entries WHERE (feed_id, external_id) IN (VALUES {})",
+            vec!["(?,?)"; entries.len()].join(",")
+        );
+        let mut select = sqlx::query_as::<_, (i64, String, i64)>(&select_str);
+        for (feed_id, item) in entries.iter() {
+            select = select.bind(feed_id).bind(&item.external_id);
+        }
+        Ok(select
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .map(|(feed_id, external_id, id)| ((feed_id, external_id), id))
+            .collect())
+    }
+
+    fn to_page(mut items: Vec<Item>, page_option: &PageOption<String>) -> Page<Item, String> {
         let next_page_offset = if items.len() > page_option.limit {
             let last_item = items.pop().unwrap();
             Some(last_item.as_offset())
         } else {
             None
         };
-        Ok(Page::<Item, String> {
+        Page::<Item, String> {
             items: items,
             next_page_offset: next_page_offset,
-        })
+        }
     }
 }
 
@@ -240,7 +528,7 @@ impl ItemRepository for ItemRepositorySqlite {
     ) -> Result<Page<Item, String>> {
         let query = format!(
             "SELECT * FROM Items WHERE user_id = ? {}",
-            Self::build_page_query(&page_option)
+            Self::build_page_query(&page_option)?
         );
         self.get_items_with_query(user_id, query, &page_option)
             .await
@@ -253,7 +541,7 @@ impl ItemRepository for ItemRepositorySqlite {
     ) -> Result<Page<Item, String>> {
         let query = format!(
             "SELECT * FROM Items WHERE user_id = ? AND read = false {}",
-            Self::build_page_query(&page_option)
+            Self::build_page_query(&page_option)?
         );
         self.get_items_with_query(user_id, query, &page_option)
             .await
@@ -266,7 +554,7 @@ impl ItemRepository for ItemRepositorySqlite {
     ) -> Result<Page<Item, String>> {
         let query = format!(
             "SELECT * FROM Items WHERE user_id = ? AND read = true {}",
-            Self::build_page_query(&page_option)
+            Self::build_page_query(&page_option)?
         );
         self.get_items_with_query(user_id, query, &page_option)
             .await
@@ -279,60 +567,431 @@ impl ItemRepository for ItemRepositorySqlite {
     ) -> Result<Page<Item, String>> {
         let query = format!(
             "SELECT * FROM Items WHERE user_id = ? AND starred = true {}",
-            Self::build_page_query(&page_option)
+            Self::build_page_query(&page_option)?
         );
         self.get_items_with_query(user_id, query, &page_option)
             .await
     }
 
-    async fn insert_items(&self, mut items: Vec<Item>) -> Result<()> {
+    async fn get_filtered_items(
+        &self,
+        user_id: &str,
+        filter: &ItemFilter,
+        page_option: PageOption<String>,
+    ) -> Result<Page<Item, String>> {
+        // Malformed offsets are rejected, even if nothing is queried.
+        let page_query = Self::build_page_query(&page_option)?;
+        let placeholders = |n: usize| vec!["?"; n].join(", ");
+        let mut conditions = vec![];
+        if let Some(ref ids) = filter.subscription_ids {
+            if ids.is_empty() {
+                return Ok(Page::empty());
+            }
+            conditions.push(format!("subscription_id IN ({})", placeholders(ids.len())));
+        }
+        if !filter.excluded_subscription_ids.is_empty() {
+            conditions.push(format!(
+                "subscription_id NOT IN ({})",
+                placeholders(filter.excluded_subscription_ids.len())
+            ));
+        }
+        for label in filter.labels.iter() {
+            conditions.push(label.condition());
+        }
+        for label in filter.excluded_labels.iter() {
+            conditions.push(format!("NOT {}", label.condition()));
+        }
+        if filter.read.is_some() {
+            conditions.push("read = ?".to_string());
+        }
+        if filter.starred.is_some() {
+            conditions.push("starred = ?".to_string());
+        }
+        if filter.newer_than_ms.is_some() {
+            conditions.push("created_at_ms >= ?".to_string());
+        }
+        if filter.older_than_ms.is_some() {
+            conditions.push("created_at_ms <= ?".to_string());
+        }
+        let search = filter.search.as_deref().map(fts_query);
+        if let Some(ref search) = search {
+            if search.is_empty() {
+                return Ok(Page::empty());
+            }
+            // Items with a content override are only found by it.
+            conditions.push(
+                "(id IN (SELECT rowid FROM ItemStatesFts WHERE ItemStatesFts MATCH ?)
+         OR (id NOT IN (SELECT rowid FROM ItemStatesFts)
+         AND entry_id IN (SELECT rowid FROM ItemsFts WHERE ItemsFts MATCH ?)))"
+                    .to_string(),
+            );
+        }
+        let query_str = format!(
+            "SELECT * FROM Items WHERE user_id = ? {} {}",
+            conditions
+                .iter()
+                .map(|condition| format!("AND {}", condition))
+                .collect::<Vec<String>>()
+                .join(" "),
+            page_query
+        );
+        let mut query = sqlx::query_as::<_, Item>(&query_str).bind(user_id);
+        for id in filter.subscription_ids.iter().flatten() {
+            query = query.bind(id);
+        }
+        for id in &filter.excluded_subscription_ids {
+            query = query.bind(id);
+        }
+        for label in filter.labels.iter().chain(filter.excluded_labels.iter()) {
+            for id in &label.subscription_ids {
+                query = query.bind(id);
+            }
+            query = query.bind(&label.label);
+        }
+        for value in [filter.read, filter.starred].into_iter().flatten() {
+            query = query.bind(value);
+        }
+        for value in [filter.newer_than_ms, filter.older_than_ms]
+            .into_iter()
+            .flatten()
+        {
+            query = query.bind(value);
+        }
+        if let Some(search) = search {
+            query = query.bind(search.clone()).bind(search);
+        }
+        let items = query.fetch_all(&self.pool).await?;
+        Ok(Self::to_page(items, &page_option))
+    }
+
+    async fn insert_items(&self, mut items: Vec<Item>) -> Result<Vec<Item>> {
         if items.is_empty() {
-            return Ok(());
+            return Ok(vec![]);
         }
-        let base = String::from("
-    INSERT INTO Items 
-    (user_id, subscription_id, external_id, title, content, author, url, created_at_ms, fetched_at_ms, starred, read)
-    VALUES ");
         items.sort_by(|a, b| a.created_at_ms.cmp(&b.created_at_ms));
-        let values = items
-            .iter()
-            .map(|_| "(?,?,?,?,?,?,?,?,?,?,?)")
-            .collect::<Vec<&str>>()
-            .join(",");
+        let mut tx = self.pool.begin().await?;
+        let feed_ids = Self::insert_feeds(&mut tx, &items).await?;
+        let entry_ids = Self::insert_entries(&mut tx, &items, &feed_ids).await?;
+        let entry_id = |item: &Item| {
+            feed_ids
+                .get(&item.subscription_id)
+                .and_then(|feed_id| entry_ids.get(&(*feed_id, item.external_id.clone())))
+                .copied()
+        };
+
         let query_str = format!(
-            "{}{} ON CONFLICT(user_id, subscription_id, external_id) DO NOTHING",
-            base, values
+            "INSERT INTO ItemStates (user_id, entry_id, starred, read) VALUES {}
+      ON CONFLICT(user_id, entry_id) DO NOTHING RETURNING id, user_id, entry_id",
+            vec!["(?,?,?,?)"; items.len()].join(",")
         );
         let mut query = sqlx::query(&query_str);
         for item in items.iter() {
             query = query
                 .bind(&item.user_id)
-                .bind(&item.subscription_id)
-                .bind(&item.external_id)
-                .bind(&item.title)
-                .bind(item.content.trim().to_owned())
-                .bind(&item.author)
-                .bind(&item.url)
-                .bind(item.created_at_ms)
-                .bind(item.fetched_at_ms)
+                .bind(entry_id(item))
                 .bind(item.starred)
-                .bind(item.read)
+                .bind(item.read);
+        }
+        let mut inserted_ids = query
+            .fetch_all(&mut tx)
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    (
+                        row.get::<String, _>("user_id"),
+                        row.get::<i64, _>("entry_id"),
+                    ),
+                    row.get::<i64, _>("id"),
+                )
+            })
+            .collect::<HashMap<(String, i64), i64>>();
+        tx.commit().await?;
+        Ok(items
+            .into_iter()
+            .filter_map(|mut item| {
+                let key = (item.user_id.clone(), entry_id(&item)?);
+                inserted_ids.remove(&key).map(|id| {
+                    item.id = id;
+                    item
+                })
+            })
+            .collect())
+    }
+
+    async fn update_item_content(&self, id: i64, content: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        // Other users of the entry keep its content.
+        sqlx::query("UPDATE ItemStates SET content_override = ? WHERE id = ?")
+            .bind(content.trim())
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        for query_str in [
+            "DELETE FROM ItemStatesFts WHERE rowid = ?",
+            "INSERT INTO ItemStatesFts (rowid, title, content, author)
+       SELECT id, title, content, author FROM Items WHERE id = ?",
+        ] {
+            sqlx::query(query_str).bind(id).execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn add_item_tags(&self, user_id: &str, item_tags: Vec<(i64, String)>) -> Result<()> {
+        if item_tags.is_empty() {
+            return Ok(());
+        }
+        let query_str = format!(
+            "INSERT INTO ItemTags (user_id, item_id, tag) VALUES {}
+      ON CONFLICT(item_id, tag) DO NOTHING",
+            vec!["(?,?,?)"; item_tags.len()].join(",")
+        );
+        let mut query = sqlx::query(&query_str);
+        for (item_id, tag) in item_tags.iter() {
+            query = query.bind(user_id).bind(item_id).bind(tag);
+        }
+        query.execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn tag_items(&self, user_id: &str, ids: &Vec<&str>, tag: &str) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        // Only the items of the user are tagged.
+        let query_str = format!(
+            "INSERT INTO ItemTags (user_id, item_id, tag)
+      SELECT user_id, id, ? FROM Items WHERE user_id = ? AND id IN ({})
+      ON CONFLICT(item_id, tag) DO NOTHING",
+            vec!["?"; ids.len()].join(", ")
+        );
+        let mut query = sqlx::query(&query_str).bind(tag).bind(user_id);
+        for id in ids {
+            query = query.bind(id_str_to_i64(id));
+        }
+        query.execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn untag_items(&self, user_id: &str, ids: &Vec<&str>, tag: &str) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let query_str = format!(
+            "DELETE FROM ItemTags WHERE user_id = ? AND tag = ? AND item_id IN ({})",
+            vec!["?"; ids.len()].join(", ")
+        );
+        let mut query = sqlx::query(&query_str).bind(user_id).bind(tag);
+        for id in ids {
+            query = query.bind(id_str_to_i64(id));
         }
         query.execute(&self.pool).await?;
         Ok(())
     }
 
-    async fn delete_items(&self, earlier_than: i64) -> Result<()> {
-        sqlx::query("DELETE FROM Items WHERE created_at_ms <= ?")
-            .bind(earlier_than)
+    async fn get_item_tags(&self, item_ids: &Vec<i64>) -> Result<Vec<(i64, String)>> {
+        if item_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let query_str = format!(
+            "SELECT item_id, tag FROM ItemTags WHERE item_id IN ({}) ORDER BY item_id, tag",
+            vec!["?"; item_ids.len()].join(", ")
+        );
+        let mut query = sqlx::query_as::<_, (i64, String)>(&query_str);
+        for id in item_ids {
+            query = query.bind(id);
+        }
+        Ok(query.fetch_all(&self.pool).await?)
+    }
+
+    async fn list_tags(&self, user_id: &str) -> Result<Vec<String>> {
+        let tags = sqlx::query("SELECT DISTINCT tag FROM ItemTags WHERE user_id = ? ORDER BY tag")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("tag"))
+            .collect();
+        Ok(tags)
+    }
+
+    async fn rename_tag(&self, user_id: &str, from: &str, to: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        // Items which already have both tags keep one of them.
+        sqlx::query("UPDATE OR IGNORE ItemTags SET tag = ? WHERE user_id = ? AND tag = ?")
+            .bind(to)
+            .bind(user_id)
+            .bind(from)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM ItemTags WHERE user_id = ? AND tag = ?")
+            .bind(user_id)
+            .bind(from)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn remove_tag(&self, user_id: &str, tag: &str) -> Result<()> {
+        sqlx::query("DELETE FROM ItemTags WHERE user_id = ? AND tag = ?")
+            .bind(user_id)
+            .bind(tag)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    async fn find_fingerprints(
+        &self,
+        user_id: &str,
+        fingerprints: &Vec<&str>,
+    ) -> Result<Vec<(String, bool)>> {
+        let fingerprints = fingerprints
+            .iter()
+            .filter(|fingerprint| !fingerprint.is_empty())
+            .collect::<Vec<&&str>>();
+        if fingerprints.is_empty() {
+            return Ok(vec![]);
+        }
+        let query_str = format!(
+            "SELECT fingerprint, MAX(read) FROM Items WHERE user_id = ? AND fingerprint IN ({})
+       AND subscription_id IN (SELECT id FROM Subscriptions WHERE user_id = ?)
+       GROUP BY fingerprint",
+            vec!["?"; fingerprints.len()].join(", ")
+        );
+        let mut query = sqlx::query_as::<_, (String, bool)>(&query_str).bind(user_id);
+        for fingerprint in fingerprints {
+            query = query.bind(*fingerprint);
+        }
+        Ok(query.bind(user_id).fetch_all(&self.pool).await?)
+    }
+
+    async fn mark_copies_as(&self, user_id: &str, ids: &Vec<&str>, state: State) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let query_str = format!(
+            "UPDATE ItemStates SET {} = ? WHERE id IN (SELECT id FROM Items
+       WHERE user_id = ? AND fingerprint != ''
+       AND fingerprint IN (SELECT fingerprint FROM Items WHERE user_id = ? AND id IN ({})))",
+            state.column(),
+            vec!["?"; ids.len()].join(", ")
+        );
+        let mut query = sqlx::query(&query_str)
+            .bind(state.value())
+            .bind(user_id)
+            .bind(user_id);
+        for id in ids {
+            query = query.bind(id_str_to_i64(id));
+        }
+        query.execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn mark_copies_of_read_items(&self, user_id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE ItemStates SET read = true WHERE id IN (SELECT id FROM Items
+       WHERE user_id = ? AND read = false AND fingerprint != ''
+       AND fingerprint IN (SELECT fingerprint FROM Items WHERE user_id = ? AND read = true))",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_items(
+        &self,
+        user_id: &str,
+        subscription_id: &str,
+        clean_up: &CleanUp,
+    ) -> Result<()> {
+        let mut conditions = vec![];
+        if clean_up.older_than_ms.is_some() {
+            conditions.push("created_at_ms <= ?");
+        }
+        if clean_up.max_items.is_some() {
+            conditions.push(
+                "id NOT IN (SELECT id FROM Items WHERE user_id = ? AND subscription_id = ?
+         ORDER BY created_at_ms DESC, id DESC LIMIT ?)",
+            );
+        }
+        if conditions.is_empty() {
+            return Ok(());
+        }
+        let condition = format!(
+            "user_id = ? AND subscription_id = ?{} AND ({})",
+            if clean_up.keep_starred {
+                " AND NOT starred"
+            } else {
+                ""
+            },
+            conditions.join(" OR ")
+        );
+        let mut tx = self.pool.begin().await?;
+        for query_str in [
+            format!(
+                "DELETE FROM ItemStatesFts WHERE rowid IN (SELECT id FROM Items WHERE {})",
+                condition
+            ),
+            format!(
+                "DELETE FROM ItemTags WHERE item_id IN (SELECT id FROM Items WHERE {})",
+                condition
+            ),
+            format!(
+                "DELETE FROM ItemStates WHERE id IN (SELECT id FROM Items WHERE {})",
+                condition
+            ),
+        ] {
+            let mut query = sqlx::query(&query_str).bind(user_id).bind(subscription_id);
+            if let Some(older_than_ms) = clean_up.older_than_ms {
+                query = query.bind(older_than_ms);
+            }
+            if let Some(max_items) = clean_up.max_items {
+                query = query.bind(user_id).bind(subscription_id).bind(max_items);
+            }
+            query.execute(&mut tx).await?;
+        }
+        delete_orphan_entries(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_unsubscribed_items(&self, older_than_ms: i64) -> Result<()> {
+        let condition = "created_at_ms <= ? AND NOT EXISTS (SELECT 1 FROM Subscriptions
+         WHERE Subscriptions.user_id = Items.user_id AND Subscriptions.id = Items.subscription_id)";
+        let mut tx = self.pool.begin().await?;
+        for query_str in [
+            format!(
+                "DELETE FROM ItemStatesFts WHERE rowid IN (SELECT id FROM Items WHERE {})",
+                condition
+            ),
+            format!(
+                "DELETE FROM ItemTags WHERE item_id IN (SELECT id FROM Items WHERE {})",
+                condition
+            ),
+            format!(
+                "DELETE FROM ItemStates WHERE id IN (SELECT id FROM Items WHERE {})",
+                condition
+            ),
+        ] {
+            sqlx::query(&query_str)
+                .bind(older_than_ms)
+                .execute(&mut tx)
+                .await?;
+        }
+        delete_orphan_entries(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     async fn mark_as(&self, item_id: ItemId, state: State) -> Result<()> {
         let query = format!(
-            "UPDATE Items SET {} = ? WHERE user_id = ? AND subscription_id = ? AND id = ?",
+            "UPDATE ItemStates SET {} = ? WHERE id IN (SELECT id FROM Items
+       WHERE user_id = ? AND subscription_id = ? AND id = ?)",
             state.column()
         );
         sqlx::query(&query)
@@ -355,7 +1014,7 @@ impl ItemRepository for ItemRepositorySqlite {
             .collect::<Vec<&str>>()
             .join("OR");
         let query_str = format!(
-            "UPDATE Items SET {} = ? WHERE user_id = ? AND {}",
+            "UPDATE ItemStates SET {} = ? WHERE user_id = ? AND ({})",
             state.column(),
             conditions
         );
@@ -368,7 +1027,7 @@ impl ItemRepository for ItemRepositorySqlite {
     }
 
     async fn mark_all_as_read(&self, user_id: &str) -> Result<()> {
-        sqlx::query("UPDATE Items SET read = true WHERE user_id = ?")
+        sqlx::query("UPDATE ItemStates SET read = true WHERE user_id = ?")
             .bind(user_id)
             .execute(&self.pool)
             .await?;
@@ -376,13 +1035,74 @@ impl ItemRepository for ItemRepositorySqlite {
     }
 
     async fn mark_older_as_read(&self, user_id: &str, older_than: i64) -> Result<()> {
-        sqlx::query("UPDATE Items SET read = true WHERE user_id = ? AND created_at_ms <= ?")
+        sqlx::query(
+            "UPDATE ItemStates SET read = true WHERE id IN (SELECT id FROM Items
+       WHERE user_id = ? AND created_at_ms <= ?)",
+        )
+        .bind(user_id)
+        .bind(older_than)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn mark_subscriptions_as_read(
+        &self,
+        user_id: &str,
+        subscription_ids: &Vec<&str>,
+        older_than: Option<i64>,
+    ) -> Result<()> {
+        if subscription_ids.is_empty() {
+            return Ok(());
+        }
+        let query_str = format!(
+            "UPDATE ItemStates SET read = true WHERE id IN (SELECT id FROM Items
+       WHERE user_id = ? AND created_at_ms <= ? AND ({}))",
+            subscription_ids
+                .iter()
+                .map(|_| "subscription_id = ?")
+                .collect::<Vec<&str>>()
+                .join(" OR ")
+        );
+        let mut query = sqlx::query(&query_str)
             .bind(user_id)
-            .bind(older_than)
-            .execute(&self.pool)
-            .await?;
+            .bind(older_than.unwrap_or(i64::MAX));
+        for subscription_id in subscription_ids {
+            query = query.bind(subscription_id);
+        }
+        query.execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn mark_tagged_as_read(
+        &self,
+        user_id: &str,
+        tag: &str,
+        older_than: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE ItemStates SET read = true WHERE id IN (SELECT id FROM Items
+       WHERE user_id = ? AND created_at_ms <= ?
+       AND id IN (SELECT item_id FROM ItemTags WHERE tag = ?))",
+        )
+        .bind(user_id)
+        .bind(older_than.unwrap_or(i64::MAX))
+        .bind(tag)
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
+
+    async fn count_unread_items(&self, user_id: &str) -> Result<Vec<UnreadCount>> {
+        let counts = sqlx::query_as::<_, UnreadCount>(
+            "SELECT subscription_id, COUNT(*) AS count, MAX(created_at_ms) AS newest_created_at_ms
+       FROM Items WHERE user_id = ? AND read = false GROUP BY subscription_id",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(counts)
+    }
 }
 
 pub async fn new_item_repository(
@@ -404,14 +1124,48 @@ mod tests {
             external_id,
             "title",
             "content",
+            "summary",
             "author",
             "url",
             created_at_ms,
         )
     }
 
-    #[tokio::test]
-    pub async fn insert_items_should_succeed() {
+    fn new_copy(subscription_id: &str, external_id: &str, url: &str) -> Item {
+        Item::new_item(
+            "user_id",
+            subscription_id,
+            external_id,
+            "Title",
+            "",
+            "",
+            "",
+            url,
+            1,
+        )
+    }
+
+    fn new_subscription(feed_url: &str) -> crate::database::subscriptions::Subscription {
+        crate::database::subscriptions::Subscription {
+            user_id: "user_id".to_string(),
+            id: format!("feed/{}", feed_url),
+            url: "url".to_string(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            feed_url: feed_url.to_string(),
+            joined_categories: String::new(),
+            last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
+        }
+    }
+
+    #[tokio::test]
+    pub async fn insert_items_should_succeed() {
         let repository = new_item_repository(in_memory_pool().await).await.unwrap();
         let items = vec![
             new_fake_item("1", 1),
@@ -433,10 +1187,12 @@ mod tests {
         let mut items = vec![new_fake_item("1", 1), new_fake_item("2", 2)];
         repository.insert_items(items.clone()).await.unwrap();
         // Insert same items again.
-        repository
+        let inserted = repository
             .insert_items(vec![new_fake_item("1", 1), new_fake_item("3", 3)])
             .await
             .unwrap();
+        assert_eq!(inserted, vec![new_fake_item("3", 3)]);
+        assert!(inserted[0].id > 0);
         items = repository
             .get_items("user_id", PageOption::<String>::new(10, false))
             .await
@@ -583,4 +1339,666 @@ mod tests {
             items
         );
     }
+
+    #[tokio::test]
+    pub async fn mark_subscriptions_as_read_should_succeed() {
+        let repository = new_item_repository(in_memory_pool().await).await.unwrap();
+        let mut other_item = new_fake_item("4", 4);
+        other_item.subscription_id = "subscription_id_2".to_string();
+        repository
+            .insert_items(vec![
+                new_fake_item("1", 1),
+                new_fake_item("2", 2),
+                new_fake_item("3", 3),
+                other_item,
+            ])
+            .await
+            .unwrap();
+
+        repository
+            .mark_subscriptions_as_read("user_id", &vec!["subscription_id"], Some(2))
+            .await
+            .unwrap();
+        let unread = repository
+            .get_unread_items("user_id", PageOption::<String>::new(10, false))
+            .await
+            .unwrap()
+            .items;
+        assert_eq!(
+            unread
+                .iter()
+                .map(|item| item.external_id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["3", "4"]
+        );
+
+        repository
+            .mark_subscriptions_as_read("user_id", &vec!["subscription_id_2"], None)
+            .await
+            .unwrap();
+        let unread = repository
+            .get_unread_items("user_id", PageOption::<String>::new(10, false))
+            .await
+            .unwrap()
+            .items;
+        assert_eq!(unread.len(), 1);
+    }
+
+    #[tokio::test]
+    pub async fn get_filtered_items_should_succeed() {
+        let repository = new_item_repository(in_memory_pool().await).await.unwrap();
+        let mut other_item = new_fake_item("4", 4);
+        other_item.subscription_id = "subscription_id_2".to_string();
+        repository
+            .insert_items(vec![
+                new_fake_item("1", 1),
+                new_fake_item("2", 2),
+                new_fake_item("3", 3),
+                other_item,
+            ])
+            .await
+            .unwrap();
+        repository.mark_older_as_read("user_id", 1).await.unwrap();
+        let external_ids = |page: Page<Item, String>| {
+            page.items
+                .into_iter()
+                .map(|item| item.external_id)
+                .collect::<Vec<String>>()
+        };
+
+        let filter = ItemFilter {
+            subscription_ids: Some(vec!["subscription_id".to_string()]),
+            read: Some(false),
+            ..ItemFilter::default()
+        };
+        let page = repository
+            .get_filtered_items("user_id", &filter, PageOption::<String>::new(10, false))
+            .await
+            .unwrap();
+        assert_eq!(external_ids(page), vec!["2", "3"]);
+
+        let filter = ItemFilter {
+            excluded_subscription_ids: vec!["subscription_id".to_string()],
+            ..ItemFilter::default()
+        };
+        let page = repository
+            .get_filtered_items("user_id", &filter, PageOption::<String>::new(10, false))
+            .await
+            .unwrap();
+        assert_eq!(external_ids(page), vec!["4"]);
+
+        let filter = ItemFilter {
+            newer_than_ms: Some(2),
+            older_than_ms: Some(3),
+            ..ItemFilter::default()
+        };
+        let page = repository
+            .get_filtered_items("user_id", &filter, PageOption::<String>::new(1, false))
+            .await
+            .unwrap();
+        assert!(page.next_page_offset.is_some());
+        assert_eq!(external_ids(page), vec!["2"]);
+
+        let filter = ItemFilter {
+            subscription_ids: Some(vec![]),
+            ..ItemFilter::default()
+        };
+        let page = repository
+            .get_filtered_items("user_id", &filter, PageOption::<String>::new(10, false))
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn tagged_items_should_be_filtered_by_label() {
+        let repository = new_item_repository(in_memory_pool().await).await.unwrap();
+        let mut other_item = new_fake_item("3", 3);
+        other_item.subscription_id = "subscription_id_2".to_string();
+        let items = repository
+            .insert_items(vec![
+                new_fake_item("1", 1),
+                new_fake_item("2", 2),
+                other_item,
+            ])
+            .await
+            .unwrap();
+        let ids = items
+            .iter()
+            .map(|item| item.id.to_string())
+            .collect::<Vec<String>>();
+        let tech = "user/-/label/Tech";
+        repository
+            .tag_items("user_id", &vec![ids[0].as_str(), ids[2].as_str()], tech)
+            .await
+            .unwrap();
+        repository
+            .tag_items("user_id", &vec![ids[0].as_str()], "user/-/label/Later")
+            .await
+            .unwrap();
+        // Items of other users are not tagged.
+        repository
+            .tag_items("other_user", &vec![ids[1].as_str()], tech)
+            .await
+            .unwrap();
+        assert_eq!(
+            repository
+                .get_item_tags(&vec![items[0].id, items[1].id])
+                .await
+                .unwrap(),
+            vec![
+                (items[0].id, "user/-/label/Later".to_string()),
+                (items[0].id, tech.to_string())
+            ]
+        );
+        let external_ids = |page: Page<Item, String>| {
+            page.items
+                .into_iter()
+                .map(|item| item.external_id)
+                .collect::<Vec<String>>()
+        };
+
+        // The label has the tagged items, and the items of its subscriptions.
+        let label = LabelFilter {
+            label: tech.to_string(),
+            subscription_ids: vec!["subscription_id_2".to_string()],
+        };
+        let filter = ItemFilter {
+            labels: vec![label.clone()],
+            ..ItemFilter::default()
+        };
+        let page = repository
+            .get_filtered_items("user_id", &filter, PageOption::<String>::new(10, false))
+            .await
+            .unwrap();
+        assert_eq!(external_ids(page), vec!["1", "3"]);
+        let filter = ItemFilter {
+            excluded_labels: vec![label],
+            ..ItemFilter::default()
+        };
+        let page = repository
+            .get_filtered_items("user_id", &filter, PageOption::<String>::new(10, false))
+            .await
+            .unwrap();
+        assert_eq!(external_ids(page), vec!["2"]);
+
+        repository
+            .untag_items("user_id", &vec![ids[2].as_str()], tech)
+            .await
+            .unwrap();
+        repository
+            .rename_tag("user_id", "user/-/label/Later", tech)
+            .await
+            .unwrap();
+        assert_eq!(repository.list_tags("user_id").await.unwrap(), vec![tech]);
+        repository
+            .mark_tagged_as_read("user_id", tech, None)
+            .await
+            .unwrap();
+        let read = repository
+            .get_read_items("user_id", PageOption::<String>::new(10, false))
+            .await
+            .unwrap();
+        assert_eq!(external_ids(read), vec!["1"]);
+        repository.remove_tag("user_id", tech).await.unwrap();
+        assert!(repository.list_tags("user_id").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn search_items_should_follow_inserts_and_deletes() {
+        let repository = new_item_repository(in_memory_pool().await).await.unwrap();
+        let mut rust_item = new_fake_item("1", 1);
+        rust_item.title = "Rust 1.66 is released".to_string();
+        let mut other_item = new_fake_item("2", 2);
+        other_item.content = "Nothing about \"rust\" here, only Go".to_string();
+        repository
+            .insert_items(vec![rust_item, other_item, new_fake_item("3", 3)])
+            .await
+            .unwrap();
+        // Items already inserted are not indexed twice.
+        repository
+            .insert_items(vec![new_fake_item("1", 1)])
+            .await
+            .unwrap();
+        let search = |words: &str| ItemFilter {
+            search: Some(words.to_string()),
+            ..ItemFilter::default()
+        };
+        let external_ids = |page: Page<Item, String>| {
+            page.items
+                .into_iter()
+                .map(|item| item.external_id)
+                .collect::<Vec<String>>()
+        };
+
+        let page = repository
+            .get_filtered_items(
+                "user_id",
+                &search("rust"),
+                PageOption::<String>::new(10, false),
+            )
+            .await
+            .unwrap();
+        assert_eq!(external_ids(page), vec!["1", "2"]);
+        let page = repository
+            .get_filtered_items(
+                "user_id",
+                &search("rust \"go"),
+                PageOption::<String>::new(10, false),
+            )
+            .await
+            .unwrap();
+        assert_eq!(external_ids(page), vec!["2"]);
+        let page = repository
+            .get_filtered_items(
+                "user_id",
+                &search("RUST AND"),
+                PageOption::<String>::new(10, false),
+            )
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+        let page = repository
+            .get_filtered_items(
+                "user_id",
+                &search("  "),
+                PageOption::<String>::new(10, false),
+            )
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+
+        repository
+            .delete_items(
+                "user_id",
+                "subscription_id",
+                &CleanUp {
+                    older_than_ms: Some(1),
+                    ..CleanUp::default()
+                },
+            )
+            .await
+            .unwrap();
+        let page = repository
+            .get_filtered_items(
+                "user_id",
+                &search("rust"),
+                PageOption::<String>::new(10, false),
+            )
+            .await
+            .unwrap();
+        assert_eq!(external_ids(page), vec!["2"]);
+    }
+
+    async fn subscription_external_ids(
+        repository: &(dyn ItemRepository + Send + Sync),
+    ) -> Vec<String> {
+        repository
+            .get_filtered_items(
+                "user_id",
+                &ItemFilter {
+                    subscription_ids: Some(vec!["subscription_id".to_string()]),
+                    ..ItemFilter::default()
+                },
+                PageOption::<String>::new(10, false),
+            )
+            .await
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|item| item.external_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    pub async fn delete_items_should_follow_clean_up() {
+        let repository = new_item_repository(in_memory_pool().await).await.unwrap();
+        let mut other_item = new_fake_item("1", 1);
+        other_item.subscription_id = "other_subscription_id".to_string();
+        let inserted = repository
+            .insert_items(vec![
+                other_item,
+                new_fake_item("1", 1),
+                new_fake_item("2", 2),
+                new_fake_item("3", 3),
+                new_fake_item("4", 4),
+            ])
+            .await
+            .unwrap();
+        let starred = inserted
+            .iter()
+            .find(|item| item.subscription_id == "subscription_id" && item.external_id == "1")
+            .unwrap();
+        repository
+            .mark_as(starred.key(), State::STARRED)
+            .await
+            .unwrap();
+
+        let clean_up = CleanUp {
+            older_than_ms: Some(2),
+            max_items: None,
+            keep_starred: true,
+        };
+        repository
+            .delete_items("user_id", "subscription_id", &clean_up)
+            .await
+            .unwrap();
+        assert_eq!(
+            subscription_external_ids(repository.as_ref()).await,
+            vec!["1", "3", "4"]
+        );
+
+        let clean_up = CleanUp {
+            older_than_ms: None,
+            max_items: Some(1),
+            keep_starred: false,
+        };
+        repository
+            .delete_items("user_id", "subscription_id", &clean_up)
+            .await
+            .unwrap();
+        assert_eq!(
+            subscription_external_ids(repository.as_ref()).await,
+            vec!["4"]
+        );
+        // Items of other subscriptions are not affected.
+        assert_eq!(
+            repository
+                .get_items("user_id", PageOption::<String>::new(10, false))
+                .await
+                .unwrap()
+                .items
+                .len(),
+            2
+        );
+
+        // No subscription exists, so all items are unsubscribed.
+        repository.delete_unsubscribed_items(3).await.unwrap();
+        assert_eq!(
+            subscription_external_ids(repository.as_ref()).await,
+            vec!["4"]
+        );
+        repository.delete_unsubscribed_items(4).await.unwrap();
+        assert!(repository
+            .get_items("user_id", PageOption::<String>::new(10, false))
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn update_item_content_should_keep_summary() {
+        let repository = new_item_repository(in_memory_pool().await).await.unwrap();
+        let inserted = repository
+            .insert_items(vec![new_fake_item("1", 1)])
+            .await
+            .unwrap();
+        repository
+            .update_item_content(inserted[0].id, " <p>The whole article</p> ")
+            .await
+            .unwrap();
+        let item = &repository
+            .get_items("user_id", PageOption::<String>::new(10, false))
+            .await
+            .unwrap()
+            .items[0];
+        assert_eq!(item.content, "<p>The whole article</p>");
+        assert_eq!(item.summary, "summary");
+
+        let page = repository
+            .get_filtered_items(
+                "user_id",
+                &ItemFilter {
+                    search: Some("article".to_string()),
+                    ..ItemFilter::default()
+                },
+                PageOption::<String>::new(10, false),
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+    }
+
+    #[tokio::test]
+    pub async fn count_unread_items_should_group_by_subscription() {
+        let repository = new_item_repository(in_memory_pool().await).await.unwrap();
+        let mut other_item = new_fake_item("4", 4);
+        other_item.subscription_id = "subscription_id_2".to_string();
+        repository
+            .insert_items(vec![
+                new_fake_item("1", 1),
+                new_fake_item("2", 2),
+                new_fake_item("3", 3),
+                other_item,
+            ])
+            .await
+            .unwrap();
+        repository.mark_older_as_read("user_id", 1).await.unwrap();
+
+        let mut counts = repository.count_unread_items("user_id").await.unwrap();
+        counts.sort_by(|a, b| a.subscription_id.cmp(&b.subscription_id));
+        assert_eq!(
+            counts,
+            vec![
+                UnreadCount {
+                    subscription_id: "subscription_id".to_string(),
+                    count: 2,
+                    newest_created_at_ms: 3,
+                },
+                UnreadCount {
+                    subscription_id: "subscription_id_2".to_string(),
+                    count: 1,
+                    newest_created_at_ms: 4,
+                },
+            ]
+        );
+        assert!(repository
+            .count_unread_items("user_id_2")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn copies_should_be_marked_together() {
+        use crate::database::subscriptions::new_subscription_repository;
+
+        let pool = in_memory_pool().await;
+        let repository = new_item_repository(pool.clone()).await.unwrap();
+        let subscription_repository = new_subscription_repository(pool).await.unwrap();
+        for feed_url in ["a", "b"] {
+            subscription_repository
+                .insert_subscription(new_subscription(feed_url))
+                .await
+                .unwrap();
+        }
+        let items = repository
+            .insert_items(vec![
+                new_copy("feed/a", "1", "https://blog.com/1"),
+                new_copy("feed/b", "2", "https://www.blog.com/1?utm_source=b"),
+                new_copy("feed/b", "3", "https://blog.com/3"),
+            ])
+            .await
+            .unwrap();
+        let fingerprint = items[0].fingerprint.as_str();
+        assert_eq!(items[1].fingerprint, fingerprint);
+        assert_eq!(
+            repository
+                .find_fingerprints("user_id", &vec![fingerprint, "other", ""])
+                .await
+                .unwrap(),
+            vec![(fingerprint.to_string(), false)]
+        );
+
+        let id = items[0].id.to_string();
+        repository
+            .mark_items_as("user_id", &vec![id.as_str()], State::READ)
+            .await
+            .unwrap();
+        repository
+            .mark_copies_of_read_items("user_id")
+            .await
+            .unwrap();
+        let read = |items: Vec<Item>| {
+            items
+                .into_iter()
+                .map(|item| (item.external_id, item.read))
+                .collect::<Vec<(String, bool)>>()
+        };
+        let all = PageOption::<String>::new(10, false);
+        assert_eq!(
+            read(repository.get_items("user_id", all).await.unwrap().items),
+            vec![
+                ("1".to_string(), true),
+                ("2".to_string(), true),
+                ("3".to_string(), false)
+            ]
+        );
+        assert_eq!(
+            repository
+                .find_fingerprints("user_id", &vec![fingerprint])
+                .await
+                .unwrap(),
+            vec![(fingerprint.to_string(), true)]
+        );
+
+        repository
+            .mark_copies_as("user_id", &vec![id.as_str()], State::UNREAD)
+            .await
+            .unwrap();
+        let all = PageOption::<String>::new(10, false);
+        assert!(repository
+            .get_items("user_id", all)
+            .await
+            .unwrap()
+            .items
+            .iter()
+            .all(|item| !item.read));
+    }
+
+    #[tokio::test]
+    pub async fn missing_fingerprints_should_be_filled() {
+        let pool = in_memory_pool().await;
+        let repository = new_item_repository(pool.clone()).await.unwrap();
+        let items = repository
+            .insert_items(vec![
+                new_copy("feed/a", "1", "https://blog.com/1"),
+                new_copy("feed/a", "2", ""),
+            ])
+            .await
+            .unwrap();
+        sqlx::query("UPDATE Entries SET fingerprint = ''")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        fill_fingerprints(&pool).await.unwrap();
+        let all = PageOption::<String>::new(10, false);
+        assert_eq!(
+            repository
+                .get_items("user_id", all)
+                .await
+                .unwrap()
+                .items
+                .into_iter()
+                .map(|item| item.fingerprint)
+                .collect::<Vec<String>>(),
+            vec![items[0].fingerprint.clone(), String::new()]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn items_of_users_should_share_entries() {
+        let pool = in_memory_pool().await;
+        let repository = new_item_repository(pool.clone()).await.unwrap();
+        let new_item = |user_id: &str| {
+            Item::new_item(user_id, "feed/a", "1", "Title", "content", "", "", "url", 1)
+        };
+        let items = repository
+            .insert_items(vec![new_item("user_id"), new_item("user_id_2")])
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 2);
+        let count_entries = || async {
+            sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM Entries")
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+                .0
+        };
+        assert_eq!(count_entries().await, 1);
+
+        repository
+            .mark_items_as(
+                "user_id",
+                &vec![items[0].id.to_string().as_str()],
+                State::READ,
+            )
+            .await
+            .unwrap();
+        repository
+            .update_item_content(items[0].id, "extracted")
+            .await
+            .unwrap();
+        // Only the user who extracted the content sees it.
+        for (user_id, content, read) in [
+            ("user_id", "extracted", true),
+            ("user_id_2", "content", false),
+        ] {
+            let all = PageOption::<String>::new(10, false);
+            let items = repository.get_items(user_id, all).await.unwrap().items;
+            assert_eq!((items[0].content.as_str(), items[0].read), (content, read));
+        }
+        let search = ItemFilter {
+            search: Some("extracted".to_string()),
+            ..ItemFilter::default()
+        };
+        for (user_id, found) in [("user_id", 1), ("user_id_2", 0)] {
+            let page = repository
+                .get_filtered_items(user_id, &search, PageOption::<String>::new(10, false))
+                .await
+                .unwrap();
+            assert_eq!(page.items.len(), found);
+        }
+
+        let clean_up = CleanUp {
+            max_items: Some(0),
+            ..CleanUp::default()
+        };
+        repository
+            .delete_items("user_id", "feed/a", &clean_up)
+            .await
+            .unwrap();
+        assert_eq!(count_entries().await, 1);
+        repository
+            .delete_items("user_id_2", "feed/a", &clean_up)
+            .await
+            .unwrap();
+        assert_eq!(count_entries().await, 0);
+    }
+
+    #[tokio::test]
+    pub async fn invalid_offsets_should_be_rejected() {
+        let repository = new_item_repository(in_memory_pool().await).await.unwrap();
+        let page_option = |offset: &str| PageOption {
+            offset: Some(offset.to_string()),
+            ..PageOption::<String>::new(10, true)
+        };
+        assert!(repository
+            .get_items("user_id", page_option("1-2"))
+            .await
+            .is_ok());
+        for offset in ["", "1", "a-1", "1-b", "1-2-3"] {
+            let err = repository
+                .get_items("user_id", page_option(offset))
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(
+                err.downcast_ref::<Errors>(),
+                Some(Errors::InvalidRequest { .. })
+            ));
+        }
+    }
 }
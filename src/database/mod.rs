@@ -1,7 +1,12 @@
+pub mod enclosures;
+pub mod feed_states;
 pub mod items;
+pub mod retentions;
+pub mod rules;
 pub mod subscriptions;
 
 use log::LevelFilter;
+use sqlx::migrate::MigrateError;
 use sqlx::{sqlite::SqliteConnectOptions, ConnectOptions, SqlitePool};
 use std::str::FromStr;
 
@@ -9,13 +14,17 @@ pub async fn in_memory_pool() -> SqlitePool {
     let mut option = SqliteConnectOptions::from_str("sqlite::memory:").unwrap();
     option.log_statements(LevelFilter::Off);
     let pool = SqlitePool::connect_with(option).await.unwrap();
-    sqlx::migrate!("./migrations")
-        .run(&pool)
+    migrate(&pool)
         .await
         .expect("Failed to migrate the database.");
     pool
 }
 
+/// Applies the migrations which are not applied to the database yet.
+pub async fn migrate(pool: &SqlitePool) -> Result<(), MigrateError> {
+    sqlx::migrate!("./migrations").run(pool).await
+}
+
 pub async fn db_pool(path: &str) -> SqlitePool {
     let db = format!("sqlite:{}", path);
     let mut option = SqliteConnectOptions::from_str(&db)
@@ -0,0 +1,249 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row, SqlitePool};
+
+/// A limit of a retention override. In json, a missing limit is inherited,
+/// and a null one removes the inherited limit.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum Limit {
+    #[default]
+    Inherited,
+    Unlimited,
+    Limited(i64),
+}
+
+impl Limit {
+    pub fn is_inherited(&self) -> bool {
+        *self == Limit::Inherited
+    }
+
+    pub fn or_inherited(self, inherited: Option<i64>) -> Option<i64> {
+        match self {
+            Limit::Inherited => inherited,
+            Limit::Unlimited => None,
+            Limit::Limited(limit) => Some(limit),
+        }
+    }
+
+    // Limits are positive, so 0 is stored for no limit.
+    fn from_column(value: Option<i64>) -> Limit {
+        match value {
+            None => Limit::Inherited,
+            Some(0) => Limit::Unlimited,
+            Some(limit) => Limit::Limited(limit),
+        }
+    }
+
+    fn to_column(self) -> Option<i64> {
+        match self {
+            Limit::Inherited => None,
+            Limit::Unlimited => Some(0),
+            Limit::Limited(limit) => Some(limit),
+        }
+    }
+}
+
+impl Serialize for Limit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Limit::Limited(limit) => serializer.serialize_some(limit),
+            _ => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Limit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(match Option::<i64>::deserialize(deserializer)? {
+            Some(limit) => Limit::Limited(limit),
+            None => Limit::Unlimited,
+        })
+    }
+}
+
+/// Overrides of the configured retention. Fields left empty are inherited,
+/// from the user for a subscription, and from the configuration for a user.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Retention {
+    #[serde(skip)]
+    pub user_id: String,
+    // Empty for the retention of all the subscriptions of the user.
+    #[serde(default)]
+    pub subscription_id: String,
+    #[serde(default, skip_serializing_if = "Limit::is_inherited")]
+    pub max_age_days: Limit,
+    #[serde(default, skip_serializing_if = "Limit::is_inherited")]
+    pub max_items: Limit,
+    pub keep_starred: Option<bool>,
+}
+
+impl FromRow<'_, SqliteRow> for Retention {
+    fn from_row(row: &SqliteRow) -> std::result::Result<Self, sqlx::Error> {
+        Ok(Retention {
+            user_id: row.try_get("user_id")?,
+            subscription_id: row.try_get("subscription_id")?,
+            max_age_days: Limit::from_column(row.try_get("max_age_days")?),
+            max_items: Limit::from_column(row.try_get("max_items")?),
+            keep_starred: row.try_get("keep_starred")?,
+        })
+    }
+}
+
+#[async_trait]
+pub trait RetentionRepository {
+    async fn upsert_retention(&self, retention: Retention) -> Result<()>;
+    async fn list_user_retentions(&self, user_id: &str) -> Result<Vec<Retention>>;
+    async fn list_all_retentions(&self) -> Result<Vec<Retention>>;
+    async fn delete_retention(&self, user_id: &str, subscription_id: &str) -> Result<()>;
+}
+
+struct RetentionRepositorySqlite {
+    pool: SqlitePool,
+}
+
+unsafe impl Send for RetentionRepositorySqlite {}
+unsafe impl Sync for RetentionRepositorySqlite {}
+
+impl RetentionRepositorySqlite {
+    pub async fn new(pool: SqlitePool) -> Result<RetentionRepositorySqlite> {
+        Ok(RetentionRepositorySqlite { pool })
+    }
+}
+
+#[async_trait]
+impl RetentionRepository for RetentionRepositorySqlite {
+    async fn upsert_retention(&self, retention: Retention) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO Retentions
+      (user_id, subscription_id, max_age_days, max_items, keep_starred)
+      VALUES (?,?,?,?,?)
+      ON CONFLICT(user_id, subscription_id) DO UPDATE SET
+        max_age_days = excluded.max_age_days,
+        max_items = excluded.max_items,
+        keep_starred = excluded.keep_starred",
+        )
+        .bind(&retention.user_id)
+        .bind(&retention.subscription_id)
+        .bind(retention.max_age_days.to_column())
+        .bind(retention.max_items.to_column())
+        .bind(retention.keep_starred)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_user_retentions(&self, user_id: &str) -> Result<Vec<Retention>> {
+        let retentions = sqlx::query_as::<_, Retention>(
+            "SELECT * FROM Retentions WHERE user_id = ? ORDER BY subscription_id",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(retentions)
+    }
+
+    async fn list_all_retentions(&self) -> Result<Vec<Retention>> {
+        let retentions = sqlx::query_as::<_, Retention>("SELECT * FROM Retentions")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(retentions)
+    }
+
+    async fn delete_retention(&self, user_id: &str, subscription_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM Retentions WHERE user_id = ? AND subscription_id = ?")
+            .bind(user_id)
+            .bind(subscription_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+pub async fn new_retention_repository(
+    pool: SqlitePool,
+) -> Result<Box<dyn RetentionRepository + Send + Sync>> {
+    let repository = RetentionRepositorySqlite::new(pool).await?;
+    Ok(Box::new(repository))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::in_memory_pool;
+    use super::*;
+
+    #[tokio::test]
+    pub async fn upsert_and_list_retentions_should_succeed() {
+        let repository = new_retention_repository(in_memory_pool().await)
+            .await
+            .unwrap();
+        let mut retention = Retention {
+            user_id: "user_id".to_string(),
+            subscription_id: "feed/a".to_string(),
+            max_age_days: Limit::Limited(30),
+            max_items: Limit::Inherited,
+            keep_starred: Some(false),
+        };
+        repository
+            .upsert_retention(retention.clone())
+            .await
+            .unwrap();
+        retention.max_age_days = Limit::Unlimited;
+        retention.max_items = Limit::Limited(100);
+        repository
+            .upsert_retention(retention.clone())
+            .await
+            .unwrap();
+        let user_retention = Retention {
+            user_id: "user_id".to_string(),
+            max_age_days: Limit::Limited(7),
+            ..Retention::default()
+        };
+        repository
+            .upsert_retention(user_retention.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.list_user_retentions("user_id").await.unwrap(),
+            vec![user_retention.clone(), retention]
+        );
+        assert!(repository
+            .list_user_retentions("other_user")
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(repository.list_all_retentions().await.unwrap().len(), 2);
+
+        repository
+            .delete_retention("user_id", "feed/a")
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.list_all_retentions().await.unwrap(),
+            vec![user_retention]
+        );
+    }
+
+    #[test]
+    fn limits_should_be_serialized_by_presence() {
+        let retention: Retention =
+            serde_json::from_str(r#"{"maxAgeDays": null, "maxItems": 10}"#).unwrap();
+        assert_eq!(retention.max_age_days, Limit::Unlimited);
+        assert_eq!(retention.max_items, Limit::Limited(10));
+        let retention: Retention = serde_json::from_str("{}").unwrap();
+        assert_eq!(retention.max_age_days, Limit::Inherited);
+
+        let json = serde_json::to_value(Retention {
+            max_age_days: Limit::Unlimited,
+            ..Retention::default()
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"subscriptionId": "", "maxAgeDays": null, "keepStarred": null})
+        );
+    }
+}
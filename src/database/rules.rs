@@ -0,0 +1,225 @@
+use crate::common::error::Errors;
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+/// The part of an item, or of its subscription, which a rule matches.
+#[derive(sqlx::Type, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RuleField {
+    Title,
+    Content,
+    Author,
+    Url,
+    // The title or the url of the subscription.
+    Feed,
+    // The labels of the subscription.
+    Label,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MatchType {
+    Substring,
+    Regex,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Read,
+    Star,
+    Tag,
+    Drop,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    #[serde(default)]
+    pub id: String,
+    #[serde(skip)]
+    pub user_id: String,
+    pub name: String,
+    pub field: RuleField,
+    pub match_type: MatchType,
+    pub pattern: String,
+    pub action: RuleAction,
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub created_at_ms: i64,
+}
+
+#[async_trait]
+pub trait RuleRepository {
+    async fn insert_rule(&self, rule: &Rule) -> Result<()>;
+
+    async fn update_rule(&self, rule: &Rule) -> Result<()>;
+
+    async fn delete_rule(&self, user_id: &str, id: &str) -> Result<()>;
+
+    async fn list_user_rules(&self, user_id: &str) -> Result<Vec<Rule>>;
+
+    async fn list_all_rules(&self) -> Result<Vec<Rule>>;
+}
+
+struct RuleRepositorySqlite {
+    pool: SqlitePool,
+}
+
+unsafe impl Send for RuleRepositorySqlite {}
+unsafe impl Sync for RuleRepositorySqlite {}
+
+impl RuleRepositorySqlite {
+    pub async fn new(pool: SqlitePool) -> Result<RuleRepositorySqlite> {
+        Ok(RuleRepositorySqlite { pool })
+    }
+
+    fn check_rule_affected(id: &str, rows_affected: u64) -> Result<()> {
+        if rows_affected == 0 {
+            Err(Error::new(Errors::RuleNotFound { id: id.to_string() }))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[async_trait]
+impl RuleRepository for RuleRepositorySqlite {
+    async fn insert_rule(&self, rule: &Rule) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO Rules
+      (id, user_id, name, field, match_type, pattern, action, tag, created_at_ms)
+      VALUES (?,?,?,?,?,?,?,?,?)",
+        )
+        .bind(&rule.id)
+        .bind(&rule.user_id)
+        .bind(&rule.name)
+        .bind(rule.field)
+        .bind(rule.match_type)
+        .bind(&rule.pattern)
+        .bind(rule.action)
+        .bind(&rule.tag)
+        .bind(rule.created_at_ms)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update_rule(&self, rule: &Rule) -> Result<()> {
+        let result = sqlx::query(
+            "UPDATE Rules SET name = ?, field = ?, match_type = ?, pattern = ?, action = ?, tag = ?
+      WHERE user_id = ? AND id = ?",
+        )
+        .bind(&rule.name)
+        .bind(rule.field)
+        .bind(rule.match_type)
+        .bind(&rule.pattern)
+        .bind(rule.action)
+        .bind(&rule.tag)
+        .bind(&rule.user_id)
+        .bind(&rule.id)
+        .execute(&self.pool)
+        .await?;
+        Self::check_rule_affected(&rule.id, result.rows_affected())
+    }
+
+    async fn delete_rule(&self, user_id: &str, id: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM Rules WHERE user_id = ? AND id = ?")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Self::check_rule_affected(id, result.rows_affected())
+    }
+
+    async fn list_user_rules(&self, user_id: &str) -> Result<Vec<Rule>> {
+        let rules = sqlx::query_as::<_, Rule>(
+            "SELECT * FROM Rules WHERE user_id = ? ORDER BY created_at_ms, id",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rules)
+    }
+
+    async fn list_all_rules(&self) -> Result<Vec<Rule>> {
+        let rules = sqlx::query_as::<_, Rule>("SELECT * FROM Rules ORDER BY created_at_ms, id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rules)
+    }
+}
+
+pub async fn new_rule_repository(
+    pool: SqlitePool,
+) -> Result<Box<dyn RuleRepository + Send + Sync>> {
+    let repository = RuleRepositorySqlite::new(pool).await?;
+    Ok(Box::new(repository))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::in_memory_pool;
+    use super::*;
+
+    fn new_rule(user_id: &str, id: &str, created_at_ms: i64) -> Rule {
+        Rule {
+            id: id.to_string(),
+            user_id: user_id.to_string(),
+            name: "rule".to_string(),
+            field: RuleField::Title,
+            match_type: MatchType::Substring,
+            pattern: "sponsored".to_string(),
+            action: RuleAction::Read,
+            tag: None,
+            created_at_ms,
+        }
+    }
+
+    #[tokio::test]
+    pub async fn rules_should_be_updated_and_deleted() {
+        let repository = new_rule_repository(in_memory_pool().await).await.unwrap();
+        let mut rule = new_rule("user_id", "1", 1);
+        repository.insert_rule(&rule).await.unwrap();
+        repository
+            .insert_rule(&new_rule("user_id", "2", 2))
+            .await
+            .unwrap();
+        repository
+            .insert_rule(&new_rule("other_user", "3", 3))
+            .await
+            .unwrap();
+
+        rule.match_type = MatchType::Regex;
+        rule.action = RuleAction::Tag;
+        rule.tag = Some("ads".to_string());
+        repository.update_rule(&rule).await.unwrap();
+        let rules = repository.list_user_rules("user_id").await.unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0], rule);
+
+        repository.delete_rule("user_id", "2").await.unwrap();
+        assert_eq!(
+            repository.list_user_rules("user_id").await.unwrap(),
+            vec![rule]
+        );
+        assert_eq!(repository.list_all_rules().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    pub async fn rules_of_other_users_should_not_be_found() {
+        let repository = new_rule_repository(in_memory_pool().await).await.unwrap();
+        let mut rule = new_rule("other_user", "1", 1);
+        repository.insert_rule(&rule).await.unwrap();
+
+        rule.user_id = "user_id".to_string();
+        assert!(repository.update_rule(&rule).await.is_err());
+        assert!(repository.delete_rule("user_id", "1").await.is_err());
+        assert_eq!(repository.list_all_rules().await.unwrap().len(), 1);
+    }
+}
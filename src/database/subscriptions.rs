@@ -2,6 +2,7 @@ use crate::common::error::Errors;
 use anyhow::{Error, Result};
 use async_trait::async_trait;
 use sqlx::SqlitePool;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
@@ -15,6 +16,13 @@ pub struct Subscription {
     pub feed_url: String,
     pub joined_categories: String,
     pub last_fetch_ms: i64,
+    pub last_success_ms: i64,
+    pub last_error: Option<String>,
+    pub last_http_status: Option<u16>,
+    pub consecutive_failures: i64,
+    pub disabled: bool,
+    // Fetches the linked articles of new items, for feeds with truncated content.
+    pub extract_content: bool,
 }
 
 impl Subscription {
@@ -54,6 +62,22 @@ pub trait SubscriptionRepository {
     async fn insert_subscription(&self, subscription: Subscription) -> Result<()>;
     async fn update_subscription(&self, subscription: Subscription) -> Result<Subscription>;
     async fn remove_subscription(&self, user_id: &str, id: &str) -> Result<()>;
+    async fn record_fetch_success(
+        &self,
+        user_id: &str,
+        id: &str,
+        fetched_at_ms: i64,
+        http_status: u16,
+    ) -> Result<()>;
+    async fn record_fetch_failure(
+        &self,
+        user_id: &str,
+        id: &str,
+        fetched_at_ms: i64,
+        error: &str,
+        http_status: Option<u16>,
+        disable: bool,
+    ) -> Result<()>;
     async fn get_subscription(&self, user_id: &str, id: &str) -> Result<Option<Subscription>>;
     async fn get_subscriptions(
         &self,
@@ -62,6 +86,9 @@ pub trait SubscriptionRepository {
     ) -> Result<HashMap<String, Subscription>>;
     async fn list_user_subscriptions(&self, user_id: &str) -> Result<Vec<Subscription>>;
     async fn list_all_subscriptions(&self) -> Result<Vec<Subscription>>;
+    async fn list_categories(&self, user_id: &str) -> Result<Vec<String>>;
+    async fn rename_category(&self, user_id: &str, from: &str, to: &str) -> Result<()>;
+    async fn remove_category(&self, user_id: &str, category: &str) -> Result<()>;
 }
 
 struct SubscriptionRepositorySqlite {
@@ -75,6 +102,41 @@ impl SubscriptionRepositorySqlite {
     pub async fn new(pool: SqlitePool) -> Result<SubscriptionRepositorySqlite> {
         Ok(SubscriptionRepositorySqlite { pool })
     }
+
+    // Rewrites the categories of every subscription of the user having the category.
+    async fn replace_category(&self, user_id: &str, from: &str, to: Option<&str>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let subscriptions =
+            sqlx::query_as::<_, Subscription>("SELECT * FROM Subscriptions WHERE user_id = ?")
+                .bind(user_id)
+                .fetch_all(&mut tx)
+                .await?;
+        for subscription in subscriptions {
+            let categories = subscription.categories();
+            if !categories.contains(&from) {
+                continue;
+            }
+            let mut replaced: Vec<&str> = vec![];
+            for category in categories {
+                let category = if category == from { to } else { Some(category) };
+                if let Some(category) = category {
+                    if !replaced.contains(&category) {
+                        replaced.push(category);
+                    }
+                }
+            }
+            sqlx::query(
+                "UPDATE Subscriptions SET joined_categories = ? WHERE user_id = ? AND id = ?",
+            )
+            .bind(replaced.join(","))
+            .bind(user_id)
+            .bind(&subscription.id)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -82,8 +144,10 @@ impl SubscriptionRepository for SubscriptionRepositorySqlite {
     async fn insert_subscription(&self, subscription: Subscription) -> Result<()> {
         sqlx::query(
             "INSERT INTO Subscriptions 
-      (user_id, id, url, title, description, feed_url, joined_categories, last_fetch_ms)
-      VALUES (?,?,?,?,?,?,?,?)",
+      (user_id, id, url, title, description, feed_url, joined_categories, last_fetch_ms,
+       last_success_ms, last_error, last_http_status, consecutive_failures, disabled,
+       extract_content)
+      VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?,?)",
         )
         .bind(&subscription.user_id)
         .bind(&subscription.id)
@@ -93,6 +157,12 @@ impl SubscriptionRepository for SubscriptionRepositorySqlite {
         .bind(&subscription.feed_url)
         .bind(&subscription.joined_categories)
         .bind(subscription.last_fetch_ms)
+        .bind(subscription.last_success_ms)
+        .bind(&subscription.last_error)
+        .bind(subscription.last_http_status)
+        .bind(subscription.consecutive_failures)
+        .bind(subscription.disabled)
+        .bind(subscription.extract_content)
         .execute(&self.pool)
         .await?;
         Ok(())
@@ -113,7 +183,13 @@ impl SubscriptionRepository for SubscriptionRepositorySqlite {
         description = ?,
         feed_url = ?,
         joined_categories = ?,
-        last_fetch_ms = ?
+        last_fetch_ms = ?,
+        last_success_ms = ?,
+        last_error = ?,
+        last_http_status = ?,
+        consecutive_failures = ?,
+        disabled = ?,
+        extract_content = ?
         WHERE user_id = ? AND id = ?",
         )
         .bind(&subscription.url)
@@ -122,6 +198,12 @@ impl SubscriptionRepository for SubscriptionRepositorySqlite {
         .bind(&subscription.feed_url)
         .bind(&subscription.joined_categories)
         .bind(subscription.last_fetch_ms)
+        .bind(subscription.last_success_ms)
+        .bind(&subscription.last_error)
+        .bind(subscription.last_http_status)
+        .bind(subscription.consecutive_failures)
+        .bind(subscription.disabled)
+        .bind(subscription.extract_content)
         .bind(&subscription.user_id)
         .bind(&subscription.id)
         .execute(&self.pool)
@@ -138,6 +220,61 @@ impl SubscriptionRepository for SubscriptionRepositorySqlite {
         Ok(())
     }
 
+    async fn record_fetch_success(
+        &self,
+        user_id: &str,
+        id: &str,
+        fetched_at_ms: i64,
+        http_status: u16,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE Subscriptions SET
+        last_fetch_ms = ?,
+        last_success_ms = ?,
+        last_error = NULL,
+        last_http_status = ?,
+        consecutive_failures = 0
+        WHERE user_id = ? AND id = ?",
+        )
+        .bind(fetched_at_ms)
+        .bind(fetched_at_ms)
+        .bind(http_status)
+        .bind(user_id)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_fetch_failure(
+        &self,
+        user_id: &str,
+        id: &str,
+        fetched_at_ms: i64,
+        error: &str,
+        http_status: Option<u16>,
+        disable: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE Subscriptions SET
+        last_fetch_ms = ?,
+        last_error = ?,
+        last_http_status = ?,
+        consecutive_failures = consecutive_failures + 1,
+        disabled = disabled OR ?
+        WHERE user_id = ? AND id = ?",
+        )
+        .bind(fetched_at_ms)
+        .bind(error)
+        .bind(http_status)
+        .bind(disable)
+        .bind(user_id)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     async fn get_subscription(&self, user_id: &str, id: &str) -> Result<Option<Subscription>> {
         let subscription_opt = sqlx::query_as::<_, Subscription>(
             "SELECT * FROM Subscriptions WHERE user_id = ? AND id = ?",
@@ -187,6 +324,25 @@ impl SubscriptionRepository for SubscriptionRepositorySqlite {
             .await?;
         Ok(subscriptions)
     }
+
+    async fn list_categories(&self, user_id: &str) -> Result<Vec<String>> {
+        let categories = self
+            .list_user_subscriptions(user_id)
+            .await?
+            .iter()
+            .flat_map(|subscription| subscription.categories())
+            .map(String::from)
+            .collect::<BTreeSet<String>>();
+        Ok(categories.into_iter().collect())
+    }
+
+    async fn rename_category(&self, user_id: &str, from: &str, to: &str) -> Result<()> {
+        self.replace_category(user_id, from, Some(to)).await
+    }
+
+    async fn remove_category(&self, user_id: &str, category: &str) -> Result<()> {
+        self.replace_category(user_id, category, None).await
+    }
 }
 
 pub async fn new_subscription_repository(
@@ -215,6 +371,12 @@ mod tests {
             feed_url: "feed_url".to_string(),
             joined_categories: "joined_categories".to_string(),
             last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
         };
         repository
             .insert_subscription(subscription.clone())
@@ -244,6 +406,12 @@ mod tests {
             feed_url: "feed_url".to_string(),
             joined_categories: "joined_categories".to_string(),
             last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
         };
         repository
             .insert_subscription(subscription.clone())
@@ -274,6 +442,12 @@ mod tests {
             feed_url: "feed_url".to_string(),
             joined_categories: "joined_categories".to_string(),
             last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
         };
         repository
             .insert_subscription(subscription.clone())
@@ -287,6 +461,11 @@ mod tests {
         updated_subscription.feed_url = "feed_url_2".to_string();
         updated_subscription.joined_categories = "joined_categories_2".to_string();
         updated_subscription.last_fetch_ms = 1;
+        updated_subscription.last_success_ms = 1;
+        updated_subscription.last_error = Some("error".to_string());
+        updated_subscription.last_http_status = Some(500);
+        updated_subscription.consecutive_failures = 1;
+        updated_subscription.disabled = true;
         repository
             .update_subscription(updated_subscription.clone())
             .await
@@ -315,6 +494,12 @@ mod tests {
             feed_url: "feed_url".to_string(),
             joined_categories: "joined_categories".to_string(),
             last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
         };
         repository
             .insert_subscription(subscription_1.clone())
@@ -342,4 +527,132 @@ mod tests {
         assert!(all_subscriptions.contains(&subscription_1));
         assert!(all_subscriptions.contains(&subscription_2));
     }
+
+    #[tokio::test]
+    pub async fn record_fetch_results_should_succeed() {
+        let repository = new_subscription_repository(in_memory_pool().await)
+            .await
+            .unwrap();
+        let subscription = Subscription {
+            user_id: "user_id".to_string(),
+            id: "id".to_string(),
+            url: "url".to_string(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            feed_url: "feed_url".to_string(),
+            joined_categories: "joined_categories".to_string(),
+            last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
+        };
+        repository
+            .insert_subscription(subscription.clone())
+            .await
+            .unwrap();
+
+        repository
+            .record_fetch_failure("user_id", "id", 1, "error", Some(503), false)
+            .await
+            .unwrap();
+        repository
+            .record_fetch_failure("user_id", "id", 2, "error", None, true)
+            .await
+            .unwrap();
+        let failed = repository
+            .get_subscription("user_id", "id")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.last_fetch_ms, 2);
+        assert_eq!(failed.last_error.as_deref(), Some("error"));
+        assert_eq!(failed.last_http_status, None);
+        assert_eq!(failed.consecutive_failures, 2);
+        assert!(failed.disabled);
+
+        repository
+            .record_fetch_success("user_id", "id", 3, 200)
+            .await
+            .unwrap();
+        let succeeded = repository
+            .get_subscription("user_id", "id")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(succeeded.last_success_ms, 3);
+        assert_eq!(succeeded.last_error, None);
+        assert_eq!(succeeded.last_http_status, Some(200));
+        assert_eq!(succeeded.consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    pub async fn rename_and_remove_category_should_succeed() {
+        let repository = new_subscription_repository(in_memory_pool().await)
+            .await
+            .unwrap();
+        let subscription_1 = Subscription {
+            user_id: "user_id".to_string(),
+            id: "id".to_string(),
+            url: "url".to_string(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            feed_url: "feed_url".to_string(),
+            joined_categories: "user/-/label/a,user/-/label/b".to_string(),
+            last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
+        };
+        let mut subscription_2 = subscription_1.clone();
+        subscription_2.id = "id_2".to_string();
+        subscription_2.joined_categories = "user/-/label/b".to_string();
+        let mut subscription_3 = subscription_1.clone();
+        subscription_3.user_id = "user_id_2".to_string();
+        for subscription in [&subscription_1, &subscription_2, &subscription_3] {
+            repository
+                .insert_subscription(subscription.clone())
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            repository.list_categories("user_id").await.unwrap(),
+            vec!["user/-/label/a", "user/-/label/b"]
+        );
+
+        repository
+            .rename_category("user_id", "user/-/label/b", "user/-/label/a")
+            .await
+            .unwrap();
+        for id in ["id", "id_2"] {
+            assert_eq!(
+                repository
+                    .get_subscription("user_id", id)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .joined_categories,
+                "user/-/label/a"
+            );
+        }
+
+        repository
+            .remove_category("user_id", "user/-/label/a")
+            .await
+            .unwrap();
+        assert!(repository
+            .list_categories("user_id")
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            repository.list_categories("user_id_2").await.unwrap(),
+            vec!["user/-/label/a", "user/-/label/b"]
+        );
+    }
 }
@@ -1,7 +1,8 @@
 use crate::database::items::Item;
 
 impl Item {
-    pub fn categories(&self) -> Vec<String> {
+    // Labels are the tags of the item, like "user/-/label/{name}".
+    pub fn categories(&self, labels: &[String]) -> Vec<String> {
         let mut ret = vec![];
         ret.push("user/-/state/com.google/reading-list".to_owned());
         if self.read {
@@ -12,6 +13,7 @@ impl Item {
         if self.starred {
             ret.push("user/-/state/com.google/starred".to_owned());
         }
+        ret.extend(labels.iter().cloned());
         ret
     }
 
@@ -0,0 +1,80 @@
+/// The streams clients can ask for, with labels in the "user/-/label/{name}"
+/// form they are stored in.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum StreamId {
+    ReadingList,
+    Read,
+    Starred,
+    Feed(String),
+    Label(String),
+}
+
+impl StreamId {
+    pub fn label(name: &str) -> StreamId {
+        StreamId::Label(format!("user/-/label/{}", name))
+    }
+
+    // User ids in stream ids can be "-" or the id of the user.
+    pub fn parse(stream_id: &str) -> Option<StreamId> {
+        if stream_id.starts_with("feed/") {
+            return Some(StreamId::Feed(stream_id.to_string()));
+        }
+        let (_, path) = stream_id.strip_prefix("user/")?.split_once('/')?;
+        if let Some(name) = path.strip_prefix("label/") {
+            return Some(StreamId::label(name));
+        }
+        match path {
+            "state/com.google/reading-list" => Some(StreamId::ReadingList),
+            "state/com.google/read" => Some(StreamId::Read),
+            "state/com.google/starred" => Some(StreamId::Starred),
+            _ => None,
+        }
+    }
+}
+
+// Clients may send labels as "user/{user_id}/label/{name}" or just "{name}".
+pub fn label_id(tag: &str) -> Option<String> {
+    match StreamId::parse(tag) {
+        Some(StreamId::Label(id)) => Some(id),
+        Some(_) => None,
+        None => Some(format!("user/-/label/{}", tag)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_id_should_be_parsed() {
+        assert_eq!(
+            StreamId::parse("feed/https://blog.com/atom.xml"),
+            Some(StreamId::Feed("feed/https://blog.com/atom.xml".to_string()))
+        );
+        assert_eq!(
+            StreamId::parse("user/12345/label/Tech"),
+            Some(StreamId::Label("user/-/label/Tech".to_string()))
+        );
+        assert_eq!(
+            StreamId::parse("user/-/state/com.google/reading-list"),
+            Some(StreamId::ReadingList)
+        );
+        assert_eq!(
+            StreamId::parse("user/-/state/com.google/starred"),
+            Some(StreamId::Starred)
+        );
+        assert_eq!(StreamId::parse("user/-/state/com.google/like"), None);
+        assert_eq!(StreamId::parse("Tech"), None);
+    }
+
+    #[test]
+    fn label_id_should_be_normalized() {
+        assert_eq!(label_id("user/-/label/Tech").unwrap(), "user/-/label/Tech");
+        assert_eq!(
+            label_id("user/12345/label/Tech").unwrap(),
+            "user/-/label/Tech"
+        );
+        assert_eq!(label_id("Tech").unwrap(), "user/-/label/Tech");
+        assert_eq!(label_id("user/-/state/com.google/starred"), None);
+    }
+}
@@ -10,7 +10,8 @@ mod user;
 
 use crate::common::Services;
 use crate::configuration::Configuration;
-use crate::middlewares::auth::reject_anonymous_user;
+use crate::middlewares::auth::{reject_anonymous_user, reject_non_admin_user};
+use crate::services::proxy::ImageProxy;
 use crate::user::UserService;
 use actix_web::dev::{HttpServiceFactory, Server};
 use actix_web::{web, App, HttpServer};
@@ -30,16 +31,32 @@ impl Application {
     pub async fn create(configuration: &Configuration) -> Result<Application> {
         let sqlite_pool =
             SqlitePoolOptions::new().connect_lazy_with(configuration.database.connect_options());
-        let services = web::Data::new(Services::new(sqlite_pool.clone()).await);
+        database::migrate(&sqlite_pool)
+            .await
+            .context("Failed to migrate the database")?;
+        database::items::fill_fingerprints(&sqlite_pool)
+            .await
+            .context("Failed to fingerprint the items")?;
+        let image_proxy = ImageProxy::new(&configuration.proxy);
+        let services = web::Data::new(
+            Services::new(
+                sqlite_pool.clone(),
+                configuration,
+                configuration.proxy.enabled.then(|| image_proxy.clone()),
+            )
+            .await,
+        );
+        let image_proxy = web::Data::new(image_proxy);
 
         let user_service = web::Data::new(UserService::new(sqlite_pool.clone()));
         user_service
-            .register(
+            .register_admin(
                 &configuration.application.email,
                 &configuration.application.password,
             )
             .await
             .expect("Failed to register the user.");
+        let application_configuration = web::Data::new(configuration.application.clone());
 
         let address = format!(
             "{}:{}",
@@ -52,10 +69,20 @@ impl Application {
             App::new()
                 .app_data(services.clone())
                 .app_data(user_service.clone())
-                .service(web::scope("/accounts").route(
-                    "/ClientLogin",
-                    web::post().to(routes::accounts::client_login),
-                ))
+                .app_data(application_configuration.clone())
+                .app_data(image_proxy.clone())
+                .route(
+                    "/proxy/{signature}/{url}",
+                    web::get().to(routes::proxy::proxy),
+                )
+                .service(
+                    web::scope("/accounts")
+                        .route(
+                            "/ClientLogin",
+                            web::post().to(routes::accounts::client_login),
+                        )
+                        .route("/register", web::post().to(routes::accounts::register)),
+                )
                 .service(Application::web_api_routes(
                     services.clone(),
                     user_service.clone(),
@@ -94,6 +121,56 @@ impl Application {
             )
             .route("/markAsRead", web::post().to(routes::api::mark_as_read))
             .route("/unread", web::get().to(routes::api::get_unread_items))
+            .route(
+                "/subscriptions",
+                web::get().to(routes::api::list_subscriptions),
+            )
+            .route(
+                "/subscriptions/extractContent",
+                web::post().to(routes::api::set_content_extraction),
+            )
+            .route("/search", web::get().to(routes::api::search))
+            .route(
+                "/retention",
+                web::get().to(routes::api::retention::get_retention),
+            )
+            .route(
+                "/retention",
+                web::post().to(routes::api::retention::set_retention),
+            )
+            .route("/rules", web::get().to(routes::api::rules::list_rules))
+            .route("/rules", web::post().to(routes::api::rules::add_rule))
+            // Before /rules/{id}, which would take dryRun as an id.
+            .route("/rules/dryRun", web::post().to(routes::api::rules::dry_run))
+            .route(
+                "/rules/{id}",
+                web::post().to(routes::api::rules::update_rule),
+            )
+            .route(
+                "/rules/{id}",
+                web::delete().to(routes::api::rules::delete_rule),
+            )
+            .route("/tokens", web::get().to(routes::api::tokens::list_tokens))
+            .route("/tokens", web::post().to(routes::api::tokens::create_token))
+            .route(
+                "/tokens/{id}",
+                web::delete().to(routes::api::tokens::revoke_token),
+            )
+            .service(
+                web::scope("/admin")
+                    .wrap(from_fn(reject_non_admin_user))
+                    .route("/users", web::get().to(routes::admin::list_users))
+                    .route("/users", web::post().to(routes::admin::add_user))
+                    .route("/users/{id}", web::delete().to(routes::admin::delete_user))
+                    .route(
+                        "/users/{id}/disabled",
+                        web::post().to(routes::admin::set_user_disabled),
+                    )
+                    .route(
+                        "/users/{id}/password",
+                        web::post().to(routes::admin::reset_password),
+                    ),
+            )
     }
 
     fn reader_routes(
@@ -105,6 +182,14 @@ impl Application {
             .app_data(services)
             .app_data(user_service)
             .route("/ping", web::get().to(routes::reader::ping))
+            .route(
+                "/subscriptions/import",
+                web::post().to(routes::reader::subscriptions::import_subscriptions),
+            )
+            .route(
+                "/subscriptions/export",
+                web::get().to(routes::reader::subscriptions::export_subscriptions),
+            )
             .service(
                 web::scope("/api/0")
                     .route(
@@ -125,6 +210,23 @@ impl Application {
                         web::post().to(routes::reader::subscriptions::edit_subscription),
                     )
                     .route("/edit-tag", web::post().to(routes::reader::edit::edit_tag))
+                    .route(
+                        "/mark-all-as-read",
+                        web::post().to(routes::reader::edit::mark_all_as_read),
+                    )
+                    .route("/tag/list", web::get().to(routes::reader::tags::list_tags))
+                    .route(
+                        "/unread-count",
+                        web::get().to(routes::reader::stream::get_unread_counts),
+                    )
+                    .route(
+                        "/rename-tag",
+                        web::post().to(routes::reader::tags::rename_tag),
+                    )
+                    .route(
+                        "/disable-tag",
+                        web::post().to(routes::reader::tags::disable_tag),
+                    )
                     .route(
                         "/stream/items/ids",
                         web::get().to(routes::reader::stream::get_item_ids),
@@ -132,6 +234,18 @@ impl Application {
                     .route(
                         "/stream/items/contents",
                         web::post().to(routes::reader::stream::get_contents),
+                    )
+                    .route(
+                        "/search/items/ids",
+                        web::get().to(routes::reader::stream::search_item_ids),
+                    )
+                    .route(
+                        "/stream/contents",
+                        web::get().to(routes::reader::stream::get_stream_contents),
+                    )
+                    .route(
+                        "/stream/contents/{stream_id:.*}",
+                        web::get().to(routes::reader::stream::get_stream_contents),
                     ),
             )
     }
@@ -1,4 +1,4 @@
-use crate::common::error::to_internal_error;
+use crate::common::error::Errors;
 use crate::common::token::Token;
 use crate::user::UserService;
 use actix_web::body::MessageBody;
@@ -13,6 +13,7 @@ pub struct AuthUser {
     pub id: String,
     pub email: String,
     pub token: String,
+    pub is_admin: bool,
 }
 
 pub async fn reject_anonymous_user(
@@ -23,7 +24,7 @@ pub async fn reject_anonymous_user(
         .headers()
         .get(AUTHORIZATION)
         .ok_or(ErrorForbidden("Missing authorization in header"))?;
-    let header_value_str = header_value.to_str().map_err(to_internal_error)?;
+    let header_value_str = header_value.to_str().map_err(Errors::invalid_request)?;
     let token = header_value_str
         .strip_prefix("GoogleLogin auth=")
         .ok_or(ErrorForbidden("Missing token in header"))?;
@@ -34,14 +35,34 @@ pub async fn reject_anonymous_user(
             .expect("Failed to get state")
             .get_user(token)
             .await
-            .map_err(to_internal_error)?;
+            .map_err(|e| match e.downcast_ref::<Errors>() {
+                Some(Errors::InvalidToken { .. }) => ErrorForbidden("Unauthorized"),
+                _ => Errors::from(e).into(),
+            })?;
         req.extensions_mut().insert(AuthUser {
+            is_admin: user.is_admin(),
             id: user.id,
             email: user.email,
-            token: user.token,
+            token: token.to_string(),
         });
         next.call(req).await
     } else {
         Err(ErrorForbidden("Unauthorized"))
     }
 }
+
+// Must be wrapped inside reject_anonymous_user, which sets the AuthUser.
+pub async fn reject_non_admin_user(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let is_admin = req
+        .extensions()
+        .get::<AuthUser>()
+        .is_some_and(|user| user.is_admin);
+    if is_admin {
+        next.call(req).await
+    } else {
+        Err(ErrorForbidden("Unauthorized"))
+    }
+}
@@ -1,20 +1,47 @@
 use crate::common::Services;
+use crate::configuration::Configuration;
+use crate::database::enclosures::new_enclosure_repository;
+use crate::database::feed_states::new_feed_state_repository;
 use crate::database::items::new_item_repository;
+use crate::database::retentions::new_retention_repository;
+use crate::database::rules::new_rule_repository;
 use crate::database::subscriptions::new_subscription_repository;
+use crate::services::proxy::ImageProxy;
+use crate::services::rules::new_rule_service;
 use crate::services::stream::new_stream_service;
 use crate::services::subscriptions::new_subscription_service;
 use sqlx::SqlitePool;
 
 impl Services {
-    pub async fn new(pool: SqlitePool) -> Services {
+    pub async fn new(
+        pool: SqlitePool,
+        configuration: &Configuration,
+        image_proxy: Option<ImageProxy>,
+    ) -> Services {
         Services {
             subscription_service: new_subscription_service(
                 new_subscription_repository(pool.clone()).await.unwrap(),
                 new_item_repository(pool.clone()).await.unwrap(),
+                new_feed_state_repository(pool.clone()).await.unwrap(),
+                new_retention_repository(pool.clone()).await.unwrap(),
+                new_enclosure_repository(pool.clone()).await.unwrap(),
+                new_rule_repository(pool.clone()).await.unwrap(),
+                configuration.retention.clone(),
+                configuration.dedup.policy,
             ),
             stream_service: new_stream_service(
                 new_item_repository(pool.clone()).await.unwrap(),
                 new_subscription_repository(pool.clone()).await.unwrap(),
+                new_retention_repository(pool.clone()).await.unwrap(),
+                new_enclosure_repository(pool.clone()).await.unwrap(),
+                configuration.retention.clone(),
+                configuration.dedup.policy,
+                image_proxy,
+            ),
+            rule_service: new_rule_service(
+                new_rule_repository(pool.clone()).await.unwrap(),
+                new_item_repository(pool.clone()).await.unwrap(),
+                new_subscription_repository(pool.clone()).await.unwrap(),
             ),
         }
     }
@@ -3,10 +3,13 @@ use crate::configuration::Configuration;
 use sqlx::sqlite::SqlitePoolOptions;
 use std::time::Duration;
 
+// Feeds are only fetched when they are due, so the loop can wake up often.
+const REFRESH_TICK: Duration = Duration::from_secs(60);
+
 pub async fn refresh_until_stopped(configuration: Configuration) -> Result<(), anyhow::Error> {
     let sqlite_pool =
         SqlitePoolOptions::new().connect_lazy_with(configuration.database.connect_options());
-    let services = Services::new(sqlite_pool.clone()).await;
+    let services = Services::new(sqlite_pool.clone(), &configuration, None).await;
     loop {
         if let Err(err) = services.stream_service.clean_up().await {
             println!("Clean up old items failed {:?}", err);
@@ -18,6 +21,6 @@ pub async fn refresh_until_stopped(configuration: Configuration) -> Result<(), a
         {
             println!("Load subscription items failed: {:?}", err);
         }
-        tokio::time::sleep(Duration::from_secs(600)).await;
+        tokio::time::sleep(REFRESH_TICK).await;
     }
 }
@@ -1,5 +1,7 @@
-use crate::user::UserService;
-use actix_web::{web, HttpResponse};
+use crate::common::error::Errors;
+use crate::configuration::ApplicationConfiguration;
+use crate::user::{Role, UserService};
+use actix_web::{web, HttpResponse, ResponseError};
 
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -20,3 +22,23 @@ pub async fn client_login(
         Err(_) => HttpResponse::Forbidden().body("Error=BadAuthentication"),
     }
 }
+
+pub async fn register(
+    request: web::Form<LoginRequest>,
+    configuration: web::Data<ApplicationConfiguration>,
+    user_service: web::Data<UserService>,
+) -> HttpResponse {
+    if !configuration.allow_registration {
+        return HttpResponse::Forbidden().body("Error=RegistrationClosed");
+    }
+    match user_service
+        .add_user(&request.email, &request.passwd, Role::User)
+        .await
+    {
+        Ok(_) => HttpResponse::Ok().body("OK"),
+        Err(e) => match e.downcast_ref::<Errors>() {
+            Some(Errors::UserExists { .. }) => HttpResponse::BadRequest().body("Error=UserExists"),
+            _ => Errors::from(e).error_response(),
+        },
+    }
+}
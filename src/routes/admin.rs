@@ -0,0 +1,110 @@
+use crate::common::error::Errors;
+use crate::middlewares::auth::AuthUser;
+use crate::user::{Role, User, UserService};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+pub struct UserInfo {
+    id: String,
+    email: String,
+    role: Role,
+    disabled: bool,
+}
+
+impl From<User> for UserInfo {
+    fn from(user: User) -> Self {
+        UserInfo {
+            id: user.id,
+            email: user.email,
+            role: user.role,
+            disabled: user.disabled,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Users {
+    users: Vec<UserInfo>,
+}
+
+pub async fn list_users(
+    user_service: web::Data<UserService>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let users = user_service.list_users().await.map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(Users {
+        users: users.into_iter().map(UserInfo::from).collect(),
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NewUser {
+    email: String,
+    password: String,
+    role: Option<Role>,
+}
+
+pub async fn add_user(
+    user_service: web::Data<UserService>,
+    new_user: web::Json<NewUser>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user = user_service
+        .add_user(
+            &new_user.email,
+            &new_user.password,
+            new_user.role.unwrap_or(Role::User),
+        )
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(UserInfo::from(user)))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Disabled {
+    disabled: bool,
+}
+
+pub async fn set_user_disabled(
+    auth_user: web::ReqData<AuthUser>,
+    user_service: web::Data<UserService>,
+    id: web::Path<String>,
+    data: web::Json<Disabled>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if auth_user.id == *id {
+        return Err(Errors::invalid_request("Admins can't disable themselves").into());
+    }
+    user_service
+        .set_user_disabled(&id, data.disabled)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Password {
+    password: String,
+}
+
+pub async fn reset_password(
+    user_service: web::Data<UserService>,
+    id: web::Path<String>,
+    data: web::Json<Password>,
+) -> Result<HttpResponse, actix_web::Error> {
+    user_service
+        .reset_password(&id, &data.password)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+pub async fn delete_user(
+    auth_user: web::ReqData<AuthUser>,
+    user_service: web::Data<UserService>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if auth_user.id == *id {
+        return Err(Errors::invalid_request("Admins can't delete themselves").into());
+    }
+    user_service.delete_user(&id).await.map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
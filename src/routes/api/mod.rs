@@ -1,6 +1,13 @@
+pub mod retention;
+pub mod rules;
+pub mod tokens;
+
+use crate::common::error::Errors;
 use crate::common::{PageOption, Services};
+use crate::greader::stream::StreamId;
 use crate::middlewares::auth::AuthUser;
-use crate::services::stream::ItemContent;
+use crate::services::stream::{ItemContent, StreamFilter};
+use crate::services::subscriptions::Subscription;
 use actix_web::{web, HttpResponse};
 use serde::{Deserialize, Serialize};
 
@@ -21,7 +28,7 @@ pub async fn get_unread_items(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
     page: web::Query<Page>,
-) -> HttpResponse {
+) -> Result<HttpResponse, actix_web::Error> {
     let user_id = &auth_user.id;
     let contents = services
         .stream_service
@@ -34,11 +41,11 @@ pub async fn get_unread_items(
             },
         )
         .await
-        .unwrap();
-    HttpResponse::Ok().json(Contents {
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(Contents {
         items: contents.items,
         next_page_offset: contents.next_page_offset,
-    })
+    }))
 }
 
 #[derive(Deserialize, Debug)]
@@ -51,14 +58,14 @@ pub async fn mark_as_read(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
     ids: web::Json<Ids>,
-) -> HttpResponse {
+) -> Result<HttpResponse, actix_web::Error> {
     let str_ids = ids.ids.iter().map(|s| &**s).collect();
     services
         .stream_service
         .mark_as_read(&auth_user.id, &str_ids)
         .await
-        .unwrap();
-    HttpResponse::Ok().body("OK")
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
 }
 
 #[derive(Deserialize, Debug)]
@@ -72,29 +79,110 @@ pub async fn add_subscription(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
     subscription: web::Json<SubscriptionData>,
-) -> HttpResponse {
+) -> Result<HttpResponse, actix_web::Error> {
     let added = services
         .subscription_service
         .add_subscription_from_url(&auth_user.id, &subscription.link)
         .await
-        .unwrap();
+        .map_err(Errors::from)?;
     if let Some(ref f) = subscription.folder {
         let tag = format!("user/-/label/{}", f);
-        let title: Option<&str> = if subscription.title.is_some() {
-            Some(&**subscription.title.as_ref().unwrap())
-        } else {
-            None
-        };
         services
             .subscription_service
-            .edit_subscription(&auth_user.id, &added.id, &title, &vec![&tag], &vec![])
+            .edit_subscription(
+                &auth_user.id,
+                &added.id,
+                &subscription.title.as_deref(),
+                &vec![&tag],
+                &vec![],
+            )
             .await
-            .unwrap();
+            .map_err(Errors::from)?;
     }
     services
         .subscription_service
         .load_all_subscription_items()
         .await
-        .unwrap();
-    HttpResponse::Ok().body("OK")
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+#[derive(Serialize)]
+pub struct Subscriptions {
+    subscriptions: Vec<Subscription>,
+}
+
+pub async fn list_subscriptions(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriptions = services
+        .subscription_service
+        .list_subscriptions(&auth_user.id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(Subscriptions { subscriptions }))
+}
+
+#[derive(Deserialize)]
+pub struct ContentExtraction {
+    id: String,
+    enabled: bool,
+}
+
+pub async fn set_content_extraction(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    request: web::Json<ContentExtraction>,
+) -> Result<HttpResponse, actix_web::Error> {
+    services
+        .subscription_service
+        .set_content_extraction(&auth_user.id, &request.id, request.enabled)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    q: String,
+    // Subscription id, like "feed/https://blog.com/atom.xml".
+    feed: Option<String>,
+    label: Option<String>,
+    offset: Option<String>,
+    limit: Option<usize>,
+}
+
+pub async fn search(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if query.q.trim().is_empty() {
+        return Err(Errors::invalid_request("Missing query").into());
+    }
+    let stream = match (&query.feed, &query.label) {
+        (Some(feed), _) => StreamId::Feed(feed.clone()),
+        (None, Some(label)) => StreamId::label(label),
+        (None, None) => StreamId::ReadingList,
+    };
+    let mut stream_filter = StreamFilter::new(stream);
+    stream_filter.search = Some(query.q.clone());
+    let contents = services
+        .stream_service
+        .get_stream_item_contents(
+            &auth_user.id,
+            &stream_filter,
+            PageOption {
+                offset: query.offset.clone(),
+                limit: query.limit.unwrap_or(100),
+                desc: true,
+            },
+        )
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(Contents {
+        items: contents.items,
+        next_page_offset: contents.next_page_offset,
+    }))
 }
@@ -0,0 +1,38 @@
+use crate::common::error::Errors;
+use crate::common::Services;
+use crate::database::retentions::{Limit, Retention};
+use crate::middlewares::auth::AuthUser;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_retention(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let retentions = services
+        .stream_service
+        .get_retentions(&auth_user.id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(retentions))
+}
+
+pub async fn set_retention(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    retention: web::Json<Retention>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let retention = retention.into_inner();
+    let is_positive = |limit: Limit| match limit {
+        Limit::Limited(limit) => limit > 0,
+        _ => true,
+    };
+    if !is_positive(retention.max_age_days) || !is_positive(retention.max_items) {
+        return Err(Errors::invalid_request("Retention limits must be positive").into());
+    }
+    services
+        .stream_service
+        .set_retention(&auth_user.id, retention)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
@@ -0,0 +1,82 @@
+use crate::common::error::Errors;
+use crate::common::Services;
+use crate::database::rules::Rule;
+use crate::middlewares::auth::AuthUser;
+use crate::services::rules::MatchedItem;
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Serialize)]
+pub struct DryRun {
+    items: Vec<MatchedItem>,
+}
+
+pub async fn list_rules(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let rules = services
+        .rule_service
+        .list_rules(&auth_user.id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(Rules { rules }))
+}
+
+pub async fn add_rule(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    rule: web::Json<Rule>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let rule = services
+        .rule_service
+        .add_rule(&auth_user.id, rule.into_inner())
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+pub async fn update_rule(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    id: web::Path<String>,
+    rule: web::Json<Rule>,
+) -> Result<HttpResponse, actix_web::Error> {
+    services
+        .rule_service
+        .update_rule(&auth_user.id, &id, rule.into_inner())
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+pub async fn delete_rule(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    services
+        .rule_service
+        .delete_rule(&auth_user.id, &id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+pub async fn dry_run(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    rule: web::Json<Rule>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let items = services
+        .rule_service
+        .dry_run(&auth_user.id, rule.into_inner())
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(DryRun { items }))
+}
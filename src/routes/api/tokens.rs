@@ -0,0 +1,74 @@
+use crate::common::error::Errors;
+use crate::middlewares::auth::AuthUser;
+use crate::user::{NamedToken, UserService};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+pub struct Tokens {
+    tokens: Vec<NamedToken>,
+}
+
+pub async fn list_tokens(
+    auth_user: web::ReqData<AuthUser>,
+    user_service: web::Data<UserService>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tokens = user_service
+        .list_tokens(&auth_user.id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(Tokens { tokens }))
+}
+
+const MAX_EXPIRE_IN_DAYS: i64 = 3650;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenData {
+    name: String,
+    expire_in_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    info: NamedToken,
+    token: String,
+}
+
+pub async fn create_token(
+    auth_user: web::ReqData<AuthUser>,
+    user_service: web::Data<UserService>,
+    data: web::Json<TokenData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(days) = data.expire_in_days {
+        if !(1..=MAX_EXPIRE_IN_DAYS).contains(&days) {
+            return Err(Errors::invalid_request(format!(
+                "Tokens must expire in 1 to {} days",
+                MAX_EXPIRE_IN_DAYS
+            ))
+            .into());
+        }
+    }
+    let token = user_service
+        .create_token(&auth_user.id, &data.name, data.expire_in_days)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(CreatedToken {
+        token: token.token.clone(),
+        info: token,
+    }))
+}
+
+pub async fn revoke_token(
+    auth_user: web::ReqData<AuthUser>,
+    user_service: web::Data<UserService>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    user_service
+        .revoke_token(&auth_user.id, &id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
@@ -1,3 +1,5 @@
 pub mod accounts;
+pub mod admin;
 pub mod api;
+pub mod proxy;
 pub mod reader;
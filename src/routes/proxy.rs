@@ -0,0 +1,26 @@
+use crate::common::error::Errors;
+use crate::services::proxy::ImageProxy;
+use actix_web::{web, HttpResponse};
+
+// Proxied urls never change, only the signature can make them stale.
+const CACHE_CONTROL: &str = "public, max-age=604800, immutable";
+
+pub async fn proxy(
+    path: web::Path<(String, String)>,
+    image_proxy: web::Data<ImageProxy>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (signature, encoded_url) = path.into_inner();
+    let url = image_proxy
+        .verify(&signature, &encoded_url)
+        .ok_or_else(|| Errors::InvalidSignature {
+            signature: signature.clone(),
+        })?;
+    let media = image_proxy.fetch(&url).await.map_err(Errors::from)?;
+    Ok(HttpResponse::Ok()
+        .content_type(media.content_type)
+        .insert_header(("Cache-Control", CACHE_CONTROL))
+        // Images like svg can have scripts, which must not run on this origin.
+        .insert_header(("Content-Security-Policy", "default-src 'none'; sandbox"))
+        .insert_header(("X-Content-Type-Options", "nosniff"))
+        .streaming(media.body))
+}
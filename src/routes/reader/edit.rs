@@ -1,53 +1,139 @@
+use crate::common::error::Errors;
 use crate::common::Services;
+use crate::greader::stream::StreamId;
 use crate::middlewares::auth::AuthUser;
 use actix_web::{web, HttpResponse};
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+const READ: &str = "user/-/state/com.google/read";
+const STARRED: &str = "user/-/state/com.google/starred";
+
+// Clients repeat i for every item, and may repeat a and r, which serde can't
+// read from forms.
+#[derive(Default, Debug, PartialEq, Eq)]
 pub struct EditTagRequest {
-    pub i: Option<Vec<String>>,
-    pub a: Option<String>,
-    pub r: Option<String>,
+    pub i: Vec<String>,
+    pub a: Vec<String>,
+    pub r: Vec<String>,
+}
+
+impl EditTagRequest {
+    fn parse(body: &[u8]) -> EditTagRequest {
+        let mut request = EditTagRequest::default();
+        for (key, value) in url::form_urlencoded::parse(body) {
+            let values = match key.as_ref() {
+                "i" => &mut request.i,
+                "a" => &mut request.a,
+                "r" => &mut request.r,
+                _ => continue,
+            };
+            values.push(value.into_owned());
+        }
+        request
+    }
+}
+
+// Adds, or removes, a state or a label of the items. Other tags are ignored.
+async fn edit_items(
+    services: &Services,
+    user_id: &str,
+    ids: &Vec<&str>,
+    tag: &str,
+    add: bool,
+) -> anyhow::Result<()> {
+    let stream_service = &services.stream_service;
+    match (tag, add) {
+        (READ, true) => stream_service.mark_as_read(user_id, ids).await,
+        (READ, false) => stream_service.mark_as_unread(user_id, ids).await,
+        (STARRED, true) => stream_service.mark_as_starred(user_id, ids).await,
+        (STARRED, false) => stream_service.mark_as_unstarred(user_id, ids).await,
+        _ => match StreamId::parse(tag) {
+            Some(StreamId::Label(label)) if add => {
+                stream_service.add_label(user_id, ids, &label).await
+            }
+            Some(StreamId::Label(label)) => stream_service.remove_label(user_id, ids, &label).await,
+            _ => Ok(()),
+        },
+    }
 }
 
 pub async fn edit_tag(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
-    request: web::Form<EditTagRequest>,
-) -> HttpResponse {
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::Error> {
     let user_id = &auth_user.id;
-    if let Some(ref ids) = request.i {
-        let ids_in_hex = super::convert_to_long_form_ids(&ids.iter().map(|s| s.as_str()).collect());
-        let ids_ref = &ids_in_hex.iter().map(|s| &**s).collect::<Vec<&str>>();
-        if let Some(to_add) = &request.a {
-            if to_add == "user/-/state/com.google/read" {
-                services
-                    .stream_service
-                    .mark_as_read(user_id, ids_ref)
-                    .await
-                    .unwrap();
-            } else if to_add == "user/-/state/com.google/starred" {
-                services
-                    .stream_service
-                    .mark_as_starred(user_id, ids_ref)
-                    .await
-                    .unwrap();
-            }
-        } else if let Some(to_remove) = &request.r {
-            if to_remove == "user/-/state/com.google/read" {
-                services
-                    .stream_service
-                    .mark_as_unread(user_id, ids_ref)
-                    .await
-                    .unwrap();
-            } else if to_remove == "user/-/state/com.google/starred" {
-                services
-                    .stream_service
-                    .mark_as_unstarred(user_id, ids_ref)
-                    .await
-                    .unwrap();
+    let request = EditTagRequest::parse(&body);
+    let ids_in_hex =
+        super::convert_to_long_form_ids(&request.i.iter().map(|s| s.as_str()).collect());
+    let ids_ref = &ids_in_hex.iter().map(|s| &**s).collect::<Vec<&str>>();
+    if ids_ref.is_empty() {
+        return Ok(HttpResponse::Ok().body("OK"));
+    }
+    let tags = request
+        .a
+        .iter()
+        .map(|tag| (tag, true))
+        .chain(request.r.iter().map(|tag| (tag, false)));
+    for (tag, add) in tags {
+        edit_items(&services, user_id, ids_ref, tag, add)
+            .await
+            .map_err(Errors::from)?;
+    }
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+#[derive(Deserialize)]
+pub struct MarkAllAsReadRequest {
+    s: String,
+    // Microseconds
+    ts: Option<String>,
+}
+
+pub async fn mark_all_as_read(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    request: web::Form<MarkAllAsReadRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let stream_id = StreamId::parse(&request.s).ok_or_else(|| Errors::InvalidStream {
+        stream_id: request.s.clone(),
+    })?;
+    // Items strictly older than ts, in microseconds, are created at or before
+    // the millisecond before it.
+    let older_than_ms = match request.ts.as_deref() {
+        Some(ts) => Some(
+            ts.parse::<i64>()
+                .map_err(|_| Errors::invalid_request(format!("Invalid timestamp {}", ts)))?
+                .saturating_sub(1)
+                .div_euclid(1000),
+        ),
+        None => None,
+    };
+    services
+        .stream_service
+        .mark_all_as_read(&auth_user.id, &stream_id, older_than_ms)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_values_should_be_parsed() {
+        let body = "i=1a&i=2b&a=user%2F-%2Flabel%2FTech&a=user/-/state/com.google/read&r=user/-/label/Later&T=token";
+        assert_eq!(
+            EditTagRequest::parse(body.as_bytes()),
+            EditTagRequest {
+                i: vec!["1a".to_string(), "2b".to_string()],
+                a: vec![
+                    "user/-/label/Tech".to_string(),
+                    "user/-/state/com.google/read".to_string()
+                ],
+                r: vec!["user/-/label/Later".to_string()],
             }
-        }
+        );
     }
-    HttpResponse::Ok().body("OK")
 }
@@ -1,6 +1,7 @@
 pub mod edit;
 pub mod stream;
 pub mod subscriptions;
+pub mod tags;
 pub mod users;
 
 use crate::middlewares::auth::AuthUser;
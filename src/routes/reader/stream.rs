@@ -1,7 +1,9 @@
+use crate::common::error::Errors;
 use crate::common::Services;
 use crate::common::{current_time_s, Page, PageOption};
+use crate::greader::stream::StreamId;
 use crate::middlewares::auth::AuthUser;
-use crate::services::stream::{ItemContent, ItemId};
+use crate::services::stream::{ItemContent, ItemId, StreamFilter, UnreadCount};
 use actix_web::{web, HttpResponse};
 use serde::{Deserialize, Serialize};
 
@@ -21,73 +23,103 @@ impl From<Page<ItemId, String>> for ItemIds {
     }
 }
 
-enum FilterType {
-    UNREAD,
-    STARRED,
-    READ,
-    ALL,
-}
-
-impl FilterType {
-    fn from_params(s: &str, xt: Option<&str>) -> FilterType {
-        if s.ends_with("/state/com.google/starred") {
-            FilterType::STARRED
-        } else if s.ends_with("/state/com.google/read") {
-            FilterType::READ
-        } else {
-            if xt.is_some() && xt.unwrap().ends_with("/state/com.google/read") {
-                FilterType::UNREAD
-            } else {
-                FilterType::ALL
-            }
-        }
-    }
-}
-
 #[derive(Deserialize)]
 pub struct Query {
-    s: String,
+    // stream/contents takes the stream from the path instead.
+    s: Option<String>,
     xt: Option<String>,
+    it: Option<String>,
+    // Seconds
+    ot: Option<i64>,
+    // Seconds
+    nt: Option<i64>,
     n: Option<usize>,
     r: Option<String>,
     c: Option<String>,
+    // Words to search, for search/items/ids.
+    q: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct StreamPath {
+    stream_id: Option<String>,
+}
+
+impl Query {
+    fn page_option(&self, default_limit: usize) -> PageOption<String> {
+        PageOption::<String> {
+            offset: self.c.as_deref().map(String::from),
+            limit: self.n.unwrap_or(default_limit),
+            desc: !self.r.as_deref().unwrap_or("").eq("o"),
+        }
+    }
+
+    // Streams the server doesn't know list all the items, as they always did.
+    fn stream_filter(&self, stream_id: &str) -> StreamFilter {
+        StreamFilter {
+            stream: StreamId::parse(stream_id).unwrap_or(StreamId::ReadingList),
+            include: parse_tags(self.it.as_deref()),
+            exclude: parse_tags(self.xt.as_deref()),
+            newer_than_ms: self.ot.map(|ot| ot * 1000),
+            older_than_ms: self.nt.map(|nt| nt * 1000),
+            search: self.q.clone(),
+        }
+    }
+}
+
+// Tags the server doesn't know, like kept-unread, can't narrow the stream.
+fn parse_tags(tag: Option<&str>) -> Vec<StreamId> {
+    tag.and_then(StreamId::parse).into_iter().collect()
 }
 
 pub async fn get_item_ids(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
     query: web::Query<Query>,
-) -> HttpResponse {
+) -> Result<HttpResponse, actix_web::Error> {
     let user_id = &auth_user.id;
-    let page_option = PageOption::<String> {
-        offset: query.c.as_deref().map(|s| String::from(s)),
-        limit: query.n.unwrap_or(100usize),
-        desc: !query.r.as_deref().unwrap_or("").eq("o"),
-    };
-    let filter_type = FilterType::from_params(&query.s, query.xt.as_deref());
-    let item_ids_page = match filter_type {
-        FilterType::STARRED => services
-            .stream_service
-            .get_starred_item_ids(user_id, page_option)
-            .await
-            .unwrap(),
-        FilterType::UNREAD => services
-            .stream_service
-            .get_unread_item_ids(user_id, page_option)
-            .await
-            .unwrap(),
-        FilterType::READ => services
-            .stream_service
-            .get_read_item_ids(user_id, page_option)
-            .await
-            .unwrap(),
-        FilterType::ALL => services
-            .stream_service
-            .get_all_item_ids(user_id, page_option)
-            .await
-            .unwrap(),
-    };
-    HttpResponse::Ok().json(ItemIds::from(item_ids_page))
+    let stream_id = query
+        .s
+        .as_deref()
+        .ok_or_else(|| Errors::invalid_request("Missing stream"))?;
+    let stream_filter = query.stream_filter(stream_id);
+    let item_ids_page = services
+        .stream_service
+        .get_item_ids(user_id, &stream_filter, query.page_option(100))
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(ItemIds::from(item_ids_page)))
+}
+
+#[derive(Serialize)]
+pub struct SearchResults {
+    results: Vec<ItemId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continuation: Option<String>,
+}
+
+pub async fn search_item_ids(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    query: web::Query<Query>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if query.q.as_deref().is_none_or(|q| q.trim().is_empty()) {
+        return Err(Errors::invalid_request("Missing query").into());
+    }
+    let stream_id = query
+        .s
+        .as_deref()
+        .unwrap_or("user/-/state/com.google/reading-list");
+    let stream_filter = query.stream_filter(stream_id);
+    let page = services
+        .stream_service
+        .get_item_ids(&auth_user.id, &stream_filter, query.page_option(100))
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(SearchResults {
+        results: page.items,
+        continuation: page.next_page_offset,
+    }))
 }
 
 #[derive(Serialize, Debug)]
@@ -98,6 +130,8 @@ pub struct Contents {
     description: String,
     updated: u64,
     items: Vec<ItemContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continuation: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -109,7 +143,7 @@ pub async fn get_contents(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
     ids: web::Form<Ids>,
-) -> HttpResponse {
+) -> Result<HttpResponse, actix_web::Error> {
     let user_id = &auth_user.id;
     let mut item_contents = match ids.i {
         Some(ref i) => {
@@ -119,17 +153,86 @@ pub async fn get_contents(
                 .stream_service
                 .get_item_contents(user_id, &ids_in_hex.iter().map(|s| &**s).collect())
                 .await
-                .unwrap()
+                .map_err(Errors::from)?
         }
         None => vec![],
     };
     item_contents.sort_by(|a, b| b.published.cmp(&a.published));
-    HttpResponse::Ok().json(Contents {
+    Ok(HttpResponse::Ok().json(Contents {
         direction: "ltr".to_string(),
         id: "user/-/state/com.google/reading-list".to_string(),
         title: "Reading List".to_string(),
         description: "Reading List".to_string(),
         updated: current_time_s() as u64,
         items: item_contents,
-    })
+        continuation: None,
+    }))
+}
+
+fn stream_title(stream: &StreamId) -> String {
+    match stream {
+        StreamId::ReadingList => "Reading List".to_string(),
+        StreamId::Read => "Read".to_string(),
+        StreamId::Starred => "Starred".to_string(),
+        StreamId::Feed(id) => id.clone(),
+        StreamId::Label(label) => label.rsplit('/').next().unwrap_or(label).to_string(),
+    }
+}
+
+pub async fn get_stream_contents(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    path: web::Path<StreamPath>,
+    query: web::Query<Query>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Stream ids in the path may keep their slashes escaped, the path is
+    // decoded by actix.
+    let stream_id = match path.into_inner().stream_id {
+        Some(stream_id) if !stream_id.is_empty() => stream_id,
+        _ => query
+            .s
+            .clone()
+            .ok_or_else(|| Errors::invalid_request("Missing stream"))?,
+    };
+    let stream_filter = query.stream_filter(&stream_id);
+    let page = services
+        .stream_service
+        .get_stream_item_contents(&auth_user.id, &stream_filter, query.page_option(20))
+        .await
+        .map_err(Errors::from)?;
+    let title = stream_title(&stream_filter.stream);
+    Ok(HttpResponse::Ok().json(Contents {
+        direction: "ltr".to_string(),
+        id: stream_id,
+        description: title.clone(),
+        title,
+        updated: current_time_s() as u64,
+        items: page.items,
+        continuation: page.next_page_offset,
+    }))
+}
+
+// Clients only use this to cap the badges they show.
+const MAX_UNREAD_COUNT: i64 = 1000;
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub struct UnreadCounts {
+    max: i64,
+    unreadcounts: Vec<UnreadCount>,
+}
+
+pub async fn get_unread_counts(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let unreadcounts = services
+        .stream_service
+        .get_unread_counts(&auth_user.id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(UnreadCounts {
+        max: MAX_UNREAD_COUNT,
+        unreadcounts,
+    }))
 }
@@ -1,6 +1,8 @@
+use crate::common::error::Errors;
 use crate::common::Services;
 use crate::middlewares::auth::AuthUser;
-use crate::services::subscriptions::Subscription;
+use crate::services::opml::parse_opml;
+use crate::services::subscriptions::{ImportResult, QuickAddResult, Subscription};
 use actix_web::{web, HttpResponse};
 use serde::{Deserialize, Serialize};
 use std::ops::Deref;
@@ -13,15 +15,14 @@ pub struct Subscriptions {
 pub async fn list_subscriptions(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
-) -> HttpResponse {
+) -> Result<HttpResponse, actix_web::Error> {
     let user_id = &auth_user.id;
-    // TODO: handle error properly
     let subscriptions = services
         .subscription_service
         .list_subscriptions(user_id)
         .await
-        .unwrap();
-    HttpResponse::Ok().json(Subscriptions { subscriptions })
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(Subscriptions { subscriptions }))
 }
 
 #[derive(serde::Deserialize)]
@@ -34,26 +35,43 @@ pub struct AddRequest {
 pub struct AddSubscriptionResponse {
     query: String,
     num_results: i64,
-    stream_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_id: Option<String>,
+    // Feeds found on the website, when there is more than one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    candidates: Vec<String>,
 }
 
 pub async fn add_subscription(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
     request: web::Form<AddRequest>,
-) -> HttpResponse {
+) -> Result<HttpResponse, actix_web::Error> {
     let user_id = &auth_user.id;
-    // TODO: handle error properly
-    let subscription = services
+    let url = request
+        .quickadd
+        .strip_prefix("feed/")
+        .unwrap_or(&request.quickadd);
+    let result = services
         .subscription_service
-        .add_subscription_from_url(user_id, &request.quickadd)
+        .quick_add_subscription(user_id, url)
         .await
-        .unwrap();
-    HttpResponse::Ok().json(AddSubscriptionResponse {
-        query: request.quickadd.clone(),
-        num_results: 1,
-        stream_id: subscription.id,
-    })
+        .map_err(Errors::from)?;
+    let response = match result {
+        QuickAddResult::Added(subscription) => AddSubscriptionResponse {
+            query: request.quickadd.clone(),
+            num_results: 1,
+            stream_id: Some(subscription.id),
+            candidates: vec![],
+        },
+        QuickAddResult::Candidates(candidates) => AddSubscriptionResponse {
+            query: request.quickadd.clone(),
+            num_results: candidates.len() as i64,
+            stream_id: None,
+            candidates,
+        },
+    };
+    Ok(HttpResponse::Ok().json(response))
 }
 
 #[derive(Deserialize)]
@@ -74,7 +92,7 @@ pub async fn edit_subscription(
     auth_user: web::ReqData<AuthUser>,
     services: web::Data<Services>,
     request: web::Form<SubscriptionEditRequest>,
-) -> HttpResponse {
+) -> Result<HttpResponse, actix_web::Error> {
     let user_id = &auth_user.id;
     if let Some(feed_url) = request.s.strip_prefix("feed/") {
         match request.ac.deref() {
@@ -83,14 +101,14 @@ pub async fn edit_subscription(
                     .subscription_service
                     .add_subscription_from_url(user_id, feed_url)
                     .await
-                    .unwrap();
+                    .map_err(Errors::from)?;
             }
             "unsubscribe" => {
                 services
                     .subscription_service
                     .remove_subscription(user_id, &request.s)
                     .await
-                    .unwrap();
+                    .map_err(Errors::from)?;
             }
             _ => {}
         };
@@ -106,7 +124,50 @@ pub async fn edit_subscription(
                 &request.r.iter().map(|s| s.as_str()).collect(),
             )
             .await
-            .unwrap();
+            .map_err(Errors::from)?;
     }
-    HttpResponse::Ok().body("OK")
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+#[derive(Serialize)]
+pub struct ImportResults {
+    results: Vec<ImportResult>,
+}
+
+pub async fn import_subscriptions(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    opml: String,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = &auth_user.id;
+    let feeds = parse_opml(&opml).map_err(Errors::invalid_request)?;
+    let results = services
+        .subscription_service
+        .import_subscriptions(user_id, feeds)
+        .await
+        .map_err(Errors::from)?;
+    services
+        .subscription_service
+        .load_subscription_items(user_id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().json(ImportResults { results }))
+}
+
+pub async fn export_subscriptions(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let opml = services
+        .subscription_service
+        .export_subscriptions(&auth_user.id)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/x-opml; charset=utf-8")
+        .insert_header((
+            "Content-Disposition",
+            "attachment; filename=\"subscriptions.opml\"",
+        ))
+        .body(opml))
 }
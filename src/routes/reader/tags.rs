@@ -0,0 +1,110 @@
+use crate::common::error::Errors;
+use crate::common::Services;
+use crate::greader::stream::label_id;
+use crate::middlewares::auth::AuthUser;
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+const STATE_TAGS: [&str; 2] = [
+    "user/-/state/com.google/starred",
+    "user/-/state/com.google/read",
+];
+
+#[derive(Serialize)]
+pub struct Tag {
+    id: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    type_f: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Tags {
+    tags: Vec<Tag>,
+}
+
+pub async fn list_tags(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let labels = services
+        .subscription_service
+        .list_labels(&auth_user.id)
+        .await
+        .map_err(Errors::from)?;
+    let mut tags = STATE_TAGS
+        .iter()
+        .map(|id| Tag {
+            id: id.to_string(),
+            type_f: None,
+        })
+        .collect::<Vec<Tag>>();
+    let item_labels = services
+        .stream_service
+        .list_labels(&auth_user.id)
+        .await
+        .map_err(Errors::from)?
+        .into_iter()
+        .filter(|id| !labels.contains(id))
+        .collect::<Vec<String>>();
+    tags.extend(labels.into_iter().map(|id| Tag {
+        id,
+        type_f: Some("folder".to_string()),
+    }));
+    tags.extend(item_labels.into_iter().map(|id| Tag {
+        id,
+        type_f: Some("tag".to_string()),
+    }));
+    Ok(HttpResponse::Ok().json(Tags { tags }))
+}
+
+#[derive(Deserialize)]
+pub struct RenameTagRequest {
+    s: String,
+    dest: String,
+}
+
+pub async fn rename_tag(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    request: web::Form<RenameTagRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (from, to) = match (label_id(&request.s), label_id(&request.dest)) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return Err(Errors::invalid_request("Only labels can be renamed").into()),
+    };
+    services
+        .subscription_service
+        .rename_label(&auth_user.id, &from, &to)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+#[derive(Deserialize)]
+pub struct DisableTagRequest {
+    s: Option<String>,
+    t: Option<String>,
+}
+
+pub async fn disable_tag(
+    auth_user: web::ReqData<AuthUser>,
+    services: web::Data<Services>,
+    request: web::Form<DisableTagRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let label = match request
+        .s
+        .as_ref()
+        .or(request.t.as_ref())
+        .map(|tag| label_id(tag))
+    {
+        Some(Some(label)) => label,
+        Some(None) => return Err(Errors::invalid_request("Only labels can be disabled").into()),
+        None => return Err(Errors::invalid_request("Missing tag").into()),
+    };
+    services
+        .subscription_service
+        .remove_label(&auth_user.id, &label)
+        .await
+        .map_err(Errors::from)?;
+    Ok(HttpResponse::Ok().body("OK"))
+}
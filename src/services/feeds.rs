@@ -1,21 +1,169 @@
+use crate::common::error::Errors;
+use crate::database::feed_states::FeedState;
 use again::RetryPolicy;
-use anyhow::Result;
+use anyhow::{Error, Result};
 use async_trait::async_trait;
 use feed_rs::model::Feed;
 use feed_rs::parser;
-use futures::future::FutureExt;
+use futures::future::{join_all, FutureExt};
 use futures::select;
 use futures::stream::FuturesUnordered;
 use futures::stream::StreamExt;
 use reqwest;
+use reqwest::header::{
+    HeaderName, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER,
+};
+use reqwest::{Client, Response, StatusCode};
+use scraper::{Html, Selector};
 use std::collections::HashMap;
 use std::time::Duration;
+use url::Url;
+
+const FEED_TYPES: [&str; 4] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+    "application/json",
+];
+
+// Paths probed when a website doesn't link its feed.
+const COMMON_FEED_PATHS: [&str; 6] = [
+    "/feed",
+    "/rss",
+    "/atom.xml",
+    "/feed.xml",
+    "/rss.xml",
+    "/index.xml",
+];
+// Most of the probed paths don't exist, so they are tried once, shortly.
+// Discovery is part of a request, the website gets the same timeout.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+// Articles are fetched while refreshing the feeds, slow or huge pages are cut
+// short not to hold up the refresh.
+const PAGE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_PAGE_BYTES: usize = 2 * 1024 * 1024;
+
+// Hints from the publisher about how often the feed should be fetched.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct RefreshHints {
+    pub cache_control: Option<String>,
+    pub retry_after: Option<String>,
+    // Seconds, from sy:updatePeriod and sy:updateFrequency.
+    pub update_period_s: Option<i64>,
+}
+
+/// A feed found for a url, with its content if it was fetched to find it.
+pub struct DiscoveredFeed {
+    pub url: String,
+    pub feed: Option<Feed>,
+}
+
+pub struct FeedResponse {
+    pub status: u16,
+    // None if the feed is not modified since the last fetch.
+    pub feed: Option<Feed>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub hints: RefreshHints,
+}
 
 #[async_trait]
 pub trait FeedService {
     async fn get_feed(&self, url: &str) -> Result<Feed>;
 
     async fn get_feeds(&self, urls: Vec<&str>) -> HashMap<String, Result<Feed>>;
+
+    async fn get_feed_if_modified(&self, state: &FeedState) -> Result<FeedResponse>;
+
+    async fn get_feeds_if_modified(
+        &self,
+        states: Vec<&FeedState>,
+    ) -> HashMap<String, Result<FeedResponse>>;
+
+    /// Finds the feeds of a url, which is either a feed or a website.
+    async fn discover_feeds(&self, url: &str) -> Result<Vec<DiscoveredFeed>>;
+
+    /// Gets the html of a web page, like the article of an item.
+    async fn get_page(&self, url: &str) -> Result<String>;
+}
+
+fn header_value(response: &Response, name: HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+/// Returns the HTTP status which caused the error, if there is one.
+pub fn error_status(error: &anyhow::Error) -> Option<u16> {
+    if let Some(Errors::UnexpectedStatus { status, .. }) = error.downcast_ref::<Errors>() {
+        return Some(*status);
+    }
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .map(|status| status.as_u16())
+}
+
+fn feed_links(base: &Url, html: &str) -> Vec<String> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("link[rel~=alternate][href]").unwrap();
+    let mut links = vec![];
+    for element in document.select(&selector) {
+        let is_feed = element
+            .value()
+            .attr("type")
+            .is_some_and(|type_f| FEED_TYPES.contains(&type_f.trim().to_lowercase().as_str()));
+        if !is_feed {
+            continue;
+        }
+        if let Some(link) = element
+            .value()
+            .attr("href")
+            .and_then(|href| base.join(href.trim()).ok())
+            .map(String::from)
+        {
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+    links
+}
+
+fn element_text<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    let start_tag = format!("<{}>", name);
+    let start = body.find(&start_tag)? + start_tag.len();
+    let end = body[start..].find(&format!("</{}>", name))? + start;
+    Some(body[start..end].trim())
+}
+
+fn update_period_s(body: &str) -> Option<i64> {
+    let period_s = match element_text(body, "sy:updatePeriod")? {
+        "hourly" => 60 * 60,
+        "daily" => 24 * 60 * 60,
+        "weekly" => 7 * 24 * 60 * 60,
+        "monthly" => 30 * 24 * 60 * 60,
+        "yearly" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let frequency = element_text(body, "sy:updateFrequency")
+        .and_then(|frequency| frequency.parse::<i64>().ok())
+        .filter(|frequency| *frequency > 0)
+        .unwrap_or(1);
+    Some(period_s / frequency)
+}
+
+async fn probe_feed(client: &Client, url: &str) -> Result<Feed> {
+    let body = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    Ok(parser::parse(&body[..])?)
 }
 
 struct FeedServiceImpl {}
@@ -51,6 +199,148 @@ impl FeedService for FeedServiceImpl {
         }
         feeds_map
     }
+
+    async fn get_feed_if_modified(&self, state: &FeedState) -> Result<FeedResponse> {
+        let client = reqwest::Client::new();
+        let policy = RetryPolicy::fixed(Duration::from_millis(100))
+            .with_max_retries(3)
+            .with_jitter(true);
+        let response = policy
+            .retry(|| {
+                let mut request = client.get(&state.feed_url);
+                if let Some(ref etag) = state.etag {
+                    request = request.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(ref last_modified) = state.last_modified {
+                    request = request.header(IF_MODIFIED_SINCE, last_modified);
+                }
+                request.send()
+            })
+            .await?;
+        let etag = header_value(&response, ETAG);
+        let last_modified = header_value(&response, LAST_MODIFIED);
+        let mut hints = RefreshHints {
+            cache_control: header_value(&response, CACHE_CONTROL),
+            retry_after: header_value(&response, RETRY_AFTER),
+            update_period_s: None,
+        };
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(FeedResponse {
+                status: status.as_u16(),
+                feed: None,
+                etag: etag.or_else(|| state.etag.clone()),
+                last_modified: last_modified.or_else(|| state.last_modified.clone()),
+                hints,
+            });
+        }
+        if !status.is_success() {
+            return Err(Error::new(Errors::UnexpectedStatus {
+                url: state.feed_url.clone(),
+                status: status.as_u16(),
+                retry_after: hints.retry_after,
+            }));
+        }
+        let body = response.bytes().await?;
+        let feed = parser::parse(&body[..])?;
+        hints.update_period_s = std::str::from_utf8(&body).ok().and_then(update_period_s);
+        Ok(FeedResponse {
+            status: status.as_u16(),
+            feed: Some(feed),
+            etag,
+            last_modified,
+            hints,
+        })
+    }
+
+    async fn get_feeds_if_modified(
+        &self,
+        states: Vec<&FeedState>,
+    ) -> HashMap<String, Result<FeedResponse>> {
+        let mut all_feeds = FuturesUnordered::new();
+        states.into_iter().for_each(|state| {
+            all_feeds.push(
+                self.get_feed_if_modified(state)
+                    .map(move |response| (state.feed_url.clone(), response)),
+            );
+        });
+        let mut feeds_map = HashMap::new();
+        loop {
+            select! {
+              completed_feed = all_feeds.select_next_some() => {
+                feeds_map.insert(completed_feed.0, completed_feed.1);
+              },
+              complete => break,
+            }
+        }
+        feeds_map
+    }
+
+    async fn discover_feeds(&self, url: &str) -> Result<Vec<DiscoveredFeed>> {
+        let base = Url::parse(url)?;
+        let client = Client::builder().timeout(PROBE_TIMEOUT).build()?;
+        let policy = RetryPolicy::fixed(Duration::from_millis(100))
+            .with_max_retries(3)
+            .with_jitter(true);
+        let response = policy.retry(|| client.get(url).send());
+        let body = response.await?.error_for_status()?.text().await?;
+        if let Ok(feed) = parser::parse(body.as_bytes()) {
+            return Ok(vec![DiscoveredFeed {
+                url: url.to_string(),
+                feed: Some(feed),
+            }]);
+        }
+        let links = feed_links(&base, &body);
+        if !links.is_empty() {
+            return Ok(links
+                .into_iter()
+                .map(|url| DiscoveredFeed { url, feed: None })
+                .collect());
+        }
+        let candidates = COMMON_FEED_PATHS
+            .iter()
+            .filter_map(|path| base.join(path).ok())
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let feeds = join_all(
+            candidates
+                .iter()
+                .map(|candidate| probe_feed(&client, candidate)),
+        )
+        .await;
+        // Common paths are usually aliases of the same feed, take the first one.
+        Ok(candidates
+            .into_iter()
+            .zip(feeds)
+            .find_map(|(url, feed)| {
+                Some(DiscoveredFeed {
+                    url,
+                    feed: Some(feed.ok()?),
+                })
+            })
+            .into_iter()
+            .collect())
+    }
+
+    async fn get_page(&self, url: &str) -> Result<String> {
+        let client = Client::builder().timeout(PAGE_TIMEOUT).build()?;
+        let policy = RetryPolicy::fixed(Duration::from_millis(100))
+            .with_max_retries(3)
+            .with_jitter(true);
+        let mut response = policy
+            .retry(|| client.get(url).send())
+            .await?
+            .error_for_status()?;
+        let mut page = vec![];
+        while let Some(chunk) = response.chunk().await? {
+            page.extend_from_slice(&chunk);
+            if page.len() >= MAX_PAGE_BYTES {
+                page.truncate(MAX_PAGE_BYTES);
+                break;
+            }
+        }
+        Ok(String::from_utf8_lossy(&page).into_owned())
+    }
 }
 
 pub fn new_feed_service() -> Box<dyn FeedService + Send + Sync> {
@@ -60,6 +350,45 @@ pub fn new_feed_service() -> Box<dyn FeedService + Send + Sync> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Blog</title>
+  <id>urn:blog</id>
+  <updated>2022-01-01T00:00:00Z</updated>
+</feed>"#;
+
+    // Serves the feed at /atom.xml, with an ETag it honors like most servers,
+    // and a page too big to be read whole at /huge.html.
+    fn spawn_site() -> String {
+        let server = HttpServer::new(|| {
+            App::new()
+                .route(
+                    "/huge.html",
+                    web::get().to(|| async { "a".repeat(MAX_PAGE_BYTES + 1) }),
+                )
+                .route(
+                    "/atom.xml",
+                    web::get().to(|request: HttpRequest| async move {
+                        if request.headers().get(IF_NONE_MATCH).map(|v| v.as_bytes())
+                            == Some(b"\"1\"")
+                        {
+                            return HttpResponse::NotModified().finish();
+                        }
+                        HttpResponse::Ok()
+                            .insert_header((ETAG, "\"1\""))
+                            .content_type("application/atom+xml")
+                            .body(ATOM)
+                    }),
+                )
+        })
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let port = server.addrs()[0].port();
+        tokio::spawn(server.run());
+        format!("http://127.0.0.1:{}", port)
+    }
 
     #[tokio::test]
     async fn get_feed_should_work() {
@@ -99,4 +428,61 @@ mod tests {
             "瞎扯"
         );
     }
+
+    #[test]
+    fn feed_links_should_be_found() {
+        let html = r#"<html><head>
+            <link rel="stylesheet" href="/style.css">
+            <link rel="alternate" type="application/rss+xml" href="/rss.xml">
+            <link rel="alternate" type="Application/Atom+xml" href="https://other.com/atom.xml">
+            <link rel="alternate" type="text/html" href="/en">
+            <link rel="alternate" type="application/rss+xml" href="rss.xml">
+            </head><body></body></html>"#;
+        let base = Url::parse("https://blog.com/posts/").unwrap();
+        assert_eq!(
+            feed_links(&base, html),
+            vec![
+                "https://blog.com/rss.xml".to_string(),
+                "https://other.com/atom.xml".to_string(),
+                "https://blog.com/posts/rss.xml".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn update_period_should_be_parsed() {
+        let body = "<channel><sy:updatePeriod> daily </sy:updatePeriod>
+            <sy:updateFrequency>2</sy:updateFrequency></channel>";
+        assert_eq!(update_period_s(body), Some(12 * 60 * 60));
+        assert_eq!(
+            update_period_s("<sy:updatePeriod>hourly</sy:updatePeriod>"),
+            Some(60 * 60)
+        );
+        assert_eq!(update_period_s("<channel></channel>"), None);
+    }
+
+    #[tokio::test]
+    async fn get_feed_if_modified_should_return_not_modified() {
+        let feed_service = FeedServiceImpl {};
+        let mut state = FeedState::new(&format!("{}/atom.xml", spawn_site()));
+        let response = feed_service.get_feed_if_modified(&state).await.unwrap();
+        assert!(response.feed.is_some());
+        assert_eq!(response.etag.as_deref(), Some("\"1\""));
+
+        state.etag = response.etag;
+        state.last_modified = response.last_modified;
+        let response = feed_service.get_feed_if_modified(&state).await.unwrap();
+        assert_eq!(response.status, 304);
+        assert!(response.feed.is_none());
+    }
+
+    #[tokio::test]
+    async fn huge_pages_should_be_cut_short() {
+        let feed_service = FeedServiceImpl {};
+        let page = feed_service
+            .get_page(&format!("{}/huge.html", spawn_site()))
+            .await
+            .unwrap();
+        assert_eq!(page.len(), MAX_PAGE_BYTES);
+    }
 }
@@ -1,3 +1,10 @@
 pub mod feeds;
+pub mod opml;
+pub mod proxy;
+pub mod readability;
+pub mod retention;
+pub mod rules;
+pub mod sanitizer;
+pub mod scheduler;
 pub mod stream;
 pub mod subscriptions;
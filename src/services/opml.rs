@@ -0,0 +1,249 @@
+use crate::database::subscriptions::Subscription;
+use anyhow::Result;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::BTreeMap;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct OpmlFeed {
+    pub title: String,
+    pub feed_url: String,
+    pub categories: Vec<String>,
+}
+
+enum Outline {
+    Feed { title: String, feed_url: String },
+    Folder { title: String },
+}
+
+fn parse_outline(element: &BytesStart) -> Result<Outline> {
+    let mut text = None;
+    let mut title = None;
+    let mut feed_url = None;
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        let value = attribute.unescape_value()?.into_owned();
+        match attribute.key.as_ref() {
+            b"text" => text = Some(value),
+            b"title" => title = Some(value),
+            b"xmlUrl" => feed_url = Some(value),
+            _ => {}
+        }
+    }
+    let title = title.or(text).unwrap_or_default();
+    Ok(match feed_url {
+        Some(feed_url) => Outline::Feed { title, feed_url },
+        None => Outline::Folder { title },
+    })
+}
+
+fn label(folders: &Vec<Option<String>>) -> Vec<String> {
+    folders
+        .iter()
+        .flatten()
+        .filter(|folder| !folder.is_empty())
+        .map(|folder| format!("user/-/label/{}", folder))
+        .collect()
+}
+
+/// Parses all feeds out of an OPML document. Every folder enclosing a feed
+/// becomes one `user/-/label/...` category of that feed.
+pub fn parse_opml(opml: &str) -> Result<Vec<OpmlFeed>> {
+    let mut reader = Reader::from_str(opml);
+    reader.trim_text(true);
+    let mut feeds = vec![];
+    // One entry per open outline, None for outlines which are not folders.
+    let mut folders: Vec<Option<String>> = vec![];
+    loop {
+        match reader.read_event()? {
+            Event::Start(ref e) if e.name().as_ref() == b"outline" => match parse_outline(e)? {
+                Outline::Feed { title, feed_url } => {
+                    feeds.push(OpmlFeed {
+                        title,
+                        feed_url,
+                        categories: label(&folders),
+                    });
+                    folders.push(None);
+                }
+                Outline::Folder { title } => folders.push(Some(title)),
+            },
+            Event::Empty(ref e) if e.name().as_ref() == b"outline" => {
+                if let Outline::Feed { title, feed_url } = parse_outline(e)? {
+                    feeds.push(OpmlFeed {
+                        title,
+                        feed_url,
+                        categories: label(&folders),
+                    });
+                }
+            }
+            Event::End(ref e) if e.name().as_ref() == b"outline" => {
+                folders.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(feeds)
+}
+
+fn render_feed(subscription: &Subscription, indent: &str) -> String {
+    format!(
+        "{}<outline type=\"rss\" text=\"{}\" title=\"{}\" xmlUrl=\"{}\" htmlUrl=\"{}\" />\n",
+        indent,
+        escape(&subscription.title),
+        escape(&subscription.title),
+        escape(&subscription.feed_url),
+        escape(&subscription.url)
+    )
+}
+
+/// Renders subscriptions as an OPML 2.0 document. Feeds are grouped into one
+/// folder per label, so a feed with several labels shows up in several folders.
+pub fn render_opml(subscriptions: &Vec<Subscription>) -> String {
+    let mut folders: BTreeMap<&str, Vec<&Subscription>> = BTreeMap::new();
+    let mut unlabeled = vec![];
+    for subscription in subscriptions {
+        let labels = subscription
+            .categories()
+            .into_iter()
+            .filter_map(|category| category.strip_prefix("user/-/label/"))
+            .collect::<Vec<&str>>();
+        if labels.is_empty() {
+            unlabeled.push(subscription);
+        }
+        for label in labels {
+            folders.entry(label).or_default().push(subscription);
+        }
+    }
+
+    let mut body = String::new();
+    for subscription in unlabeled {
+        body.push_str(&render_feed(subscription, "    "));
+    }
+    for (label, folder_subscriptions) in folders {
+        body.push_str(&format!(
+            "    <outline text=\"{}\" title=\"{}\">\n",
+            escape(label),
+            escape(label)
+        ));
+        for subscription in folder_subscriptions {
+            body.push_str(&render_feed(subscription, "      "));
+        }
+        body.push_str("    </outline>\n");
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<opml version=\"2.0\">
+  <head>
+    <title>Near-RSS Subscriptions</title>
+  </head>
+  <body>
+{}  </body>
+</opml>
+",
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_opml_should_work() {
+        let opml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="1.0">
+  <head><title>Subscriptions</title></head>
+  <body>
+    <outline text="Root" xmlUrl="https://root.com/feed" />
+    <outline text="Tech" title="Tech">
+      <outline text="Blog" title="A &amp; B" type="rss" xmlUrl="https://blog.com/atom.xml" />
+      <outline text="Rust">
+        <outline text="Rust Blog" xmlUrl="https://blog.rust-lang.org/feed.xml"></outline>
+      </outline>
+    </outline>
+  </body>
+</opml>"#;
+        assert_eq!(
+            parse_opml(opml).unwrap(),
+            vec![
+                OpmlFeed {
+                    title: "Root".to_string(),
+                    feed_url: "https://root.com/feed".to_string(),
+                    categories: vec![],
+                },
+                OpmlFeed {
+                    title: "A & B".to_string(),
+                    feed_url: "https://blog.com/atom.xml".to_string(),
+                    categories: vec!["user/-/label/Tech".to_string()],
+                },
+                OpmlFeed {
+                    title: "Rust Blog".to_string(),
+                    feed_url: "https://blog.rust-lang.org/feed.xml".to_string(),
+                    categories: vec![
+                        "user/-/label/Tech".to_string(),
+                        "user/-/label/Rust".to_string(),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_opml_should_fail() {
+        assert!(parse_opml("<opml><body><outline text=\"a\"></body></opml>").is_err());
+    }
+
+    fn new_subscription(feed_url: &str, title: &str, joined_categories: &str) -> Subscription {
+        Subscription {
+            user_id: "user_id".to_string(),
+            id: format!("feed/{}", feed_url),
+            url: "https://home.com".to_string(),
+            title: title.to_string(),
+            description: "description".to_string(),
+            feed_url: feed_url.to_string(),
+            joined_categories: joined_categories.to_string(),
+            last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
+        }
+    }
+
+    #[test]
+    fn render_opml_should_be_parsable() {
+        let subscriptions = vec![
+            new_subscription("https://a.com/feed?x=1&y=2", "A <&> A", ""),
+            new_subscription(
+                "https://b.com/atom.xml",
+                "B",
+                ",user/-/label/Tech,user/-/label/News",
+            ),
+        ];
+        let opml = render_opml(&subscriptions);
+        assert_eq!(
+            parse_opml(&opml).unwrap(),
+            vec![
+                OpmlFeed {
+                    title: "A <&> A".to_string(),
+                    feed_url: "https://a.com/feed?x=1&y=2".to_string(),
+                    categories: vec![],
+                },
+                OpmlFeed {
+                    title: "B".to_string(),
+                    feed_url: "https://b.com/atom.xml".to_string(),
+                    categories: vec!["user/-/label/News".to_string()],
+                },
+                OpmlFeed {
+                    title: "B".to_string(),
+                    feed_url: "https://b.com/atom.xml".to_string(),
+                    categories: vec!["user/-/label/Tech".to_string()],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,569 @@
+use crate::common::error::Errors;
+use crate::common::new_id;
+use crate::configuration::ProxyConfiguration;
+use crate::services::sanitizer::rewrite_image_sources;
+use actix_web::web::Bytes;
+use anyhow::{Error, Result};
+use futures::stream::{self, BoxStream, StreamExt};
+use hmac::{Hmac, Mac};
+use reqwest::header::{CONTENT_TYPE, LOCATION};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Response};
+use sha2::Sha256;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use url::{Host, Url};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const MEDIA_TYPES: [&str; 3] = ["image/", "video/", "audio/"];
+
+// Every hop of a redirect is checked like the first url.
+const MAX_REDIRECTS: usize = 5;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Slow upstreams shouldn't hold the proxy requests open.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Media {
+    pub content_type: String,
+    pub body: BoxStream<'static, Result<Bytes>>,
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // This network, 0.0.0.0/8.
+                || a == 0
+                // Shared address space of carrier-grade NATs, 100.64.0.0/10.
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local addresses, fc00::/7.
+                    || first & 0xfe00 == 0xfc00
+                    // Link local addresses, fe80::/10.
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+// Only remote http urls are proxied, so feeds can't make the server request
+// itself or its network. Domains are checked again when they are resolved.
+fn is_public_url(url: &Url) -> bool {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => domain != "localhost" && !domain.ends_with(".localhost"),
+        Some(Host::Ipv4(ip)) => is_public_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_public_ip(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+fn is_proxyable(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| is_public_url(&url))
+}
+
+// The address to connect to, if none of the addresses of the host is private.
+fn public_addr(addrs: Vec<SocketAddr>) -> Option<SocketAddr> {
+    if addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+        addrs.into_iter().next()
+    } else {
+        None
+    }
+}
+
+// A client which connects to the checked address of the host of the url, so
+// the host can't resolve to another address when it's requested.
+async fn pinned_client(url: &Url) -> Result<Client> {
+    let invalid_url = || {
+        Error::new(Errors::InvalidMediaUrl {
+            url: url.to_string(),
+        })
+    };
+    if !is_public_url(url) {
+        return Err(invalid_url());
+    }
+    let builder = Client::builder()
+        .redirect(Policy::none())
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT);
+    let builder = match url.host() {
+        Some(Host::Domain(domain)) => {
+            let host = (
+                domain.to_string(),
+                url.port_or_known_default().unwrap_or(80),
+            );
+            let addrs = tokio::task::spawn_blocking(move || host.to_socket_addrs())
+                .await??
+                .collect::<Vec<SocketAddr>>();
+            let addr = public_addr(addrs).ok_or_else(invalid_url)?;
+            builder.resolve(domain, addr)
+        }
+        _ => builder,
+    };
+    Ok(builder.build()?)
+}
+
+// Reads a file in chunks, without blocking the runtime.
+fn read_file(file: File) -> BoxStream<'static, Result<Bytes>> {
+    stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let read = tokio::task::spawn_blocking(move || {
+            let mut buffer = vec![0; CHUNK_SIZE];
+            let size = file.read(&mut buffer)?;
+            buffer.truncate(size);
+            Ok::<_, Error>((file, buffer))
+        })
+        .await
+        .map_err(Error::new)
+        .and_then(|read| read);
+        match read {
+            Ok((_, buffer)) if buffer.is_empty() => None,
+            Ok((file, buffer)) => Some((Ok(Bytes::from(buffer)), Some(file))),
+            Err(err) => Some((Err(err), None)),
+        }
+    })
+    .boxed()
+}
+
+// Removes the oldest cached media until the cache fits in max_bytes.
+fn evict_cache(cache_path: &Path, max_bytes: u64) -> Result<()> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(cache_path)? {
+        let path = entry?.path();
+        if path.extension().is_some() {
+            continue;
+        }
+        let metadata = std::fs::metadata(&path)?;
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        files.push((modified, metadata.len(), path));
+    }
+    let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+    files.sort();
+    for (_, len, path) in files {
+        if size <= max_bytes {
+            break;
+        }
+        // The type goes first, so a removed body is never read. Files which
+        // are already removed by another request are skipped.
+        let _ = std::fs::remove_file(path.with_extension("type"));
+        let _ = std::fs::remove_file(&path);
+        size -= len;
+    }
+    Ok(())
+}
+
+// Media being downloaded, which is written to the cache as it's read.
+struct Download {
+    url: String,
+    response: Response,
+    size: u64,
+    max_size_bytes: u64,
+    cache: Option<CacheWriter>,
+}
+
+struct CacheWriter {
+    file: File,
+    partial_file: PathBuf,
+    body_file: PathBuf,
+    type_file: PathBuf,
+    content_type: String,
+    cache_path: PathBuf,
+    max_bytes: u64,
+}
+
+impl CacheWriter {
+    async fn write(self, chunk: Bytes) -> Result<CacheWriter> {
+        tokio::task::spawn_blocking(move || {
+            let mut writer = self;
+            match writer.file.write_all(&chunk) {
+                Ok(()) => Ok(writer),
+                Err(err) => {
+                    let _ = std::fs::remove_file(&writer.partial_file);
+                    Err(Error::new(err))
+                }
+            }
+        })
+        .await?
+    }
+
+    // The body is moved in place before the type is written, so a partial
+    // body is never read.
+    async fn finish(self) -> Result<()> {
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            std::fs::rename(&self.partial_file, &self.body_file)?;
+            std::fs::write(&self.type_file, &self.content_type)?;
+            evict_cache(&self.cache_path, self.max_bytes)
+        })
+        .await?
+    }
+
+    fn discard(self) {
+        let _ = std::fs::remove_file(&self.partial_file);
+    }
+}
+
+impl Download {
+    // Reads the next chunk of the body, and fails when the body is too large.
+    async fn next(mut self) -> Option<(Result<Bytes>, Option<Download>)> {
+        let chunk = match self.response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => {
+                if let Some(cache) = self.cache {
+                    if let Err(err) = cache.finish().await {
+                        tracing::warn!("Failed to cache {}: {}", self.url, err);
+                    }
+                }
+                return None;
+            }
+            Err(err) => {
+                if let Some(cache) = self.cache {
+                    cache.discard();
+                }
+                return Some((Err(Error::new(err)), None));
+            }
+        };
+        self.size += chunk.len() as u64;
+        if self.size > self.max_size_bytes {
+            if let Some(cache) = self.cache {
+                cache.discard();
+            }
+            let err = Error::new(Errors::MediaTooLarge {
+                url: self.url,
+                max_size_bytes: self.max_size_bytes,
+            });
+            return Some((Err(err), None));
+        }
+        if let Some(cache) = self.cache.take() {
+            match cache.write(chunk.clone()).await {
+                Ok(cache) => self.cache = Some(cache),
+                Err(err) => tracing::warn!("Failed to cache {}: {}", self.url, err),
+            }
+        }
+        Some((Ok(chunk), Some(self)))
+    }
+}
+
+/// Signs the urls of images in items, and gets the images for the readers.
+#[derive(Clone)]
+pub struct ImageProxy {
+    key: Vec<u8>,
+    max_size_bytes: u64,
+    cache_path: Option<PathBuf>,
+    cache_max_bytes: u64,
+}
+
+impl ImageProxy {
+    pub fn new(configuration: &ProxyConfiguration) -> ImageProxy {
+        let secret = if configuration.secret.is_empty() {
+            new_id(64)
+        } else {
+            configuration.secret.clone()
+        };
+        ImageProxy {
+            key: secret.into_bytes(),
+            max_size_bytes: configuration.max_size_bytes,
+            cache_path: configuration.cache_path.as_ref().map(PathBuf::from),
+            cache_max_bytes: configuration.cache_max_bytes,
+        }
+    }
+
+    fn mac(&self, url: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC takes keys of any size");
+        mac.update(url.as_bytes());
+        mac
+    }
+
+    pub fn signature(&self, url: &str) -> String {
+        hex::encode(self.mac(url).finalize().into_bytes())
+    }
+
+    /// The path of the url on the proxy. The url is hex encoded, so it's one
+    /// path segment whatever it contains.
+    pub fn proxy_url(&self, url: &str) -> String {
+        format!("/proxy/{}/{}", self.signature(url), hex::encode(url))
+    }
+
+    /// Decodes the url of a proxy path, if it's signed by this proxy.
+    pub fn verify(&self, signature: &str, encoded_url: &str) -> Option<String> {
+        let url = String::from_utf8(hex::decode(encoded_url).ok()?).ok()?;
+        let signature = hex::decode(signature).ok()?;
+        self.mac(&url).verify_slice(&signature).ok()?;
+        Some(url)
+    }
+
+    /// Points the images in sanitized html to the proxy.
+    pub fn rewrite_images(&self, html: &str) -> String {
+        let proxy = self.clone();
+        rewrite_image_sources(html, move |src| {
+            if is_proxyable(src) {
+                Some(proxy.proxy_url(src))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn cache_files(&self, url: &str) -> Option<(PathBuf, PathBuf)> {
+        let signature = self.signature(url);
+        self.cache_path.as_ref().map(|path| {
+            (
+                path.join(&signature),
+                path.join(format!("{}.type", signature)),
+            )
+        })
+    }
+
+    async fn read_cache(&self, url: &str) -> Option<Media> {
+        let (body_file, type_file) = self.cache_files(url)?;
+        let (content_type, file) = tokio::task::spawn_blocking(move || {
+            Some((
+                std::fs::read_to_string(type_file).ok()?,
+                File::open(body_file).ok()?,
+            ))
+        })
+        .await
+        .ok()??;
+        Some(Media {
+            content_type,
+            body: read_file(file),
+        })
+    }
+
+    // Starts to cache the body of the url, if a cache is configured.
+    async fn new_cache_writer(&self, url: &str, content_type: &str) -> Result<Option<CacheWriter>> {
+        let (body_file, type_file) = match self.cache_files(url) {
+            Some(files) => files,
+            None => return Ok(None),
+        };
+        let cache_path = self.cache_path.clone().unwrap();
+        // Requests of the same url write their own partial files.
+        let partial_file = body_file.with_extension(format!("{}.partial", new_id(8)));
+        let content_type = content_type.to_string();
+        let max_bytes = self.cache_max_bytes;
+        tokio::task::spawn_blocking(move || -> Result<Option<CacheWriter>> {
+            std::fs::create_dir_all(&cache_path)?;
+            Ok(Some(CacheWriter {
+                file: File::create(&partial_file)?,
+                partial_file,
+                body_file,
+                type_file,
+                content_type,
+                cache_path,
+                max_bytes,
+            }))
+        })
+        .await?
+    }
+
+    // Gets the url, and follows its redirects to public urls only.
+    async fn get(&self, url: &str) -> Result<Response> {
+        let mut url = Url::parse(url).map_err(|_| Errors::InvalidMediaUrl {
+            url: url.to_string(),
+        })?;
+        for _ in 0..=MAX_REDIRECTS {
+            let response = pinned_client(&url).await?.get(url.clone()).send().await?;
+            if !response.status().is_redirection() {
+                return Ok(response.error_for_status()?);
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| Errors::FetchFailed {
+                    reason: format!("{} redirects without a location", url),
+                })?;
+            url = url.join(location).map_err(|_| Errors::InvalidMediaUrl {
+                url: location.to_string(),
+            })?;
+        }
+        Err(Error::new(Errors::FetchFailed {
+            reason: format!("{} redirects more than {} times", url, MAX_REDIRECTS),
+        }))
+    }
+
+    /// Gets an image, or other media, which is not larger than the limit. The
+    /// body is streamed, and fails once it's larger than the limit.
+    pub async fn fetch(&self, url: &str) -> Result<Media> {
+        if !is_proxyable(url) {
+            return Err(Error::new(Errors::InvalidMediaUrl {
+                url: url.to_string(),
+            }));
+        }
+        if let Some(media) = self.read_cache(url).await {
+            return Ok(media);
+        }
+        let response = self.get(url).await?;
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        if !MEDIA_TYPES
+            .iter()
+            .any(|media_type| content_type.starts_with(media_type))
+        {
+            return Err(Error::new(Errors::UnsupportedMedia {
+                url: url.to_string(),
+                content_type,
+            }));
+        }
+        // Content-Length may be missing, or wrong, so the body is checked too.
+        if response.content_length().unwrap_or(0) > self.max_size_bytes {
+            return Err(Error::new(Errors::MediaTooLarge {
+                url: url.to_string(),
+                max_size_bytes: self.max_size_bytes,
+            }));
+        }
+        let cache = self
+            .new_cache_writer(url, &content_type)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::warn!("Failed to cache {}: {}", url, err);
+                None
+            });
+        let download = Download {
+            url: url.to_string(),
+            response,
+            size: 0,
+            max_size_bytes: self.max_size_bytes,
+            cache,
+        };
+        Ok(Media {
+            content_type,
+            body: stream::unfold(
+                Some(download),
+                |download| async move { download?.next().await },
+            )
+            .boxed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_proxy() -> ImageProxy {
+        ImageProxy::new(&ProxyConfiguration {
+            secret: "secret".to_string(),
+            ..ProxyConfiguration::default()
+        })
+    }
+
+    #[test]
+    fn proxy_url_should_be_verified() {
+        let proxy = new_proxy();
+        let url = "https://blog.com/images/a.png?size=2";
+        let proxy_url = proxy.proxy_url(url);
+        let parts = proxy_url.split('/').collect::<Vec<&str>>();
+        assert_eq!(parts[1], "proxy");
+        assert_eq!(proxy.verify(parts[2], parts[3]), Some(url.to_string()));
+
+        let other_url = hex::encode("https://blog.com/images/b.png");
+        assert_eq!(proxy.verify(parts[2], &other_url), None);
+        assert_eq!(proxy.verify("not-hex", parts[3]), None);
+        let other_proxy = ImageProxy::new(&ProxyConfiguration::default());
+        assert_eq!(other_proxy.verify(parts[2], parts[3]), None);
+    }
+
+    #[test]
+    fn only_remote_images_should_be_rewritten() {
+        let proxy = new_proxy();
+        let html = r#"<img src="https://blog.com/a.png"><img src="http://127.0.0.1/b.png"><img src="data:image/png;base64,AAAA">"#;
+        assert_eq!(
+            proxy.rewrite_images(html),
+            format!(
+                r#"<img src="{}"><img src="http://127.0.0.1/b.png"><img>"#,
+                proxy.proxy_url("https://blog.com/a.png")
+            )
+        );
+    }
+
+    #[test]
+    fn local_urls_should_not_be_proxyable() {
+        assert!(is_proxyable("https://blog.com/a.png"));
+        assert!(is_proxyable("http://8.8.8.8/a.png"));
+        assert!(!is_proxyable("http://localhost:8000/api"));
+        assert!(!is_proxyable("http://10.0.0.1/a.png"));
+        assert!(!is_proxyable("http://[::1]/a.png"));
+        assert!(!is_proxyable("file:///etc/passwd"));
+        assert!(!is_proxyable("/proxy/a/b"));
+        assert!(!is_proxyable("http://0.0.0.0/a.png"));
+        assert!(!is_proxyable("http://100.64.0.1/a.png"));
+        assert!(!is_proxyable("http://169.254.169.254/latest"));
+        assert!(!is_proxyable("http://[fd00::1]/a.png"));
+        assert!(!is_proxyable("http://[fe80::1]/a.png"));
+        assert!(!is_proxyable("http://[::ffff:127.0.0.1]/a.png"));
+        assert!(is_proxyable("http://100.128.0.1/a.png"));
+        assert!(is_proxyable("http://[2001:4860:4860::8888]/a.png"));
+    }
+
+    #[test]
+    fn hosts_with_any_private_address_should_be_rejected() {
+        let addr = |ip: &str| SocketAddr::new(ip.parse().unwrap(), 80);
+        assert_eq!(
+            public_addr(vec![addr("8.8.8.8"), addr("8.8.4.4")]),
+            Some(addr("8.8.8.8"))
+        );
+        assert_eq!(public_addr(vec![addr("8.8.8.8"), addr("127.0.0.1")]), None);
+        assert_eq!(public_addr(vec![addr("::1")]), None);
+        assert_eq!(public_addr(vec![]), None);
+    }
+
+    #[tokio::test]
+    async fn local_urls_should_not_be_requested() {
+        for url in ["http://localhost:8000/a.png", "http://127.0.0.1/a.png"] {
+            let url = Url::parse(url).unwrap();
+            assert!(pinned_client(&url).await.is_err());
+        }
+    }
+
+    #[test]
+    fn cache_should_evict_the_oldest_media() {
+        let cache_path = std::env::temp_dir().join(format!("proxy-{}", new_id(8)));
+        std::fs::create_dir_all(&cache_path).unwrap();
+        let now = SystemTime::now();
+        for (i, name) in ["old", "new"].iter().enumerate() {
+            let file = cache_path.join(name);
+            std::fs::write(&file, [0; 10]).unwrap();
+            std::fs::write(file.with_extension("type"), "image/png").unwrap();
+            File::options()
+                .write(true)
+                .open(&file)
+                .unwrap()
+                .set_modified(now - std::time::Duration::from_secs(60 * (2 - i as u64)))
+                .unwrap();
+        }
+
+        evict_cache(&cache_path, 20).unwrap();
+        assert!(cache_path.join("old").exists());
+        evict_cache(&cache_path, 15).unwrap();
+        assert!(!cache_path.join("old").exists());
+        assert!(!cache_path.join("old.type").exists());
+        assert!(cache_path.join("new").exists());
+        std::fs::remove_dir_all(cache_path).unwrap();
+    }
+}
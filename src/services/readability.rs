@@ -0,0 +1,124 @@
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashMap;
+
+// Pages with less text than this in their main content are likely not articles.
+const MIN_CONTENT_LENGTH: usize = 200;
+
+const POSITIVE_HINTS: [&str; 6] = ["article", "content", "entry", "main", "post", "text"];
+const NEGATIVE_HINTS: [&str; 9] = [
+    "comment", "footer", "header", "menu", "nav", "related", "share", "sidebar", "social",
+];
+
+fn text_length(element: &ElementRef) -> usize {
+    element.text().map(|text| text.trim().chars().count()).sum()
+}
+
+// Multiplier from the class and id of the element.
+fn hint_weight(element: &ElementRef) -> f64 {
+    let names = format!(
+        "{} {}",
+        element.value().attr("class").unwrap_or(""),
+        element.value().attr("id").unwrap_or("")
+    )
+    .to_lowercase();
+    if NEGATIVE_HINTS.iter().any(|hint| names.contains(hint)) {
+        0.25
+    } else if POSITIVE_HINTS.iter().any(|hint| names.contains(hint)) {
+        1.25
+    } else {
+        1.0
+    }
+}
+
+fn link_density(element: &ElementRef) -> f64 {
+    let length = text_length(element);
+    if length == 0 {
+        return 1.0;
+    }
+    let selector = Selector::parse("a").unwrap();
+    let link_length: usize = element.select(&selector).map(|a| text_length(&a)).sum();
+    link_length as f64 / length as f64
+}
+
+/// Finds the main content of an article page, like Readability does.
+///
+/// Every paragraph scores its parent, and half of it to its grandparent. The
+/// element with the best score, discounted by its links, is the main content.
+pub fn extract_main_content(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let paragraphs = Selector::parse("p, pre, blockquote").unwrap();
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&paragraphs) {
+        let length = text_length(&paragraph);
+        if length < 25 {
+            continue;
+        }
+        // Longer paragraphs and paragraphs with more commas are more likely to be prose.
+        let score = 1.0
+            + paragraph
+                .text()
+                .map(|text| text.matches(',').count())
+                .sum::<usize>() as f64
+            + (length / 100).min(3) as f64;
+        let mut ancestors = paragraph.ancestors().filter_map(ElementRef::wrap);
+        if let Some(parent) = ancestors.next() {
+            *scores.entry(parent.id()).or_insert(0.0) += score;
+        }
+        if let Some(grandparent) = ancestors.next() {
+            *scores.entry(grandparent.id()).or_insert(0.0) += score / 2.0;
+        }
+    }
+    let best = scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let element = ElementRef::wrap(document.tree.get(id)?)?;
+            if matches!(element.value().name(), "html" | "body") {
+                return None;
+            }
+            Some((
+                element,
+                score * hint_weight(&element) * (1.0 - link_density(&element)),
+            ))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(element, _)| element)?;
+    if text_length(&best) < MIN_CONTENT_LENGTH {
+        return None;
+    }
+    Some(best.inner_html().trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paragraph(sentence: &str) -> String {
+        format!("<p>{}</p>", vec![sentence; 5].join(" "))
+    }
+
+    #[test]
+    fn main_content_should_be_extracted() {
+        let html = format!(
+            r#"<html><body>
+            <div class="nav"><a href="/">Home</a> <a href="/about">About</a></div>
+            <div class="post-content"><h1>Title</h1>{}{}</div>
+            <div class="comments">{}</div>
+            </body></html>"#,
+            paragraph("The article, which is long enough, keeps going."),
+            paragraph("Another paragraph of the article, with more words."),
+            paragraph("A comment, short."),
+        );
+        let content = extract_main_content(&html).unwrap();
+        assert!(content.starts_with("<h1>Title</h1>"));
+        assert!(content.contains("Another paragraph of the article"));
+        assert!(!content.contains("A comment"));
+        assert!(!content.contains("About"));
+    }
+
+    #[test]
+    fn short_pages_should_have_no_main_content() {
+        let html = "<html><body><div><p>Only a teaser, nothing else here.</p></div></body></html>";
+        assert_eq!(extract_main_content(html), None);
+        assert_eq!(extract_main_content(""), None);
+    }
+}
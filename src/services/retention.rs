@@ -0,0 +1,200 @@
+use crate::configuration::RetentionConfiguration;
+use crate::database::items::{CleanUp, Item};
+use crate::database::retentions::Retention;
+use serde::Serialize;
+use std::collections::HashMap;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// The retention of a subscription, after applying the overrides.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<i64>,
+    pub max_items: Option<i64>,
+    pub keep_starred: bool,
+}
+
+impl From<&RetentionConfiguration> for RetentionPolicy {
+    fn from(configuration: &RetentionConfiguration) -> Self {
+        RetentionPolicy {
+            max_age_days: configuration.max_age_days,
+            max_items: configuration.max_items,
+            keep_starred: configuration.keep_starred,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    fn with_override(self, retention: Option<&Retention>) -> RetentionPolicy {
+        match retention {
+            Some(retention) => RetentionPolicy {
+                max_age_days: retention.max_age_days.or_inherited(self.max_age_days),
+                max_items: retention.max_items.or_inherited(self.max_items),
+                keep_starred: retention.keep_starred.unwrap_or(self.keep_starred),
+            },
+            None => self,
+        }
+    }
+
+    /// Items created at or before this are deleted.
+    pub fn oldest_allowed_time_ms(&self, now_ms: i64) -> Option<i64> {
+        self.max_age_days.map(|days| now_ms - days * DAY_MS)
+    }
+
+    pub fn clean_up(&self, now_ms: i64) -> CleanUp {
+        CleanUp {
+            older_than_ms: self.oldest_allowed_time_ms(now_ms),
+            max_items: self.max_items,
+            keep_starred: self.keep_starred,
+        }
+    }
+
+    /// Keeps the items from a feed which survive the clean up, otherwise they
+    /// would come back as unread on every refresh.
+    pub fn retain(&self, mut items: Vec<Item>, now_ms: i64) -> Vec<Item> {
+        if let Some(oldest_allowed_time_ms) = self.oldest_allowed_time_ms(now_ms) {
+            items.retain(|item| item.created_at_ms > oldest_allowed_time_ms);
+        }
+        if let Some(max_items) = self.max_items {
+            items.sort_by_key(|item| std::cmp::Reverse(item.created_at_ms));
+            items.truncate(max_items.max(0) as usize);
+        }
+        items
+    }
+}
+
+/// Finds the retention of any subscription, from the configuration and the
+/// overrides of the users.
+pub struct RetentionPolicies {
+    default: RetentionPolicy,
+    // Keyed by user id and subscription id, which is empty for the user.
+    overrides: HashMap<(String, String), Retention>,
+}
+
+impl RetentionPolicies {
+    pub fn new(configuration: &RetentionConfiguration, retentions: Vec<Retention>) -> Self {
+        RetentionPolicies {
+            default: RetentionPolicy::from(configuration),
+            overrides: retentions
+                .into_iter()
+                .map(|retention| {
+                    (
+                        (retention.user_id.clone(), retention.subscription_id.clone()),
+                        retention,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn default_policy(&self) -> &RetentionPolicy {
+        &self.default
+    }
+
+    pub fn user_policy(&self, user_id: &str) -> RetentionPolicy {
+        self.default
+            .clone()
+            .with_override(self.overrides.get(&(user_id.to_string(), String::new())))
+    }
+
+    pub fn policy(&self, user_id: &str, subscription_id: &str) -> RetentionPolicy {
+        self.user_policy(user_id).with_override(
+            self.overrides
+                .get(&(user_id.to_string(), subscription_id.to_string())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::retentions::Limit;
+
+    fn new_retention(
+        user_id: &str,
+        subscription_id: &str,
+        max_age_days: Limit,
+        max_items: Limit,
+        keep_starred: Option<bool>,
+    ) -> Retention {
+        Retention {
+            user_id: user_id.to_string(),
+            subscription_id: subscription_id.to_string(),
+            max_age_days,
+            max_items,
+            keep_starred,
+        }
+    }
+
+    #[test]
+    fn overrides_should_be_inherited() {
+        let policies = RetentionPolicies::new(
+            &RetentionConfiguration::default(),
+            vec![
+                new_retention("a", "", Limit::Limited(30), Limit::Inherited, None),
+                new_retention(
+                    "a",
+                    "feed/1",
+                    Limit::Inherited,
+                    Limit::Limited(10),
+                    Some(false),
+                ),
+                new_retention("b", "feed/1", Limit::Limited(7), Limit::Inherited, None),
+                new_retention("b", "feed/2", Limit::Unlimited, Limit::Inherited, None),
+            ],
+        );
+        assert_eq!(
+            policies.policy("a", "feed/1"),
+            RetentionPolicy {
+                max_age_days: Some(30),
+                max_items: Some(10),
+                keep_starred: false,
+            }
+        );
+        assert_eq!(policies.policy("a", "feed/2"), policies.user_policy("a"));
+        assert_eq!(policies.policy("b", "feed/1").max_age_days, Some(7));
+        // Kept forever, though the configuration limits the age.
+        assert_eq!(policies.policy("b", "feed/2").max_age_days, None);
+        assert_eq!(policies.policy("c", "feed/1"), *policies.default_policy());
+        assert_eq!(policies.default_policy().max_age_days, Some(14));
+        assert!(policies.default_policy().keep_starred);
+    }
+
+    #[test]
+    fn retain_should_keep_latest_items() {
+        let policy = RetentionPolicy {
+            max_age_days: Some(1),
+            max_items: Some(2),
+            keep_starred: true,
+        };
+        let now_ms = 10 * DAY_MS;
+        let new_item = |external_id: &str, created_at_ms: i64| {
+            Item::new_item("u", "s", external_id, "", "", "", "", "", created_at_ms)
+        };
+        let items = policy.retain(
+            vec![
+                new_item("1", now_ms - 2 * DAY_MS),
+                new_item("2", now_ms - 3),
+                new_item("3", now_ms - 1),
+                new_item("4", now_ms - 2),
+            ],
+            now_ms,
+        );
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.external_id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["3", "4"]
+        );
+
+        let forever = RetentionPolicy {
+            max_age_days: None,
+            max_items: None,
+            keep_starred: true,
+        };
+        assert_eq!(forever.retain(vec![new_item("1", 0)], now_ms).len(), 1);
+        assert_eq!(forever.clean_up(now_ms).older_than_ms, None);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::common::error::Errors;
+use crate::common::{current_time_ms, new_id, PageOption};
+use crate::database::items::{Item, ItemRepository};
+use crate::database::rules::{MatchType, Rule, RuleAction, RuleField, RuleRepository};
+use crate::database::subscriptions::{Subscription, SubscriptionRepository};
+use crate::greader::stream::label_id;
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Recent items checked by a dry run.
+const DRY_RUN_ITEMS: usize = 200;
+// Bounds the memory of a compiled pattern, since users write the patterns.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+fn invalid_rule(reason: &str) -> Error {
+    Error::new(Errors::InvalidRule {
+        reason: reason.to_string(),
+    })
+}
+
+enum Matcher {
+    // Lowercased, substrings are matched ignoring case.
+    Substring(String),
+    Regex(Regex),
+}
+
+/// A rule which is checked, and ready to match items.
+pub struct CompiledRule {
+    rule: Rule,
+    matcher: Matcher,
+}
+
+impl CompiledRule {
+    pub fn new(rule: Rule) -> Result<CompiledRule> {
+        if rule.pattern.trim().is_empty() {
+            return Err(invalid_rule("The pattern is empty"));
+        }
+        if rule.action == RuleAction::Tag
+            && rule.tag.as_deref().is_none_or(|tag| tag.trim().is_empty())
+        {
+            return Err(invalid_rule("The tag action needs a tag"));
+        }
+        let matcher = match rule.match_type {
+            MatchType::Substring => Matcher::Substring(rule.pattern.to_lowercase()),
+            MatchType::Regex => Matcher::Regex(
+                RegexBuilder::new(&rule.pattern)
+                    .case_insensitive(true)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .build()
+                    .map_err(|e| invalid_rule(&e.to_string()))?,
+            ),
+        };
+        Ok(CompiledRule { rule, matcher })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self.matcher {
+            Matcher::Substring(ref pattern) => text.to_lowercase().contains(pattern),
+            Matcher::Regex(ref regex) => regex.is_match(text),
+        }
+    }
+
+    pub fn matches(&self, item: &Item, subscription: &Subscription) -> bool {
+        match self.rule.field {
+            RuleField::Title => self.is_match(&item.title),
+            RuleField::Content => self.is_match(&item.content),
+            RuleField::Author => self.is_match(&item.author),
+            RuleField::Url => self.is_match(&item.url),
+            RuleField::Feed => {
+                self.is_match(&subscription.title) || self.is_match(&subscription.feed_url)
+            }
+            RuleField::Label => subscription
+                .categories()
+                .iter()
+                .any(|category| self.is_match(category.rsplit('/').next().unwrap_or(category))),
+        }
+    }
+}
+
+/// Items which are not dropped by the rules, and the tags to add to them.
+pub struct FilteredItems {
+    pub items: Vec<Item>,
+    // Label ids, keyed by the external ids of the items.
+    pub tags: HashMap<String, Vec<String>>,
+}
+
+/// The rules of the users, which run on the items of their subscriptions
+/// before the items are stored.
+pub struct RuleSet {
+    // Keyed by user id, in the order the rules are created.
+    rules: HashMap<String, Vec<CompiledRule>>,
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<Rule>) -> RuleSet {
+        let mut rule_set = RuleSet {
+            rules: HashMap::new(),
+        };
+        for rule in rules {
+            let id = rule.id.clone();
+            match CompiledRule::new(rule) {
+                Ok(rule) => rule_set
+                    .rules
+                    .entry(rule.rule.user_id.clone())
+                    .or_default()
+                    .push(rule),
+                Err(err) => tracing::warn!("Skip rule {}: {}", id, err),
+            }
+        }
+        rule_set
+    }
+
+    pub fn apply(&self, items: Vec<Item>, subscription: &Subscription) -> FilteredItems {
+        let rules = match self.rules.get(&subscription.user_id) {
+            Some(rules) => rules,
+            None => {
+                return FilteredItems {
+                    items,
+                    tags: HashMap::new(),
+                }
+            }
+        };
+        let mut filtered = FilteredItems {
+            items: vec![],
+            tags: HashMap::new(),
+        };
+        'items: for mut item in items {
+            let mut tags = vec![];
+            let matched = rules
+                .iter()
+                .filter(|rule| rule.matches(&item, subscription))
+                .map(|rule| (rule.rule.action, rule.rule.tag.clone()))
+                .collect::<Vec<_>>();
+            for (action, tag) in matched {
+                match action {
+                    RuleAction::Read => item.read = true,
+                    RuleAction::Star => item.starred = true,
+                    RuleAction::Tag => tags.extend(tag.as_deref().and_then(label_id)),
+                    RuleAction::Drop => continue 'items,
+                }
+            }
+            if !tags.is_empty() {
+                filtered.tags.insert(item.external_id.clone(), tags);
+            }
+            filtered.items.push(item);
+        }
+        filtered
+    }
+}
+
+/// An item which a rule would match.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchedItem {
+    pub id: String,
+    pub subscription_id: String,
+    pub title: String,
+    pub url: String,
+    pub created_at_ms: i64,
+}
+
+impl From<Item> for MatchedItem {
+    fn from(item: Item) -> MatchedItem {
+        MatchedItem {
+            id: item.id.to_string(),
+            subscription_id: item.subscription_id,
+            title: item.title,
+            url: item.url,
+            created_at_ms: item.created_at_ms,
+        }
+    }
+}
+
+#[async_trait]
+pub trait RuleService {
+    async fn list_rules(&self, user_id: &str) -> Result<Vec<Rule>>;
+
+    async fn add_rule(&self, user_id: &str, rule: Rule) -> Result<Rule>;
+
+    async fn update_rule(&self, user_id: &str, id: &str, rule: Rule) -> Result<()>;
+
+    async fn delete_rule(&self, user_id: &str, id: &str) -> Result<()>;
+
+    /// Finds the recent items which the rule would match, without changing them.
+    async fn dry_run(&self, user_id: &str, rule: Rule) -> Result<Vec<MatchedItem>>;
+}
+
+struct RuleServiceImpl {
+    rule_repository: Box<dyn RuleRepository + Send + Sync>,
+    item_repository: Box<dyn ItemRepository + Send + Sync>,
+    subscription_repository: Box<dyn SubscriptionRepository + Send + Sync>,
+}
+
+#[async_trait]
+impl RuleService for RuleServiceImpl {
+    async fn list_rules(&self, user_id: &str) -> Result<Vec<Rule>> {
+        self.rule_repository.list_user_rules(user_id).await
+    }
+
+    async fn add_rule(&self, user_id: &str, mut rule: Rule) -> Result<Rule> {
+        rule.id = new_id(10);
+        rule.user_id = user_id.to_string();
+        rule.created_at_ms = current_time_ms();
+        let rule = CompiledRule::new(rule)?.rule;
+        self.rule_repository.insert_rule(&rule).await?;
+        Ok(rule)
+    }
+
+    async fn update_rule(&self, user_id: &str, id: &str, mut rule: Rule) -> Result<()> {
+        rule.id = id.to_string();
+        rule.user_id = user_id.to_string();
+        let rule = CompiledRule::new(rule)?.rule;
+        self.rule_repository.update_rule(&rule).await
+    }
+
+    async fn delete_rule(&self, user_id: &str, id: &str) -> Result<()> {
+        self.rule_repository.delete_rule(user_id, id).await
+    }
+
+    async fn dry_run(&self, user_id: &str, mut rule: Rule) -> Result<Vec<MatchedItem>> {
+        rule.user_id = user_id.to_string();
+        let rule = CompiledRule::new(rule)?;
+        let subscriptions = self
+            .subscription_repository
+            .list_user_subscriptions(user_id)
+            .await?;
+        let items = self
+            .item_repository
+            .get_items(
+                user_id,
+                PageOption {
+                    offset: None,
+                    limit: DRY_RUN_ITEMS,
+                    desc: true,
+                },
+            )
+            .await?
+            .items;
+        Ok(items
+            .into_iter()
+            .filter(|item| {
+                subscriptions
+                    .iter()
+                    .find(|sub| sub.id == item.subscription_id)
+                    .is_some_and(|sub| rule.matches(item, sub))
+            })
+            .map(MatchedItem::from)
+            .collect())
+    }
+}
+
+pub fn new_rule_service(
+    rule_repository: Box<dyn RuleRepository + Send + Sync>,
+    item_repository: Box<dyn ItemRepository + Send + Sync>,
+    subscription_repository: Box<dyn SubscriptionRepository + Send + Sync>,
+) -> Box<dyn RuleService + Send + Sync> {
+    Box::new(RuleServiceImpl {
+        rule_repository,
+        item_repository,
+        subscription_repository,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_subscription(user_id: &str, joined_categories: &str) -> Subscription {
+        Subscription {
+            user_id: user_id.to_string(),
+            id: "feed/https://blog.com/atom.xml".to_string(),
+            url: "https://blog.com".to_string(),
+            title: "Blog".to_string(),
+            description: String::new(),
+            feed_url: "https://blog.com/atom.xml".to_string(),
+            joined_categories: joined_categories.to_string(),
+            last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
+        }
+    }
+
+    fn new_rule(
+        field: RuleField,
+        match_type: MatchType,
+        pattern: &str,
+        action: RuleAction,
+    ) -> Rule {
+        Rule {
+            id: pattern.to_string(),
+            user_id: "user_id".to_string(),
+            name: pattern.to_string(),
+            field,
+            match_type,
+            pattern: pattern.to_string(),
+            action,
+            tag: (action == RuleAction::Tag).then(|| "tagged".to_string()),
+            created_at_ms: 0,
+        }
+    }
+
+    fn new_item(external_id: &str, title: &str) -> Item {
+        Item::new_item(
+            "user_id",
+            "feed/https://blog.com/atom.xml",
+            external_id,
+            title,
+            "",
+            "",
+            "",
+            &format!("https://blog.com/{}", external_id),
+            0,
+        )
+    }
+
+    #[test]
+    fn invalid_rules_should_be_rejected() {
+        let rule = new_rule(RuleField::Title, MatchType::Regex, "(", RuleAction::Read);
+        assert!(CompiledRule::new(rule).is_err());
+        let rule = new_rule(
+            RuleField::Title,
+            MatchType::Substring,
+            " ",
+            RuleAction::Read,
+        );
+        assert!(CompiledRule::new(rule).is_err());
+        let mut rule = new_rule(RuleField::Title, MatchType::Substring, "a", RuleAction::Tag);
+        rule.tag = None;
+        assert!(CompiledRule::new(rule).is_err());
+    }
+
+    #[test]
+    fn fields_should_be_matched() {
+        let subscription = new_subscription("user_id", "Tech,News");
+        let item = new_item("1", "Weekly Sponsored Post");
+        let matches = |field, match_type, pattern| {
+            CompiledRule::new(new_rule(field, match_type, pattern, RuleAction::Read))
+                .unwrap()
+                .matches(&item, &subscription)
+        };
+        assert!(matches(RuleField::Title, MatchType::Substring, "sponsored"));
+        assert!(matches(RuleField::Title, MatchType::Regex, r"^weekly\s"));
+        assert!(!matches(RuleField::Title, MatchType::Regex, "^sponsored"));
+        assert!(matches(RuleField::Url, MatchType::Substring, "blog.com/1"));
+        assert!(matches(RuleField::Feed, MatchType::Substring, "Blog"));
+        assert!(matches(RuleField::Label, MatchType::Regex, "^news$"));
+        assert!(!matches(RuleField::Label, MatchType::Substring, "sports"));
+        assert!(!matches(RuleField::Author, MatchType::Substring, "someone"));
+    }
+
+    #[test]
+    fn actions_should_be_applied() {
+        let rule_set = RuleSet::new(vec![
+            new_rule(
+                RuleField::Title,
+                MatchType::Substring,
+                "rust",
+                RuleAction::Star,
+            ),
+            new_rule(
+                RuleField::Title,
+                MatchType::Substring,
+                "rust",
+                RuleAction::Tag,
+            ),
+            new_rule(
+                RuleField::Title,
+                MatchType::Substring,
+                "ad",
+                RuleAction::Drop,
+            ),
+            new_rule(
+                RuleField::Title,
+                MatchType::Substring,
+                "old",
+                RuleAction::Read,
+            ),
+            new_rule(RuleField::Title, MatchType::Regex, "(", RuleAction::Drop),
+        ]);
+        let items = vec![
+            new_item("1", "Rust news"),
+            new_item("2", "An ad"),
+            new_item("3", "Old news"),
+        ];
+
+        let filtered = rule_set.apply(items.clone(), &new_subscription("user_id", ""));
+        assert_eq!(filtered.items, vec![items[0].clone(), items[2].clone()]);
+        assert!(filtered.items[0].starred && !filtered.items[0].read);
+        assert!(filtered.items[1].read && !filtered.items[1].starred);
+        assert_eq!(
+            filtered.tags,
+            HashMap::from([("1".to_string(), vec!["user/-/label/tagged".to_string()])])
+        );
+
+        let filtered = rule_set.apply(items.clone(), &new_subscription("other_user", ""));
+        assert_eq!(filtered.items.len(), 3);
+        assert!(filtered.tags.is_empty());
+    }
+}
@@ -0,0 +1,134 @@
+use ammonia::{Builder, UrlRelative};
+use std::borrow::Cow;
+use url::Url;
+
+// Embedded players which are allowed in iframes, other iframes lose their src.
+const TRUSTED_IFRAME_HOSTS: [&str; 6] = [
+    "www.youtube.com",
+    "youtube.com",
+    "www.youtube-nocookie.com",
+    "player.vimeo.com",
+    "player.bilibili.com",
+    "open.spotify.com",
+];
+
+fn is_trusted_iframe(src: &str) -> bool {
+    // Protocol relative urls are common in embed codes.
+    let src = match src.strip_prefix("//") {
+        Some(src) => Cow::Owned(format!("https://{}", src)),
+        None => Cow::Borrowed(src),
+    };
+    Url::parse(&src).is_ok_and(|url| {
+        url.scheme() == "https"
+            && url
+                .host_str()
+                .is_some_and(|host| TRUSTED_IFRAME_HOSTS.contains(&host))
+    })
+}
+
+// Tags and attributes allowed in the content of items.
+fn builder() -> Builder<'static> {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(&["iframe"])
+        .add_tag_attributes("iframe", &["src", "width", "height", "allowfullscreen"]);
+    builder
+}
+
+/// Cleans html from feeds before it gets to the readers.
+///
+/// Scripts, styles, event handlers and `javascript:` urls are removed, and
+/// relative urls are resolved against the base url. Without a base url,
+/// relative urls are removed, since they would point to this server.
+pub fn sanitize_html(html: &str, base_url: Option<&str>) -> String {
+    let url_relative = match base_url.and_then(|base_url| Url::parse(base_url).ok()) {
+        Some(base) => UrlRelative::RewriteWithBase(base),
+        None => UrlRelative::Deny,
+    };
+    builder()
+        .attribute_filter(|element, attribute, value| {
+            if element == "iframe" && attribute == "src" && !is_trusted_iframe(value) {
+                None
+            } else {
+                Some(value.into())
+            }
+        })
+        .url_relative(url_relative)
+        .clean(html)
+        .to_string()
+}
+
+/// Rewrites the src of images in sanitized html. Images keep their src if
+/// rewrite returns None.
+pub fn rewrite_image_sources<F>(html: &str, rewrite: F) -> String
+where
+    F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+{
+    builder()
+        .attribute_filter(move |element, attribute, value| {
+            if element == "img" && attribute == "src" {
+                if let Some(rewritten) = rewrite(value) {
+                    return Some(rewritten.into());
+                }
+            }
+            Some(value.into())
+        })
+        .url_relative(UrlRelative::PassThrough)
+        .clean(html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_URL: &str = "https://blog.com/posts/1.html";
+
+    #[test]
+    fn scripts_and_handlers_should_be_removed() {
+        let html = r#"<p onclick="steal()">Hi<script>alert(1)</script></p><style>p {}</style>"#;
+        assert_eq!(sanitize_html(html, Some(BASE_URL)), "<p>Hi</p>");
+    }
+
+    #[test]
+    fn javascript_urls_should_be_removed() {
+        let html = r#"<a href="javascript:alert(1)">link</a>"#;
+        assert_eq!(
+            sanitize_html(html, Some(BASE_URL)),
+            r#"<a rel="noopener noreferrer">link</a>"#
+        );
+    }
+
+    #[test]
+    fn only_trusted_iframes_should_keep_src() {
+        let html = r#"<iframe src="https://evil.com/frame"></iframe><iframe src="//www.youtube.com/embed/1" width="560"></iframe>"#;
+        assert_eq!(
+            sanitize_html(html, Some(BASE_URL)),
+            r#"<iframe></iframe><iframe src="https://www.youtube.com/embed/1" width="560"></iframe>"#
+        );
+    }
+
+    #[test]
+    fn image_sources_should_be_rewritten() {
+        let html = r#"<p><img src="https://blog.com/a.png" alt="a"><img src="https://blog.com/b.png"></p>"#;
+        assert_eq!(
+            rewrite_image_sources(html, |src| src
+                .ends_with("a.png")
+                .then(|| "/proxy/a".to_string())),
+            r#"<p><img src="/proxy/a" alt="a"><img src="https://blog.com/b.png"></p>"#
+        );
+    }
+
+    #[test]
+    fn relative_urls_should_be_resolved() {
+        let html = r#"<img src="../images/a.png"><a href="/about">about</a>"#;
+        assert_eq!(
+            sanitize_html(html, Some(BASE_URL)),
+            r#"<img src="https://blog.com/images/a.png"><a href="https://blog.com/about" rel="noopener noreferrer">about</a>"#
+        );
+        assert_eq!(
+            sanitize_html(html, None),
+            r#"<img><a rel="noopener noreferrer">about</a>"#
+        );
+    }
+}
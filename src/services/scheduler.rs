@@ -0,0 +1,295 @@
+use crate::common::current_time_ms;
+use crate::common::error::Errors;
+use crate::database::feed_states::FeedState;
+use crate::services::feeds::{FeedResponse, RefreshHints};
+use chrono::DateTime;
+use feed_rs::model::Feed;
+
+const MIN_INTERVAL_MS: i64 = 10 * 60 * 1000;
+const MAX_INTERVAL_MS: i64 = 24 * 60 * 60 * 1000;
+const DEFAULT_INTERVAL_MS: i64 = 60 * 60 * 1000;
+// Failing feeds are retried after MIN_INTERVAL_MS, doubled on every failure.
+const MAX_BACKOFF_EXPONENT: i64 = 8;
+// Number of the latest entries used to estimate how often a feed publishes.
+const POSTING_SAMPLE_SIZE: usize = 10;
+
+pub trait Clock {
+    fn now_ms(&self) -> i64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> i64 {
+        current_time_ms()
+    }
+}
+
+fn posting_interval_ms(feed: &Feed) -> Option<i64> {
+    let mut published_at_ms = feed
+        .entries
+        .iter()
+        .filter_map(|entry| entry.published.or(entry.updated))
+        .map(|d| d.timestamp_millis())
+        .collect::<Vec<i64>>();
+    published_at_ms.sort_by(|a, b| b.cmp(a));
+    published_at_ms.truncate(POSTING_SAMPLE_SIZE);
+    if published_at_ms.len() < 2 {
+        return None;
+    }
+    let newest = published_at_ms[0];
+    let oldest = published_at_ms[published_at_ms.len() - 1];
+    Some((newest - oldest) / (published_at_ms.len() as i64 - 1))
+}
+
+fn max_age_ms(cache_control: &str) -> Option<i64> {
+    cache_control
+        .split(',')
+        .filter_map(|directive| directive.trim().strip_prefix("max-age="))
+        .filter_map(|seconds| seconds.trim_matches('"').parse::<i64>().ok())
+        .next()
+        .map(|seconds| seconds.saturating_mul(1000))
+}
+
+/// Decides when each feed should be fetched next, from how often the feed
+/// publishes and the hints the publisher gives about it.
+pub struct RefreshScheduler {
+    clock: Box<dyn Clock + Send + Sync>,
+}
+
+impl RefreshScheduler {
+    pub fn new(clock: Box<dyn Clock + Send + Sync>) -> RefreshScheduler {
+        RefreshScheduler { clock }
+    }
+
+    pub fn now_ms(&self) -> i64 {
+        self.clock.now_ms()
+    }
+
+    pub fn is_due(&self, state: &FeedState) -> bool {
+        state.next_fetch_ms <= self.now_ms()
+    }
+
+    // Publishers can postpone the next fetch by MAX_INTERVAL_MS at most.
+    fn retry_at_ms(&self, retry_after: &str) -> Option<i64> {
+        let now_ms = self.now_ms();
+        let retry_at_ms = match retry_after.trim().parse::<i64>() {
+            Ok(seconds) => Some(now_ms.saturating_add(seconds.saturating_mul(1000))),
+            Err(_) => DateTime::parse_from_rfc2822(retry_after.trim())
+                .ok()
+                .map(|d| d.timestamp_millis()),
+        };
+        retry_at_ms.map(|retry_at_ms| retry_at_ms.min(now_ms + MAX_INTERVAL_MS))
+    }
+
+    fn interval_of_feed(feed: &Feed, hints: &RefreshHints) -> i64 {
+        let mut interval_ms = posting_interval_ms(feed).unwrap_or(DEFAULT_INTERVAL_MS);
+        if let Some(ttl_minutes) = feed.ttl {
+            interval_ms = interval_ms.max(ttl_minutes as i64 * 60 * 1000);
+        }
+        if let Some(update_period_s) = hints.update_period_s {
+            interval_ms = interval_ms.max(update_period_s.saturating_mul(1000));
+        }
+        interval_ms
+    }
+
+    pub fn on_success(&self, state: &mut FeedState, response: &FeedResponse) {
+        let now_ms = self.now_ms();
+        if let Some(ref feed) = response.feed {
+            state.interval_ms = Self::interval_of_feed(feed, &response.hints);
+        }
+        let mut interval_ms = if state.interval_ms > 0 {
+            state.interval_ms
+        } else {
+            DEFAULT_INTERVAL_MS
+        };
+        if let Some(max_age_ms) = response.hints.cache_control.as_deref().and_then(max_age_ms) {
+            interval_ms = interval_ms.max(max_age_ms);
+        }
+        let mut next_fetch_ms = now_ms + interval_ms.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS);
+        if let Some(retry_at_ms) = response
+            .hints
+            .retry_after
+            .as_deref()
+            .and_then(|retry_after| self.retry_at_ms(retry_after))
+        {
+            next_fetch_ms = next_fetch_ms.max(retry_at_ms);
+        }
+        state.etag = response.etag.clone();
+        state.last_modified = response.last_modified.clone();
+        state.last_fetch_ms = now_ms;
+        state.next_fetch_ms = next_fetch_ms;
+        state.consecutive_failures = 0;
+    }
+
+    pub fn on_failure(&self, state: &mut FeedState, error: &anyhow::Error) {
+        let exponent = state.consecutive_failures.min(MAX_BACKOFF_EXPONENT);
+        let backoff_ms = (MIN_INTERVAL_MS << exponent).min(MAX_INTERVAL_MS);
+        let mut next_fetch_ms = self.now_ms() + backoff_ms;
+        if let Some(Errors::UnexpectedStatus {
+            retry_after: Some(retry_after),
+            ..
+        }) = error.downcast_ref::<Errors>()
+        {
+            if let Some(retry_at_ms) = self.retry_at_ms(retry_after) {
+                next_fetch_ms = next_fetch_ms.max(retry_at_ms);
+            }
+        }
+        state.next_fetch_ms = next_fetch_ms;
+        state.consecutive_failures += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Error;
+    use feed_rs::parser;
+
+    const NOW_MS: i64 = 1_669_852_800_000;
+
+    struct FakeClock {}
+
+    impl Clock for FakeClock {
+        fn now_ms(&self) -> i64 {
+            NOW_MS
+        }
+    }
+
+    fn new_scheduler() -> RefreshScheduler {
+        RefreshScheduler::new(Box::new(FakeClock {}))
+    }
+
+    fn new_response(rss: &str, hints: RefreshHints) -> FeedResponse {
+        FeedResponse {
+            status: 200,
+            feed: Some(parser::parse(rss.as_bytes()).unwrap()),
+            etag: Some("etag".to_string()),
+            last_modified: None,
+            hints,
+        }
+    }
+
+    const HOURLY_RSS: &str = r#"<rss version="2.0"><channel><title>t</title>
+        <item><guid>1</guid><pubDate>Thu, 01 Dec 2022 00:00:00 GMT</pubDate></item>
+        <item><guid>2</guid><pubDate>Wed, 30 Nov 2022 22:00:00 GMT</pubDate></item>
+        <item><guid>3</guid><pubDate>Wed, 30 Nov 2022 23:00:00 GMT</pubDate></item>
+        </channel></rss>"#;
+
+    #[test]
+    fn schedule_by_posting_frequency() {
+        let scheduler = new_scheduler();
+        let mut state = FeedState::new("url");
+        scheduler.on_success(
+            &mut state,
+            &new_response(HOURLY_RSS, RefreshHints::default()),
+        );
+        assert_eq!(state.interval_ms, 60 * 60 * 1000);
+        assert_eq!(state.next_fetch_ms, NOW_MS + 60 * 60 * 1000);
+        assert_eq!(state.last_fetch_ms, NOW_MS);
+        assert_eq!(state.etag.as_deref(), Some("etag"));
+        assert!(!scheduler.is_due(&state));
+    }
+
+    #[test]
+    fn schedule_respects_ttl_and_update_period() {
+        let scheduler = new_scheduler();
+        let mut state = FeedState::new("url");
+        let rss = HOURLY_RSS.replace("<title>t</title>", "<title>t</title><ttl>180</ttl>");
+        scheduler.on_success(&mut state, &new_response(&rss, RefreshHints::default()));
+        assert_eq!(state.interval_ms, 3 * 60 * 60 * 1000);
+
+        scheduler.on_success(
+            &mut state,
+            &new_response(
+                HOURLY_RSS,
+                RefreshHints {
+                    update_period_s: Some(24 * 60 * 60),
+                    ..RefreshHints::default()
+                },
+            ),
+        );
+        assert_eq!(state.next_fetch_ms, NOW_MS + MAX_INTERVAL_MS);
+    }
+
+    #[test]
+    fn schedule_respects_http_headers() {
+        let scheduler = new_scheduler();
+        let mut state = FeedState::new("url");
+        scheduler.on_success(
+            &mut state,
+            &new_response(
+                HOURLY_RSS,
+                RefreshHints {
+                    cache_control: Some("public, max-age=7200".to_string()),
+                    ..RefreshHints::default()
+                },
+            ),
+        );
+        assert_eq!(state.interval_ms, 60 * 60 * 1000);
+        assert_eq!(state.next_fetch_ms, NOW_MS + 2 * 60 * 60 * 1000);
+
+        // Not modified keeps the interval learned from the last fetched feed.
+        scheduler.on_success(
+            &mut state,
+            &FeedResponse {
+                status: 304,
+                feed: None,
+                etag: None,
+                last_modified: None,
+                hints: RefreshHints {
+                    retry_after: Some("Fri, 02 Dec 2022 00:00:00 GMT".to_string()),
+                    ..RefreshHints::default()
+                },
+            },
+        );
+        assert_eq!(state.interval_ms, 60 * 60 * 1000);
+        assert_eq!(state.next_fetch_ms, NOW_MS + 24 * 60 * 60 * 1000);
+
+        // Huge hints don't overflow, nor postpone the fetch for longer than a day.
+        scheduler.on_success(
+            &mut state,
+            &new_response(
+                HOURLY_RSS,
+                RefreshHints {
+                    cache_control: Some("max-age=99999999999999999".to_string()),
+                    retry_after: Some("99999999999999999".to_string()),
+                    ..RefreshHints::default()
+                },
+            ),
+        );
+        assert_eq!(state.next_fetch_ms, NOW_MS + MAX_INTERVAL_MS);
+    }
+
+    #[test]
+    fn schedule_after_failure() {
+        let scheduler = new_scheduler();
+        let mut state = FeedState::new("url");
+        scheduler.on_failure(&mut state, &Error::msg("failed"));
+        assert_eq!(state.next_fetch_ms, NOW_MS + MIN_INTERVAL_MS);
+        scheduler.on_failure(&mut state, &Error::msg("failed"));
+        assert_eq!(state.next_fetch_ms, NOW_MS + 2 * MIN_INTERVAL_MS);
+        assert_eq!(state.consecutive_failures, 2);
+
+        scheduler.on_failure(
+            &mut state,
+            &Error::new(Errors::UnexpectedStatus {
+                url: "url".to_string(),
+                status: 429,
+                retry_after: Some("3600".to_string()),
+            }),
+        );
+        assert_eq!(state.next_fetch_ms, NOW_MS + 60 * 60 * 1000);
+
+        for _ in 0..20 {
+            scheduler.on_failure(&mut state, &Error::msg("failed"));
+        }
+        assert_eq!(state.next_fetch_ms, NOW_MS + MAX_INTERVAL_MS);
+
+        scheduler.on_success(
+            &mut state,
+            &new_response(HOURLY_RSS, RefreshHints::default()),
+        );
+        assert_eq!(state.consecutive_failures, 0);
+    }
+}
@@ -1,9 +1,20 @@
-use crate::common::{oldest_allowed_time_ms, Page, PageOption};
-use crate::database::items::{Item, ItemRepository, State};
+use crate::common::error::Errors;
+use crate::common::{current_time_ms, Page, PageOption};
+use crate::configuration::{DedupPolicy, RetentionConfiguration};
+use crate::database::enclosures::{Enclosure, EnclosureRepository};
+use crate::database::items::{Item, ItemFilter, ItemRepository, LabelFilter, State};
+use crate::database::retentions::{Retention, RetentionRepository};
 use crate::database::subscriptions::{Subscription, SubscriptionRepository};
-use anyhow::Result;
+use crate::greader::stream::StreamId;
+use crate::services::proxy::ImageProxy;
+use crate::services::retention::{RetentionPolicies, RetentionPolicy};
+use crate::services::sanitizer::sanitize_html;
+use anyhow::{Error, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const READING_LIST: &str = "user/-/state/com.google/reading-list";
 
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -32,6 +43,33 @@ pub struct Url {
     type_f: Option<String>,
 }
 
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EnclosureUrl {
+    href: String,
+    #[serde(rename = "type")]
+    type_f: String,
+    // Bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    length: Option<String>,
+    // Seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<String>,
+}
+
+impl From<Enclosure> for EnclosureUrl {
+    fn from(enclosure: Enclosure) -> EnclosureUrl {
+        EnclosureUrl {
+            href: enclosure.url,
+            type_f: enclosure.mime_type,
+            length: enclosure.length.map(|length| length.to_string()),
+            duration: enclosure
+                .duration_seconds
+                .map(|seconds| seconds.to_string()),
+        }
+    }
+}
+
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Summary {
@@ -60,6 +98,8 @@ pub struct ItemContent {
     pub updated: i64,
     pub canonical: Vec<Url>,
     pub alternate: Vec<Url>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub enclosure: Vec<EnclosureUrl>,
     pub summary: Summary,
     pub title: String,
     pub author: String,
@@ -67,12 +107,14 @@ pub struct ItemContent {
 }
 
 impl ItemContent {
-    fn from(item: Item, sub: &Subscription) -> ItemContent {
+    fn from(item: Item, sub: &Subscription, labels: &[String]) -> ItemContent {
+        // Items stored before content was sanitized on ingestion are cleaned here.
+        let content = sanitize_html(&item.content, Some(&item.url));
         ItemContent {
             crawl_time_msec: item.fetched_at_ms.to_string(),
             timestamp_usec: (item.created_at_ms * 1000).to_string(),
             id: format!("tag:google.com,2005:reader/item/{:016x}", item.id),
-            categories: item.categories(),
+            categories: item.categories(labels),
             published: item.created_at_ms / 1000,
             updated: item.created_at_ms / 1000,
             canonical: vec![Url {
@@ -83,9 +125,10 @@ impl ItemContent {
                 href: item.url,
                 type_f: Option::Some("text/html".to_owned()),
             }],
+            enclosure: vec![],
             summary: Summary {
                 direction: String::from("ltr"),
-                content: item.content,
+                content,
             },
             title: item.title,
             author: item.author,
@@ -98,37 +141,122 @@ impl ItemContent {
     }
 }
 
+/// The retention of the user, and the overrides of the user and its subscriptions.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Retentions {
+    pub policy: RetentionPolicy,
+    pub overrides: Vec<Retention>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnreadCount {
+    pub id: String,
+    pub count: i64,
+    pub newest_item_timestamp_usec: String,
+}
+
+/// Selects the items of a stream, like the s, it, xt, ot and nt parameters of
+/// stream/items/ids.
+#[derive(Debug, Clone)]
+pub struct StreamFilter {
+    pub stream: StreamId,
+    pub include: Vec<StreamId>,
+    pub exclude: Vec<StreamId>,
+    pub newer_than_ms: Option<i64>,
+    pub older_than_ms: Option<i64>,
+    // Only items matching the words, like the q parameter of search/items/ids.
+    pub search: Option<String>,
+}
+
+impl StreamFilter {
+    pub fn new(stream: StreamId) -> StreamFilter {
+        StreamFilter {
+            stream,
+            include: vec![],
+            exclude: vec![],
+            newer_than_ms: None,
+            older_than_ms: None,
+            search: None,
+        }
+    }
+}
+
+// Narrows the filter to the stream, or to everything except the stream.
+// Returns false if no item can match the filter anymore.
+fn restrict(
+    filter: &mut ItemFilter,
+    stream: &StreamId,
+    included: bool,
+    subscriptions: &Vec<Subscription>,
+) -> bool {
+    let restrict_state = |state: &mut Option<bool>| match state {
+        Some(value) => *value == included,
+        None => {
+            *state = Some(included);
+            true
+        }
+    };
+    let subscription_ids = match stream {
+        StreamId::ReadingList => return included,
+        StreamId::Read => return restrict_state(&mut filter.read),
+        StreamId::Starred => return restrict_state(&mut filter.starred),
+        StreamId::Feed(id) => vec![id.clone()],
+        // Items can have the label from their subscription, or their own tags.
+        StreamId::Label(label) => {
+            let label = LabelFilter {
+                label: label.clone(),
+                subscription_ids: subscriptions
+                    .iter()
+                    .filter(|sub| sub.categories().contains(&label.as_str()))
+                    .map(|sub| sub.id.clone())
+                    .collect(),
+            };
+            if included {
+                filter.labels.push(label);
+            } else {
+                filter.excluded_labels.push(label);
+            }
+            return true;
+        }
+    };
+    if !included {
+        filter.excluded_subscription_ids.extend(subscription_ids);
+        return true;
+    }
+    let subscription_ids = match filter.subscription_ids.take() {
+        Some(ids) => ids
+            .into_iter()
+            .filter(|id| subscription_ids.contains(id))
+            .collect(),
+        None => subscription_ids,
+    };
+    filter.subscription_ids = Some(subscription_ids);
+    true
+}
+
 #[async_trait]
 pub trait StreamService {
-    async fn get_unread_item_ids(
+    async fn get_item_ids(
         &self,
         user_id: &str,
+        stream_filter: &StreamFilter,
         page_option: PageOption<String>,
     ) -> Result<Page<ItemId, String>>;
 
-    async fn get_unread_item_contents(
+    async fn get_stream_item_contents(
         &self,
         user_id: &str,
+        stream_filter: &StreamFilter,
         page_option: PageOption<String>,
     ) -> Result<Page<ItemContent, String>>;
 
-    async fn get_read_item_ids(
-        &self,
-        user_id: &str,
-        page_option: PageOption<String>,
-    ) -> Result<Page<ItemId, String>>;
-
-    async fn get_starred_item_ids(
-        &self,
-        user_id: &str,
-        page_option: PageOption<String>,
-    ) -> Result<Page<ItemId, String>>;
-
-    async fn get_all_item_ids(
+    async fn get_unread_item_contents(
         &self,
         user_id: &str,
         page_option: PageOption<String>,
-    ) -> Result<Page<ItemId, String>>;
+    ) -> Result<Page<ItemContent, String>>;
 
     async fn get_item_contents(&self, user_id: &str, ids: &Vec<&str>) -> Result<Vec<ItemContent>>;
 
@@ -140,28 +268,155 @@ pub trait StreamService {
 
     async fn mark_as_unstarred(&self, user_id: &str, ids: &Vec<&str>) -> Result<()>;
 
+    async fn add_label(&self, user_id: &str, ids: &Vec<&str>, label: &str) -> Result<()>;
+
+    async fn remove_label(&self, user_id: &str, ids: &Vec<&str>, label: &str) -> Result<()>;
+
+    /// Lists the labels of items, which may not be labels of subscriptions.
+    async fn list_labels(&self, user_id: &str) -> Result<Vec<String>>;
+
+    /// Marks the items of a feed, a label or the reading list as read, only
+    /// the ones created before older_than_ms if it is given.
+    async fn mark_all_as_read(
+        &self,
+        user_id: &str,
+        stream_id: &StreamId,
+        older_than_ms: Option<i64>,
+    ) -> Result<()>;
+
+    /// Unread counts of every subscription, label and the reading list.
+    async fn get_unread_counts(&self, user_id: &str) -> Result<Vec<UnreadCount>>;
+
     async fn clean_up(&self) -> Result<()>;
+
+    async fn get_retentions(&self, user_id: &str) -> Result<Retentions>;
+
+    async fn set_retention(&self, user_id: &str, retention: Retention) -> Result<()>;
 }
 
 struct StreamServiceImpl {
     item_repository: Box<dyn ItemRepository + Send + Sync>,
     subscription_repository: Box<dyn SubscriptionRepository + Send + Sync>,
+    retention_repository: Box<dyn RetentionRepository + Send + Sync>,
+    enclosure_repository: Box<dyn EnclosureRepository + Send + Sync>,
+    retention: RetentionConfiguration,
+    dedup: DedupPolicy,
+    image_proxy: Option<ImageProxy>,
+}
+
+// Enclosures and labels of a page of items, by the ids of the items.
+#[derive(Default)]
+struct Attachments {
+    enclosures: HashMap<i64, Vec<Enclosure>>,
+    labels: HashMap<i64, Vec<String>>,
+}
+
+impl StreamServiceImpl {
+    // Takes the attachments of the item out of the attachments of all items.
+    fn to_content(
+        &self,
+        item: Item,
+        subscription: &Subscription,
+        attachments: &mut Attachments,
+    ) -> ItemContent {
+        let enclosures = attachments.enclosures.remove(&item.id).unwrap_or_default();
+        let labels = attachments.labels.remove(&item.id).unwrap_or_default();
+        let mut content = ItemContent::from(item, subscription, &labels);
+        content.enclosure = enclosures.into_iter().map(EnclosureUrl::from).collect();
+        if let Some(ref image_proxy) = self.image_proxy {
+            content.summary.content = image_proxy.rewrite_images(&content.summary.content);
+        }
+        content
+    }
+
+    async fn get_attachments(&self, items: &[Item]) -> Result<Attachments> {
+        let ids = items.iter().map(|item| item.id).collect();
+        let mut attachments = Attachments::default();
+        for enclosure in self.enclosure_repository.get_enclosures(&ids).await? {
+            attachments
+                .enclosures
+                .entry(enclosure.item_id)
+                .or_default()
+                .push(enclosure);
+        }
+        for (item_id, label) in self.item_repository.get_item_tags(&ids).await? {
+            attachments.labels.entry(item_id).or_default().push(label);
+        }
+        Ok(attachments)
+    }
+
+    // Also returns the subscriptions of the user, which the items belong to.
+    async fn get_stream_items(
+        &self,
+        user_id: &str,
+        stream_filter: &StreamFilter,
+        page_option: PageOption<String>,
+    ) -> Result<(Page<Item, String>, Vec<Subscription>)> {
+        let subscriptions = self
+            .subscription_repository
+            .list_user_subscriptions(user_id)
+            .await?;
+        let mut filter = ItemFilter {
+            // Items of removed subscriptions are not shown anywhere.
+            subscription_ids: Some(subscriptions.iter().map(|sub| sub.id.clone()).collect()),
+            newer_than_ms: stream_filter.newer_than_ms,
+            older_than_ms: stream_filter.older_than_ms,
+            search: stream_filter.search.clone(),
+            ..ItemFilter::default()
+        };
+        let included = std::iter::once(&stream_filter.stream)
+            .chain(stream_filter.include.iter())
+            .map(|stream| (stream, true));
+        let excluded = stream_filter.exclude.iter().map(|stream| (stream, false));
+        for (stream, is_included) in included.chain(excluded) {
+            if !restrict(&mut filter, stream, is_included, &subscriptions) {
+                // Nothing is queried, but malformed continuations are still rejected.
+                if let Some(ref offset) = page_option.offset {
+                    Item::parse_offset(offset)?;
+                }
+                return Ok((Page::empty(), subscriptions));
+            }
+        }
+        let page = self
+            .item_repository
+            .get_filtered_items(user_id, &filter, page_option)
+            .await?;
+        Ok((page, subscriptions))
+    }
 }
 
 #[async_trait]
 impl StreamService for StreamServiceImpl {
-    async fn get_unread_item_ids(
+    async fn get_item_ids(
         &self,
         user_id: &str,
+        stream_filter: &StreamFilter,
         page_option: PageOption<String>,
     ) -> Result<Page<ItemId, String>> {
-        let page = self
-            .item_repository
-            .get_unread_items(user_id, page_option)
+        let (page, _) = self
+            .get_stream_items(user_id, stream_filter, page_option)
             .await?;
         Ok(page.convert::<ItemId, _>(|item| ItemId::from(item)))
     }
 
+    async fn get_stream_item_contents(
+        &self,
+        user_id: &str,
+        stream_filter: &StreamFilter,
+        page_option: PageOption<String>,
+    ) -> Result<Page<ItemContent, String>> {
+        let (page, subscriptions) = self
+            .get_stream_items(user_id, stream_filter, page_option)
+            .await?;
+        let mut attachments = self.get_attachments(&page.items).await?;
+        Ok(page.filter_map::<ItemContent, _>(|item| {
+            let subscription = subscriptions
+                .iter()
+                .find(|sub| sub.id == item.subscription_id)?;
+            Some(self.to_content(item, subscription, &mut attachments))
+        }))
+    }
+
     async fn get_unread_item_contents(
         &self,
         user_id: &str,
@@ -183,45 +438,15 @@ impl StreamService for StreamServiceImpl {
             .subscription_repository
             .get_subscriptions(user_id, &subscription_ids)
             .await?;
-        Ok(page.convert::<ItemContent, _>(|item| {
-            let subscription = subscriptions.get(&item.subscription_id).unwrap();
-            ItemContent::from(item, subscription)
+        let mut attachments = self.get_attachments(&page.items).await?;
+        // Items of removed subscriptions are kept until they are cleaned up,
+        // but they are not shown.
+        Ok(page.filter_map::<ItemContent, _>(|item| {
+            let subscription = subscriptions.get(&item.subscription_id)?;
+            Some(self.to_content(item, subscription, &mut attachments))
         }))
     }
 
-    async fn get_read_item_ids(
-        &self,
-        user_id: &str,
-        page_option: PageOption<String>,
-    ) -> Result<Page<ItemId, String>> {
-        let page = self
-            .item_repository
-            .get_read_items(user_id, page_option)
-            .await?;
-        Ok(page.convert::<ItemId, _>(|item| ItemId::from(item)))
-    }
-
-    async fn get_starred_item_ids(
-        &self,
-        user_id: &str,
-        page_option: PageOption<String>,
-    ) -> Result<Page<ItemId, String>> {
-        let page = self
-            .item_repository
-            .get_starred_items(user_id, page_option)
-            .await?;
-        Ok(page.convert::<ItemId, _>(|item| ItemId::from(item)))
-    }
-
-    async fn get_all_item_ids(
-        &self,
-        user_id: &str,
-        page_option: PageOption<String>,
-    ) -> Result<Page<ItemId, String>> {
-        let page = self.item_repository.get_items(user_id, page_option).await?;
-        Ok(page.convert::<ItemId, _>(|item| ItemId::from(item)))
-    }
-
     async fn get_item_contents(&self, user_id: &str, ids: &Vec<&str>) -> Result<Vec<ItemContent>> {
         if ids.is_empty() {
             return Ok(vec![]);
@@ -232,11 +457,12 @@ impl StreamService for StreamServiceImpl {
             .subscription_repository
             .get_subscriptions(user_id, &subscription_ids)
             .await?;
+        let mut attachments = self.get_attachments(&items).await?;
         Ok(items
             .into_iter()
-            .map(|item| {
-                let subscription = subscriptions.get(&item.subscription_id).unwrap();
-                ItemContent::from(item, subscription)
+            .filter_map(|item| {
+                let subscription = subscriptions.get(&item.subscription_id)?;
+                Some(self.to_content(item, subscription, &mut attachments))
             })
             .collect())
     }
@@ -247,7 +473,13 @@ impl StreamService for StreamServiceImpl {
         }
         self.item_repository
             .mark_items_as(user_id, ids, State::READ)
-            .await
+            .await?;
+        if self.dedup == DedupPolicy::MarkRead {
+            self.item_repository
+                .mark_copies_as(user_id, ids, State::READ)
+                .await?;
+        }
+        Ok(())
     }
 
     async fn mark_as_unread(&self, user_id: &str, ids: &Vec<&str>) -> Result<()> {
@@ -256,7 +488,13 @@ impl StreamService for StreamServiceImpl {
         }
         self.item_repository
             .mark_items_as(user_id, ids, State::UNREAD)
-            .await
+            .await?;
+        if self.dedup == DedupPolicy::MarkRead {
+            self.item_repository
+                .mark_copies_as(user_id, ids, State::UNREAD)
+                .await?;
+        }
+        Ok(())
     }
 
     async fn mark_as_starred(&self, user_id: &str, ids: &Vec<&str>) -> Result<()> {
@@ -277,19 +515,354 @@ impl StreamService for StreamServiceImpl {
             .await
     }
 
+    async fn add_label(&self, user_id: &str, ids: &Vec<&str>, label: &str) -> Result<()> {
+        self.item_repository.tag_items(user_id, ids, label).await
+    }
+
+    async fn remove_label(&self, user_id: &str, ids: &Vec<&str>, label: &str) -> Result<()> {
+        self.item_repository.untag_items(user_id, ids, label).await
+    }
+
+    async fn list_labels(&self, user_id: &str) -> Result<Vec<String>> {
+        self.item_repository.list_tags(user_id).await
+    }
+
+    async fn mark_all_as_read(
+        &self,
+        user_id: &str,
+        stream_id: &StreamId,
+        older_than_ms: Option<i64>,
+    ) -> Result<()> {
+        match stream_id {
+            StreamId::ReadingList => match older_than_ms {
+                Some(older_than_ms) => {
+                    self.item_repository
+                        .mark_older_as_read(user_id, older_than_ms)
+                        .await
+                }
+                None => self.item_repository.mark_all_as_read(user_id).await,
+            },
+            StreamId::Feed(id) => {
+                self.item_repository
+                    .mark_subscriptions_as_read(user_id, &vec![id], older_than_ms)
+                    .await
+            }
+            StreamId::Label(label) => {
+                let subscriptions = self
+                    .subscription_repository
+                    .list_user_subscriptions(user_id)
+                    .await?;
+                let subscription_ids = subscriptions
+                    .iter()
+                    .filter(|sub| sub.categories().contains(&label.as_str()))
+                    .map(|sub| sub.id.as_str())
+                    .collect();
+                self.item_repository
+                    .mark_subscriptions_as_read(user_id, &subscription_ids, older_than_ms)
+                    .await?;
+                self.item_repository
+                    .mark_tagged_as_read(user_id, label, older_than_ms)
+                    .await
+            }
+            _ => Err(Error::new(Errors::InvalidStream {
+                stream_id: format!("{:?}", stream_id),
+            })),
+        }?;
+        if self.dedup == DedupPolicy::MarkRead {
+            self.item_repository
+                .mark_copies_of_read_items(user_id)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_unread_counts(&self, user_id: &str) -> Result<Vec<UnreadCount>> {
+        let subscriptions = self
+            .subscription_repository
+            .list_user_subscriptions(user_id)
+            .await?;
+        let counts = self.item_repository.count_unread_items(user_id).await?;
+        // Stream id -> (count, newest created_at_ms)
+        let mut rolled_up = BTreeMap::<String, (i64, i64)>::new();
+        let mut add = |id: &str, count: i64, newest_created_at_ms: i64| {
+            let entry = rolled_up.entry(id.to_string()).or_insert((0, 0));
+            entry.0 += count;
+            entry.1 = entry.1.max(newest_created_at_ms);
+        };
+        for count in counts {
+            let subscription = subscriptions
+                .iter()
+                .find(|sub| sub.id == count.subscription_id);
+            // Items of removed subscriptions are not shown anywhere.
+            if let Some(subscription) = subscription {
+                add(&subscription.id, count.count, count.newest_created_at_ms);
+                for label in subscription.categories() {
+                    add(label, count.count, count.newest_created_at_ms);
+                }
+                add(READING_LIST, count.count, count.newest_created_at_ms);
+            }
+        }
+        Ok(rolled_up
+            .into_iter()
+            .map(|(id, (count, newest_created_at_ms))| UnreadCount {
+                id,
+                count,
+                newest_item_timestamp_usec: (newest_created_at_ms * 1000).to_string(),
+            })
+            .collect())
+    }
+
     async fn clean_up(&self) -> Result<()> {
-        self.item_repository
-            .delete_items(oldest_allowed_time_ms())
-            .await
+        let policies = RetentionPolicies::new(
+            &self.retention,
+            self.retention_repository.list_all_retentions().await?,
+        );
+        let now_ms = current_time_ms();
+        for subscription in self
+            .subscription_repository
+            .list_all_subscriptions()
+            .await?
+        {
+            let policy = policies.policy(&subscription.user_id, &subscription.id);
+            self.item_repository
+                .delete_items(
+                    &subscription.user_id,
+                    &subscription.id,
+                    &policy.clean_up(now_ms),
+                )
+                .await?;
+        }
+        if let Some(older_than_ms) = policies.default_policy().oldest_allowed_time_ms(now_ms) {
+            self.item_repository
+                .delete_unsubscribed_items(older_than_ms)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_retentions(&self, user_id: &str) -> Result<Retentions> {
+        let overrides = self
+            .retention_repository
+            .list_user_retentions(user_id)
+            .await?;
+        let policies = RetentionPolicies::new(&self.retention, overrides.clone());
+        Ok(Retentions {
+            policy: policies.user_policy(user_id),
+            overrides,
+        })
+    }
+
+    async fn set_retention(&self, user_id: &str, mut retention: Retention) -> Result<()> {
+        if !retention.subscription_id.is_empty()
+            && self
+                .subscription_repository
+                .get_subscription(user_id, &retention.subscription_id)
+                .await?
+                .is_none()
+        {
+            return Err(Error::new(Errors::SubscriptionNotFound));
+        }
+        retention.user_id = user_id.to_string();
+        self.retention_repository.upsert_retention(retention).await
     }
 }
 
 pub fn new_stream_service(
     item_repository: Box<dyn ItemRepository + Send + Sync>,
     subscription_repository: Box<dyn SubscriptionRepository + Send + Sync>,
+    retention_repository: Box<dyn RetentionRepository + Send + Sync>,
+    enclosure_repository: Box<dyn EnclosureRepository + Send + Sync>,
+    retention: RetentionConfiguration,
+    dedup: DedupPolicy,
+    image_proxy: Option<ImageProxy>,
 ) -> Box<dyn StreamService + Send + Sync> {
     Box::new(StreamServiceImpl {
         item_repository: item_repository,
         subscription_repository: subscription_repository,
+        retention_repository,
+        enclosure_repository,
+        retention,
+        dedup,
+        image_proxy,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_subscription(id: &str, joined_categories: &str) -> Subscription {
+        Subscription {
+            user_id: "user_id".to_string(),
+            id: id.to_string(),
+            url: "url".to_string(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            feed_url: "feed_url".to_string(),
+            joined_categories: joined_categories.to_string(),
+            last_fetch_ms: 0,
+            last_success_ms: 0,
+            last_error: None,
+            last_http_status: None,
+            consecutive_failures: 0,
+            disabled: false,
+            extract_content: false,
+        }
+    }
+
+    #[test]
+    fn restrict_should_narrow_filter() {
+        let subscriptions = vec![
+            new_subscription("feed/a", "user/-/label/Tech"),
+            new_subscription("feed/b", "user/-/label/Tech,user/-/label/News"),
+            new_subscription("feed/c", ""),
+        ];
+        let mut filter = ItemFilter::default();
+        assert!(restrict(
+            &mut filter,
+            &StreamId::label("Tech"),
+            true,
+            &subscriptions
+        ));
+        assert!(restrict(
+            &mut filter,
+            &StreamId::Feed("feed/b".to_string()),
+            true,
+            &subscriptions
+        ));
+        assert!(restrict(
+            &mut filter,
+            &StreamId::Read,
+            false,
+            &subscriptions
+        ));
+        assert!(restrict(
+            &mut filter,
+            &StreamId::label("News"),
+            false,
+            &subscriptions
+        ));
+        assert_eq!(
+            filter,
+            ItemFilter {
+                subscription_ids: Some(vec!["feed/b".to_string()]),
+                labels: vec![LabelFilter {
+                    label: "user/-/label/Tech".to_string(),
+                    subscription_ids: vec!["feed/a".to_string(), "feed/b".to_string()],
+                }],
+                excluded_labels: vec![LabelFilter {
+                    label: "user/-/label/News".to_string(),
+                    subscription_ids: vec!["feed/b".to_string()],
+                }],
+                read: Some(false),
+                ..ItemFilter::default()
+            }
+        );
+
+        assert!(!restrict(
+            &mut filter,
+            &StreamId::Read,
+            true,
+            &subscriptions
+        ));
+        assert!(!restrict(
+            &mut filter,
+            &StreamId::ReadingList,
+            false,
+            &subscriptions
+        ));
+    }
+
+    #[test]
+    fn enclosures_should_be_serialized() {
+        let subscription = new_subscription("feed/a", "");
+        let item = Item::new_item(
+            "user_id",
+            "feed/a",
+            "1",
+            "Title",
+            "",
+            "",
+            "",
+            "https://blog.com/1",
+            1,
+        );
+        let mut content = ItemContent::from(item, &subscription, &[]);
+        assert!(serde_json::to_value(&content)
+            .unwrap()
+            .get("enclosure")
+            .is_none());
+
+        content.enclosure = vec![EnclosureUrl::from(Enclosure {
+            item_id: 1,
+            url: "https://blog.com/1.mp3".to_string(),
+            mime_type: "audio/mpeg".to_string(),
+            length: Some(1024),
+            duration_seconds: None,
+        })];
+        assert_eq!(
+            serde_json::to_value(&content).unwrap()["enclosure"],
+            serde_json::json!([
+                {"href": "https://blog.com/1.mp3", "type": "audio/mpeg", "length": "1024"}
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn items_of_removed_subscriptions_should_be_skipped() {
+        use crate::database::enclosures::new_enclosure_repository;
+        use crate::database::in_memory_pool;
+        use crate::database::items::new_item_repository;
+        use crate::database::retentions::new_retention_repository;
+        use crate::database::subscriptions::new_subscription_repository;
+
+        let pool = in_memory_pool().await;
+        let subscription_repository = new_subscription_repository(pool.clone()).await.unwrap();
+        subscription_repository
+            .insert_subscription(new_subscription("feed/a", ""))
+            .await
+            .unwrap();
+        let item_repository = new_item_repository(pool.clone()).await.unwrap();
+        let new_item = |subscription_id: &str, external_id: &str| {
+            Item::new_item(
+                "user_id",
+                subscription_id,
+                external_id,
+                "Title",
+                "",
+                "",
+                "",
+                "url",
+                1,
+            )
+        };
+        let ids = item_repository
+            .insert_items(vec![new_item("feed/a", "1"), new_item("feed/removed", "2")])
+            .await
+            .unwrap()
+            .iter()
+            .map(|item| item.id.to_string())
+            .collect::<Vec<String>>();
+        let service = new_stream_service(
+            item_repository,
+            subscription_repository,
+            new_retention_repository(pool.clone()).await.unwrap(),
+            new_enclosure_repository(pool).await.unwrap(),
+            RetentionConfiguration::default(),
+            DedupPolicy::Off,
+            None,
+        );
+
+        let unread = service
+            .get_unread_item_contents("user_id", PageOption::new(10, false))
+            .await
+            .unwrap();
+        assert_eq!(unread.items.len(), 1);
+        let contents = service
+            .get_item_contents("user_id", &ids.iter().map(String::as_str).collect())
+            .await
+            .unwrap();
+        assert_eq!(contents.len(), 1);
+        assert_eq!(contents[0].origin.stream_id, "feed/a");
+    }
+}
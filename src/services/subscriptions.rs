@@ -1,11 +1,31 @@
-use crate::common::{current_time_ms, oldest_allowed_time_ms};
+use crate::common::current_time_ms;
+use crate::common::error::Errors;
+use crate::configuration::{DedupPolicy, RetentionConfiguration};
+use crate::database::enclosures::{Enclosure, EnclosureRepository};
+use crate::database::feed_states::{FeedState, FeedStateRepository};
 use crate::database::items::{Item, ItemRepository};
+use crate::database::retentions::RetentionRepository;
+use crate::database::rules::RuleRepository;
 use crate::database::subscriptions::SubscriptionRepository;
-use crate::services::feeds::{new_feed_service, FeedService};
+use crate::services::feeds::{error_status, new_feed_service, FeedService};
+use crate::services::opml::{render_opml, OpmlFeed};
+use crate::services::readability::extract_main_content;
+use crate::services::retention::{RetentionPolicies, RetentionPolicy};
+use crate::services::rules::RuleSet;
+use crate::services::sanitizer::sanitize_html;
+use crate::services::scheduler::{RefreshScheduler, SystemClock};
 use anyhow::Result;
 use async_trait::async_trait;
-use feed_rs::model::Feed;
+use feed_rs::model::{Entry, Feed};
+use futures::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// Subscriptions failing for longer than this are disabled, until the user
+// edits them again.
+const DISABLE_AFTER_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+// Articles are fetched from the sites, which shouldn't get too many requests.
+const MAX_CONCURRENT_EXTRACTIONS: usize = 4;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Category {
@@ -13,6 +33,15 @@ pub struct Category {
     pub label: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SubscriptionHealth {
+    pub last_success_ms: i64,
+    pub last_error: Option<String>,
+    pub last_http_status: Option<u16>,
+    pub consecutive_failures: i64,
+    pub disabled: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Subscription {
     pub id: String,
@@ -21,6 +50,10 @@ pub struct Subscription {
     pub categories: Vec<Category>,
     pub url: String,
     pub feed_url: String,
+    #[serde(default)]
+    pub health: SubscriptionHealth,
+    #[serde(default)]
+    pub extract_content: bool,
 }
 
 impl From<crate::database::subscriptions::Subscription> for Subscription {
@@ -46,6 +79,14 @@ impl From<crate::database::subscriptions::Subscription> for Subscription {
             url: db_subscription.url,
             feed_url: db_subscription.feed_url,
             categories: categories,
+            health: SubscriptionHealth {
+                last_success_ms: db_subscription.last_success_ms,
+                last_error: db_subscription.last_error,
+                last_http_status: db_subscription.last_http_status,
+                consecutive_failures: db_subscription.consecutive_failures,
+                disabled: db_subscription.disabled,
+            },
+            extract_content: db_subscription.extract_content,
         }
     }
 }
@@ -66,6 +107,12 @@ impl Subscription {
                 .collect::<Vec<String>>()
                 .join(","),
             last_fetch_ms: 0,
+            last_success_ms: self.health.last_success_ms,
+            last_error: self.health.last_error,
+            last_http_status: self.health.last_http_status,
+            consecutive_failures: self.health.consecutive_failures,
+            disabled: self.health.disabled,
+            extract_content: self.extract_content,
         }
     }
 
@@ -81,6 +128,42 @@ impl Subscription {
                 feed.links[0].href.clone()
             },
             feed_url: url.to_string(),
+            health: SubscriptionHealth::default(),
+            extract_content: false,
+        }
+    }
+}
+
+pub enum QuickAddResult {
+    Added(Subscription),
+    // The url isn't a feed, and it doesn't point to exactly one feed.
+    Candidates(Vec<String>),
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    Added,
+    Skipped,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub feed_url: String,
+    pub title: String,
+    pub status: ImportStatus,
+    pub error: Option<String>,
+}
+
+impl ImportResult {
+    fn new(feed: &OpmlFeed, status: ImportStatus, error: Option<String>) -> ImportResult {
+        ImportResult {
+            feed_url: feed.feed_url.clone(),
+            title: feed.title.clone(),
+            status,
+            error,
         }
     }
 }
@@ -91,10 +174,20 @@ pub trait SubscriptionService {
 
     async fn add_subscription_from_url(&self, user_id: &str, url: &str) -> Result<Subscription>;
 
+    async fn quick_add_subscription(&self, user_id: &str, url: &str) -> Result<QuickAddResult>;
+
     async fn add_subscription(&self, user_id: &str, subscription: Subscription) -> Result<()>;
 
     async fn remove_subscription(&self, user_id: &str, id: &str) -> Result<()>;
 
+    async fn import_subscriptions(
+        &self,
+        user_id: &str,
+        feeds: Vec<OpmlFeed>,
+    ) -> Result<Vec<ImportResult>>;
+
+    async fn export_subscriptions(&self, user_id: &str) -> Result<String>;
+
     async fn list_subscriptions(&self, user_id: &str) -> Result<Vec<Subscription>>;
 
     async fn load_subscription_items(&self, user_id: &str) -> Result<()>;
@@ -109,12 +202,31 @@ pub trait SubscriptionService {
         to_add: &Vec<&str>,
         to_remove: &Vec<&str>,
     ) -> Result<()>;
+
+    /// Lists the labels used by the subscriptions of the user.
+    async fn list_labels(&self, user_id: &str) -> Result<Vec<String>>;
+
+    /// Renames the label of subscriptions and items.
+    async fn rename_label(&self, user_id: &str, from: &str, to: &str) -> Result<()>;
+
+    /// Removes the label from subscriptions and items.
+    async fn remove_label(&self, user_id: &str, label: &str) -> Result<()>;
+
+    /// Whether to replace the content of new items with their linked articles.
+    async fn set_content_extraction(&self, user_id: &str, id: &str, enabled: bool) -> Result<()>;
 }
 
 struct SubscriptionServiceImpl {
     subscription_repository: Box<dyn SubscriptionRepository + Send + Sync>,
     item_repository: Box<dyn ItemRepository + Send + Sync>,
+    feed_state_repository: Box<dyn FeedStateRepository + Send + Sync>,
+    retention_repository: Box<dyn RetentionRepository + Send + Sync>,
+    enclosure_repository: Box<dyn EnclosureRepository + Send + Sync>,
+    rule_repository: Box<dyn RuleRepository + Send + Sync>,
     feed_service: Box<dyn FeedService + Send + Sync>,
+    scheduler: RefreshScheduler,
+    retention: RetentionConfiguration,
+    dedup: DedupPolicy,
 }
 
 fn extract_items_from_feed(user_id: &str, subscription_id: &str, feed: &Feed) -> Vec<Item> {
@@ -125,12 +237,26 @@ fn extract_items_from_feed(user_id: &str, subscription_id: &str, feed: &Feed) ->
                 .published
                 .or(entry.updated)
                 .map_or(current_time_ms(), |d| d.timestamp_millis());
+            // Relative urls in the content are relative to the entry, or the feed.
+            let base_url = entry
+                .links
+                .first()
+                .or_else(|| feed.links.first())
+                .map(|link| link.href.as_str());
+            let summary = entry.summary.as_ref().map_or("", |t| &t.content);
+            let content = entry
+                .content
+                .as_ref()
+                .and_then(|c| c.body.as_deref())
+                .filter(|body| !body.trim().is_empty())
+                .unwrap_or(summary);
             Item::new_item(
                 user_id,
                 subscription_id,
                 &entry.id,
                 &entry.title.as_ref().map_or("", |t| &t.content),
-                &entry.summary.as_ref().map_or("", |t| &t.content),
+                &sanitize_html(content, base_url),
+                &sanitize_html(summary, base_url),
                 &entry
                     .authors
                     .iter()
@@ -141,10 +267,194 @@ fn extract_items_from_feed(user_id: &str, subscription_id: &str, feed: &Feed) ->
                 created_at_ms,
             )
         })
-        .filter(|item| item.created_at_ms > oldest_allowed_time_ms())
         .collect()
 }
 
+// Enclosures of RSS are parsed as media, while Atom keeps them as links.
+fn extract_enclosures_from_entry(entry: &Entry) -> Vec<Enclosure> {
+    let media = entry.media.iter().flat_map(|object| {
+        object.content.iter().filter_map(move |content| {
+            Some(Enclosure {
+                item_id: 0,
+                url: content.url.as_ref()?.to_string(),
+                mime_type: content
+                    .content_type
+                    .as_ref()
+                    .map_or(String::new(), |mime| mime.to_string()),
+                length: content.size.map(|size| size as i64),
+                duration_seconds: content
+                    .duration
+                    .or(object.duration)
+                    .map(|duration| duration.as_secs() as i64),
+            })
+        })
+    });
+    let links = entry
+        .links
+        .iter()
+        .filter(|link| link.rel.as_deref() == Some("enclosure"))
+        .map(|link| Enclosure {
+            item_id: 0,
+            url: link.href.clone(),
+            mime_type: link.media_type.clone().unwrap_or_default(),
+            length: link.length.map(|length| length as i64),
+            duration_seconds: None,
+        });
+    let mut urls = HashSet::new();
+    media
+        .chain(links)
+        .filter(|enclosure| urls.insert(enclosure.url.clone()))
+        .collect()
+}
+
+impl SubscriptionServiceImpl {
+    async fn retention_policies(&self) -> Result<RetentionPolicies> {
+        Ok(RetentionPolicies::new(
+            &self.retention,
+            self.retention_repository.list_all_retentions().await?,
+        ))
+    }
+
+    async fn insert_items(
+        &self,
+        subscription: &crate::database::subscriptions::Subscription,
+        feed: &Feed,
+        policy: &RetentionPolicy,
+        rules: &RuleSet,
+    ) -> Result<()> {
+        let filtered = rules.apply(
+            extract_items_from_feed(&subscription.user_id, &subscription.id, feed),
+            subscription,
+        );
+        let items = policy.retain(filtered.items, current_time_ms());
+        let items = self.dedup_items(&subscription.user_id, items).await?;
+        let items = self.item_repository.insert_items(items).await?;
+        let item_tags = items
+            .iter()
+            .flat_map(|item| {
+                filtered
+                    .tags
+                    .get(&item.external_id)
+                    .into_iter()
+                    .flatten()
+                    .map(move |tag| (item.id, tag.clone()))
+            })
+            .collect();
+        self.item_repository
+            .add_item_tags(&subscription.user_id, item_tags)
+            .await?;
+        let entries = feed
+            .entries
+            .iter()
+            .map(|entry| (&entry.id, entry))
+            .collect::<HashMap<&String, &Entry>>();
+        let enclosures = items
+            .iter()
+            .filter_map(|item| entries.get(&item.external_id).map(|entry| (item, entry)))
+            .flat_map(|(item, entry)| {
+                extract_enclosures_from_entry(entry)
+                    .into_iter()
+                    .map(move |enclosure| Enclosure {
+                        item_id: item.id,
+                        ..enclosure
+                    })
+            })
+            .collect();
+        self.enclosure_repository
+            .insert_enclosures(enclosures)
+            .await?;
+        if subscription.extract_content {
+            self.extract_contents(items).await?;
+        }
+        Ok(())
+    }
+
+    // Drops the copies of items the user already has, or marks them as read
+    // if a copy is read, depending on the policy.
+    async fn dedup_items(&self, user_id: &str, mut items: Vec<Item>) -> Result<Vec<Item>> {
+        if self.dedup == DedupPolicy::Off {
+            return Ok(items);
+        }
+        let existing = self
+            .item_repository
+            .find_fingerprints(
+                user_id,
+                &items.iter().map(|item| item.fingerprint.as_str()).collect(),
+            )
+            .await?
+            .into_iter()
+            .collect::<HashMap<String, bool>>();
+        match self.dedup {
+            DedupPolicy::Hide => {
+                // Copies in the same feed are dropped too, except the first one.
+                let mut seen = existing.into_keys().collect::<HashSet<String>>();
+                items.retain(|item| {
+                    item.fingerprint.is_empty() || seen.insert(item.fingerprint.clone())
+                });
+            }
+            DedupPolicy::MarkRead => {
+                for item in items.iter_mut() {
+                    if existing.get(&item.fingerprint) == Some(&true) {
+                        item.read = true;
+                    }
+                }
+            }
+            DedupPolicy::Off => {}
+        }
+        Ok(items)
+    }
+
+    // Subscribes to an imported feed. Its failure is reported in the results,
+    // the other feeds are still imported.
+    async fn import_feed(&self, user_id: &str, feed: &OpmlFeed, fetched_feed: Feed) -> Result<()> {
+        let subscription = Subscription::from_feed(&feed.feed_url, fetched_feed);
+        let id = subscription.id.clone();
+        self.add_subscription(user_id, subscription).await?;
+        let title = if feed.title.is_empty() {
+            None
+        } else {
+            Some(feed.title.as_str())
+        };
+        self.edit_subscription(
+            user_id,
+            &id,
+            &title,
+            &feed.categories.iter().map(|c| c.as_str()).collect(),
+            &vec![],
+        )
+        .await
+    }
+
+    // Replaces the content of the items with the main content of their linked
+    // pages. Items keep the content from the feed if their page fails.
+    async fn extract_contents(&self, items: Vec<Item>) -> Result<()> {
+        let pages = stream::iter(items)
+            .map(|item| async move {
+                let page = self.feed_service.get_page(&item.url).await;
+                (item, page)
+            })
+            .buffer_unordered(MAX_CONCURRENT_EXTRACTIONS)
+            .collect::<Vec<_>>()
+            .await;
+        for (item, page) in pages {
+            let content = match page {
+                Ok(page) => extract_main_content(&page)
+                    .map(|content| sanitize_html(&content, Some(&item.url))),
+                Err(err) => {
+                    tracing::warn!("Failed to get the article {}: {}", item.url, err);
+                    continue;
+                }
+            };
+            if let Some(content) = content {
+                self.item_repository
+                    .update_item_content(item.id, &content)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl SubscriptionService for SubscriptionServiceImpl {
     async fn get_subscription_from_url(&self, url: &str) -> Result<Subscription> {
@@ -158,6 +468,33 @@ impl SubscriptionService for SubscriptionServiceImpl {
         Ok(subscription)
     }
 
+    async fn quick_add_subscription(&self, user_id: &str, url: &str) -> Result<QuickAddResult> {
+        let mut candidates = self.feed_service.discover_feeds(url).await?;
+        if candidates.len() == 1 {
+            let candidate = candidates.pop().unwrap();
+            let subscription = match candidate.feed {
+                // The feed is fetched already when the url is the feed.
+                Some(feed) => {
+                    let subscription = Subscription::from_feed(&candidate.url, feed);
+                    self.add_subscription(user_id, subscription.clone()).await?;
+                    subscription
+                }
+                None => {
+                    self.add_subscription_from_url(user_id, &candidate.url)
+                        .await?
+                }
+            };
+            Ok(QuickAddResult::Added(subscription))
+        } else {
+            Ok(QuickAddResult::Candidates(
+                candidates
+                    .into_iter()
+                    .map(|candidate| candidate.url)
+                    .collect(),
+            ))
+        }
+    }
+
     async fn add_subscription(&self, user_id: &str, subscription: Subscription) -> Result<()> {
         self.subscription_repository
             .insert_subscription(subscription.to_db(user_id))
@@ -169,7 +506,69 @@ impl SubscriptionService for SubscriptionServiceImpl {
         self.subscription_repository
             .remove_subscription(user_id, id)
             .await?;
-        Ok(())
+        self.retention_repository
+            .delete_retention(user_id, id)
+            .await
+    }
+
+    async fn import_subscriptions(
+        &self,
+        user_id: &str,
+        feeds: Vec<OpmlFeed>,
+    ) -> Result<Vec<ImportResult>> {
+        let existing_urls = self
+            .subscription_repository
+            .list_user_subscriptions(user_id)
+            .await?
+            .into_iter()
+            .map(|sub| sub.feed_url)
+            .collect::<HashSet<String>>();
+        let mut urls = feeds
+            .iter()
+            .filter(|feed| !existing_urls.contains(&feed.feed_url))
+            .map(|feed| -> &str { &feed.feed_url })
+            .collect::<Vec<&str>>();
+        urls.sort();
+        urls.dedup();
+        let mut fetched_feeds = self.feed_service.get_feeds(urls).await;
+
+        let mut seen_urls = HashSet::new();
+        let mut results = vec![];
+        for feed in feeds.iter() {
+            if existing_urls.contains(&feed.feed_url) || !seen_urls.insert(&feed.feed_url) {
+                results.push(ImportResult::new(feed, ImportStatus::Skipped, None));
+                continue;
+            }
+            match fetched_feeds.remove(&feed.feed_url) {
+                Some(Ok(fetched_feed)) => {
+                    results.push(match self.import_feed(user_id, feed, fetched_feed).await {
+                        Ok(()) => ImportResult::new(feed, ImportStatus::Added, None),
+                        Err(err) => {
+                            ImportResult::new(feed, ImportStatus::Failed, Some(err.to_string()))
+                        }
+                    })
+                }
+                Some(Err(err)) => results.push(ImportResult::new(
+                    feed,
+                    ImportStatus::Failed,
+                    Some(err.to_string()),
+                )),
+                None => results.push(ImportResult::new(
+                    feed,
+                    ImportStatus::Failed,
+                    Some(String::from("Feed is not fetched")),
+                )),
+            }
+        }
+        Ok(results)
+    }
+
+    async fn export_subscriptions(&self, user_id: &str) -> Result<String> {
+        let subscriptions = self
+            .subscription_repository
+            .list_user_subscriptions(user_id)
+            .await?;
+        Ok(render_opml(&subscriptions))
     }
 
     async fn list_subscriptions(&self, user_id: &str) -> Result<Vec<Subscription>> {
@@ -193,13 +592,15 @@ impl SubscriptionService for SubscriptionServiceImpl {
             .map(|sub| -> &str { &sub.feed_url })
             .collect::<Vec<&str>>();
         let feeds = self.feed_service.get_feeds(urls).await;
+        let policies = self.retention_policies().await?;
+        let rules = RuleSet::new(self.rule_repository.list_user_rules(user_id).await?);
         for subscription in subscriptions {
             let url = &subscription.feed_url;
             match feeds.get(url) {
                 Some(Ok(feed)) => {
-                    self.item_repository
-                        .insert_items(extract_items_from_feed(user_id, &subscription.id, feed))
-                        .await?;
+                    let policy = policies.policy(user_id, &subscription.id);
+                    self.insert_items(&subscription, feed, &policy, &rules)
+                        .await?
                 }
                 _ => continue,
             };
@@ -211,28 +612,93 @@ impl SubscriptionService for SubscriptionServiceImpl {
         let subscriptions = self
             .subscription_repository
             .list_all_subscriptions()
-            .await?;
-        let mut urls = subscriptions
-            .iter()
-            .map(|sub| -> &str { &sub.feed_url })
-            .collect::<Vec<&str>>();
-        urls.dedup();
-        let feeds = self.feed_service.get_feeds(urls).await;
-        for subscription in subscriptions {
+            .await?
+            .into_iter()
+            .filter(|subscription| !subscription.disabled)
+            .collect::<Vec<_>>();
+        let mut stored_states = self.feed_state_repository.list_feed_states().await?;
+        let mut states: HashMap<&str, FeedState> = HashMap::new();
+        for subscription in subscriptions.iter() {
+            let url: &str = &subscription.feed_url;
+            let state = states.entry(url).or_insert_with(|| {
+                stored_states
+                    .remove(url)
+                    .unwrap_or_else(|| FeedState::new(url))
+            });
+            // A subscription which never got the feed needs the whole feed,
+            // even if other subscriptions of the same feed are up to date.
+            if subscription.last_success_ms == 0 {
+                state.etag = None;
+                state.last_modified = None;
+            }
+            if subscription.last_fetch_ms == 0 {
+                state.next_fetch_ms = 0;
+            }
+        }
+        let due_states = states
+            .values()
+            .filter(|state| self.scheduler.is_due(state))
+            .collect::<Vec<&FeedState>>();
+        if due_states.is_empty() {
+            return Ok(());
+        }
+        let responses = self.feed_service.get_feeds_if_modified(due_states).await;
+        let policies = self.retention_policies().await?;
+        let rules = RuleSet::new(self.rule_repository.list_all_rules().await?);
+        let fetched_at_ms = current_time_ms();
+        for subscription in subscriptions.iter() {
             let url = &subscription.feed_url;
-            match feeds.get(url) {
-                Some(Ok(feed)) => {
-                    self.item_repository
-                        .insert_items(extract_items_from_feed(
+            match responses.get(url) {
+                Some(Ok(response)) => {
+                    if let Some(ref feed) = response.feed {
+                        let policy = policies.policy(&subscription.user_id, &subscription.id);
+                        self.insert_items(subscription, feed, &policy, &rules)
+                            .await?;
+                    }
+                    self.subscription_repository
+                        .record_fetch_success(
                             &subscription.user_id,
                             &subscription.id,
-                            feed,
-                        ))
+                            fetched_at_ms,
+                            response.status,
+                        )
                         .await?;
                 }
-                _ => continue,
+                Some(Err(err)) => {
+                    let disable = subscription.last_success_ms > 0
+                        && fetched_at_ms - subscription.last_success_ms >= DISABLE_AFTER_MS;
+                    if disable {
+                        tracing::warn!(
+                            "Disable subscription {} of user {}, it keeps failing: {}",
+                            subscription.id,
+                            subscription.user_id,
+                            err
+                        );
+                    }
+                    self.subscription_repository
+                        .record_fetch_failure(
+                            &subscription.user_id,
+                            &subscription.id,
+                            fetched_at_ms,
+                            &err.to_string(),
+                            error_status(err),
+                            disable,
+                        )
+                        .await?;
+                }
+                None => continue,
             };
         }
+        for (url, response) in responses.into_iter() {
+            let mut state = states
+                .remove(url.as_str())
+                .unwrap_or_else(|| FeedState::new(&url));
+            match response {
+                Ok(ref response) => self.scheduler.on_success(&mut state, response),
+                Err(ref err) => self.scheduler.on_failure(&mut state, err),
+            }
+            self.feed_state_repository.upsert_feed_state(state).await?;
+        }
         Ok(())
     }
 
@@ -254,21 +720,239 @@ impl SubscriptionService for SubscriptionServiceImpl {
             }
             subscription.remove_categories(to_remove);
             subscription.add_categories(to_add);
+            // Editing a subscription gives a disabled feed another chance.
+            subscription.disabled = false;
+            subscription.consecutive_failures = 0;
             self.subscription_repository
                 .update_subscription(subscription)
                 .await?;
         }
         Ok(())
     }
+
+    async fn list_labels(&self, user_id: &str) -> Result<Vec<String>> {
+        self.subscription_repository.list_categories(user_id).await
+    }
+
+    async fn rename_label(&self, user_id: &str, from: &str, to: &str) -> Result<()> {
+        self.subscription_repository
+            .rename_category(user_id, from, to)
+            .await?;
+        self.item_repository.rename_tag(user_id, from, to).await
+    }
+
+    async fn remove_label(&self, user_id: &str, label: &str) -> Result<()> {
+        self.subscription_repository
+            .remove_category(user_id, label)
+            .await?;
+        self.item_repository.remove_tag(user_id, label).await
+    }
+
+    async fn set_content_extraction(&self, user_id: &str, id: &str, enabled: bool) -> Result<()> {
+        let mut subscription = self
+            .subscription_repository
+            .get_subscription(user_id, id)
+            .await?
+            .ok_or(Errors::SubscriptionNotFound)?;
+        subscription.extract_content = enabled;
+        self.subscription_repository
+            .update_subscription(subscription)
+            .await?;
+        Ok(())
+    }
 }
 
 pub fn new_subscription_service(
     subscription_repository: Box<dyn SubscriptionRepository + Send + Sync>,
     item_repository: Box<dyn ItemRepository + Send + Sync>,
+    feed_state_repository: Box<dyn FeedStateRepository + Send + Sync>,
+    retention_repository: Box<dyn RetentionRepository + Send + Sync>,
+    enclosure_repository: Box<dyn EnclosureRepository + Send + Sync>,
+    rule_repository: Box<dyn RuleRepository + Send + Sync>,
+    retention: RetentionConfiguration,
+    dedup: DedupPolicy,
 ) -> Box<dyn SubscriptionService + Send + Sync> {
     Box::new(SubscriptionServiceImpl {
         subscription_repository: subscription_repository,
         item_repository: item_repository,
+        feed_state_repository: feed_state_repository,
+        retention_repository,
+        enclosure_repository,
+        rule_repository,
         feed_service: new_feed_service(),
+        scheduler: RefreshScheduler::new(Box::new(SystemClock)),
+        retention,
+        dedup,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enclosures_should_be_extracted() {
+        let rss = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">
+  <channel>
+    <title>Podcast</title>
+    <item>
+      <guid>1</guid>
+      <title>Episode 1</title>
+      <enclosure url="https://podcast.com/1.mp3" type="audio/mpeg" length="1024"/>
+      <itunes:duration>01:02:03</itunes:duration>
+    </item>
+  </channel>
+</rss>"#;
+        let feed = feed_rs::parser::parse(rss.as_bytes()).unwrap();
+        assert_eq!(
+            extract_enclosures_from_entry(&feed.entries[0]),
+            vec![Enclosure {
+                item_id: 0,
+                url: "https://podcast.com/1.mp3".to_string(),
+                mime_type: "audio/mpeg".to_string(),
+                length: Some(1024),
+                duration_seconds: Some(3723),
+            }]
+        );
+
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Videos</title>
+  <entry>
+    <id>1</id>
+    <title>Video 1</title>
+    <link href="https://videos.com/1.html"/>
+    <link rel="enclosure" href="https://videos.com/1.mp4" type="video/mp4" length="2048"/>
+  </entry>
+</feed>"#;
+        let feed = feed_rs::parser::parse(atom.as_bytes()).unwrap();
+        assert_eq!(
+            extract_enclosures_from_entry(&feed.entries[0]),
+            vec![Enclosure {
+                item_id: 0,
+                url: "https://videos.com/1.mp4".to_string(),
+                mime_type: "video/mp4".to_string(),
+                length: Some(2048),
+                duration_seconds: None,
+            }]
+        );
+    }
+
+    // The user is subscribed to feed/a.
+    async fn new_service(dedup: DedupPolicy) -> SubscriptionServiceImpl {
+        use crate::database::enclosures::new_enclosure_repository;
+        use crate::database::feed_states::new_feed_state_repository;
+        use crate::database::in_memory_pool;
+        use crate::database::items::new_item_repository;
+        use crate::database::retentions::new_retention_repository;
+        use crate::database::rules::new_rule_repository;
+        use crate::database::subscriptions::new_subscription_repository;
+
+        let pool = in_memory_pool().await;
+        let service = SubscriptionServiceImpl {
+            subscription_repository: new_subscription_repository(pool.clone()).await.unwrap(),
+            item_repository: new_item_repository(pool.clone()).await.unwrap(),
+            feed_state_repository: new_feed_state_repository(pool.clone()).await.unwrap(),
+            retention_repository: new_retention_repository(pool.clone()).await.unwrap(),
+            enclosure_repository: new_enclosure_repository(pool.clone()).await.unwrap(),
+            rule_repository: new_rule_repository(pool).await.unwrap(),
+            feed_service: new_feed_service(),
+            scheduler: RefreshScheduler::new(Box::new(SystemClock)),
+            retention: RetentionConfiguration::default(),
+            dedup,
+        };
+        service
+            .add_subscription(
+                "user_id",
+                Subscription {
+                    id: "feed/a".to_string(),
+                    title: "title".to_string(),
+                    description: "description".to_string(),
+                    categories: vec![],
+                    url: "url".to_string(),
+                    feed_url: "a".to_string(),
+                    health: SubscriptionHealth::default(),
+                    extract_content: false,
+                },
+            )
+            .await
+            .unwrap();
+        service
+    }
+
+    fn new_copy(subscription_id: &str, external_id: &str, url: &str) -> Item {
+        Item::new_item(
+            "user_id",
+            subscription_id,
+            external_id,
+            "Title",
+            "",
+            "",
+            "",
+            url,
+            1,
+        )
+    }
+
+    #[tokio::test]
+    async fn copies_should_be_hidden() {
+        let service = new_service(DedupPolicy::Hide).await;
+        service
+            .item_repository
+            .insert_items(vec![
+                new_copy("feed/a", "1", "https://blog.com/1"),
+                new_copy("feed/removed", "4", "https://blog.com/4"),
+            ])
+            .await
+            .unwrap();
+
+        let items = service
+            .dedup_items(
+                "user_id",
+                vec![
+                    new_copy("feed/b", "1", "https://blog.com/1/"),
+                    new_copy("feed/b", "2", "https://blog.com/2"),
+                    new_copy("feed/b", "3", "https://blog.com/2#comments"),
+                    new_copy("feed/b", "4", "https://blog.com/4"),
+                ],
+            )
+            .await
+            .unwrap();
+        // The copy of the unsubscribed feed isn't shown, so it hides nothing.
+        assert_eq!(
+            items,
+            vec![
+                new_copy("feed/b", "2", "https://blog.com/2"),
+                new_copy("feed/b", "4", "https://blog.com/4")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn copies_of_read_items_should_be_read() {
+        let service = new_service(DedupPolicy::MarkRead).await;
+        let mut read = new_copy("feed/a", "1", "https://blog.com/1");
+        read.read = true;
+        service
+            .item_repository
+            .insert_items(vec![read, new_copy("feed/a", "2", "https://blog.com/2")])
+            .await
+            .unwrap();
+
+        let items = service
+            .dedup_items(
+                "user_id",
+                vec![
+                    new_copy("feed/b", "1", "https://blog.com/1"),
+                    new_copy("feed/b", "2", "https://blog.com/2"),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            items.iter().map(|item| item.read).collect::<Vec<bool>>(),
+            vec![true, false]
+        );
+    }
+}
@@ -1,21 +1,46 @@
 use crate::common::error::Errors;
-use crate::common::new_id;
 use crate::common::token::Token;
+use crate::common::{current_time_ms, current_time_s, new_id};
+use crate::database::items::delete_orphan_entries;
 use anyhow::{Error, Result};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hasher;
 
+#[derive(sqlx::Type, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    User,
+}
+
 #[derive(sqlx::FromRow, PartialEq, Eq, Debug, Clone)]
 pub struct User {
     pub id: String,
     pub email: String,
     pub password_hash: String,
     pub token: String,
+    pub role: Role,
+    pub disabled: bool,
 }
 
 impl User {
+    // Argon2id in the PHC string format.
     fn hash_password(password: &str) -> String {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .expect("Failed to hash the password.")
+            .to_string()
+    }
+
+    // Passwords were hashed like this before Argon2 was used.
+    fn legacy_hash_password(password: &str) -> String {
         let mut hasher = DefaultHasher::new();
         hasher.write(password.as_bytes());
         hasher.finish().to_string()
@@ -27,16 +52,48 @@ impl User {
             email: email.to_string(),
             password_hash: User::hash_password(password),
             token: Token::new(id).to_string(),
+            role: Role::User,
+            disabled: false,
         }
     }
 
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
     pub fn token(&self) -> Token {
         Token::parse(&self.token).unwrap()
     }
 
     pub fn match_password(&self, password: &str) -> bool {
-        self.password_hash.eq(&User::hash_password(password))
+        match PasswordHash::new(&self.password_hash) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(_) => self.password_hash.eq(&User::legacy_hash_password(password)),
+        }
     }
+
+    pub fn has_legacy_password_hash(&self) -> bool {
+        PasswordHash::new(&self.password_hash).is_err()
+    }
+}
+
+// Default lifetime of the tokens issued to client apps.
+const NAMED_TOKEN_LIFETIME_S: i64 = 365 * 24 * 60 * 60;
+
+/// A token issued to one client app, which can be revoked on its own.
+#[derive(sqlx::FromRow, Serialize, PartialEq, Eq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NamedToken {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token: String,
+    pub created_at_ms: i64,
+    pub expire_at: i64,
 }
 
 pub struct UserCreds {
@@ -59,9 +116,20 @@ impl UserService {
             None => Err(Error::new(Errors::NonExistUser {
                 email: email.to_string(),
             })),
+            Some(ref user) if user.disabled => Err(Error::new(Errors::DisabledUser {
+                email: email.to_string(),
+            })),
             Some(ref user) => {
-                let token = user.token();
                 if user.match_password(password) {
+                    if user.has_legacy_password_hash() {
+                        self.update_password_hash(&user.id, &User::hash_password(password))
+                            .await?;
+                    }
+                    let mut token = user.token();
+                    if token.is_expired() {
+                        token = Token::new(&user.id);
+                        self.update_token(&user.id, &token.to_string()).await?;
+                    }
                     Ok(UserCreds {
                         sid: token.sid.clone(),
                         lsid: token.sid.clone(),
@@ -74,8 +142,96 @@ impl UserService {
         }
     }
 
-    pub async fn register(&self, email: &str, password: &str) -> Result<()> {
-        self.create_user(email, password).await?.unwrap();
+    /// Registers the user from the configuration, who administrates the others.
+    pub async fn register_admin(&self, email: &str, password: &str) -> Result<()> {
+        let user = self.create_user(email, password).await?;
+        self.set_role(&user.id, Role::Admin).await
+    }
+
+    pub async fn add_user(&self, email: &str, password: &str, role: Role) -> Result<User> {
+        if self.get_user_by_email(email).await?.is_some() {
+            return Err(Error::new(Errors::UserExists {
+                email: email.to_string(),
+            }));
+        }
+        let mut user = self.create_user(email, password).await?;
+        if role != Role::User {
+            self.set_role(&user.id, role).await?;
+            user.role = role;
+        }
+        Ok(user)
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<User>> {
+        let users = sqlx::query_as::<_, User>("SELECT * FROM Users ORDER BY email")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(users)
+    }
+
+    pub async fn set_user_disabled(&self, id: &str, disabled: bool) -> Result<()> {
+        let result = sqlx::query("UPDATE Users SET disabled = ? WHERE id = ?")
+            .bind(disabled)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Self::check_user_affected(id, result.rows_affected())
+    }
+
+    /// Resets the password, and signs the user out of every client.
+    pub async fn reset_password(&self, id: &str, password: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query("UPDATE Users SET password_hash = ?, token = ? WHERE id = ?")
+            .bind(User::hash_password(password))
+            .bind(Token::new(id).to_string())
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        Self::check_user_affected(id, result.rows_affected())?;
+        sqlx::query("DELETE FROM Tokens WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes the user together with the subscriptions, items and tokens.
+    pub async fn delete_user(&self, id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM ItemStatesFts WHERE rowid IN (SELECT id FROM ItemStates WHERE user_id = ?)",
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+        for table in [
+            "ItemStates",
+            "ItemTags",
+            "Subscriptions",
+            "Tokens",
+            "Retentions",
+            "Rules",
+        ] {
+            sqlx::query(&format!("DELETE FROM {} WHERE user_id = ?", table))
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+        }
+        delete_orphan_entries(&mut tx).await?;
+        let result = sqlx::query("DELETE FROM Users WHERE id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        Self::check_user_affected(id, result.rows_affected())?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    fn check_user_affected(id: &str, rows_affected: u64) -> Result<()> {
+        if rows_affected == 0 {
+            return Err(Error::new(Errors::UserNotFound { id: id.to_string() }));
+        }
         Ok(())
     }
 
@@ -88,9 +244,67 @@ impl UserService {
         }
     }
 
-    async fn create_user(&self, email: &str, password: &str) -> Result<Option<User>> {
+    pub async fn create_token(
+        &self,
+        user_id: &str,
+        name: &str,
+        expire_in_days: Option<i64>,
+    ) -> Result<NamedToken> {
+        let lifetime_s = match expire_in_days {
+            Some(days) => days.checked_mul(24 * 60 * 60),
+            None => Some(NAMED_TOKEN_LIFETIME_S),
+        };
+        let expire_at = lifetime_s
+            .and_then(|lifetime_s| current_time_s().checked_add(lifetime_s))
+            .ok_or_else(|| Errors::invalid_request("Token lifetime is too long"))?;
+        let token = NamedToken {
+            id: new_id(10),
+            user_id: user_id.to_string(),
+            name: name.to_string(),
+            token: Token::expire_at(user_id, expire_at).to_string(),
+            created_at_ms: current_time_ms(),
+            expire_at,
+        };
+        sqlx::query(
+            "INSERT INTO Tokens (id, user_id, name, token, created_at_ms, expire_at)
+       VALUES(?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&token.id)
+        .bind(&token.user_id)
+        .bind(&token.name)
+        .bind(&token.token)
+        .bind(token.created_at_ms)
+        .bind(token.expire_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    pub async fn list_tokens(&self, user_id: &str) -> Result<Vec<NamedToken>> {
+        let tokens = sqlx::query_as::<_, NamedToken>(
+            "SELECT * FROM Tokens WHERE user_id = ? ORDER BY created_at_ms",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tokens)
+    }
+
+    pub async fn revoke_token(&self, user_id: &str, id: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM Tokens WHERE user_id = ? AND id = ?")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::new(Errors::TokenNotFound { id: id.to_string() }));
+        }
+        Ok(())
+    }
+
+    async fn create_user(&self, email: &str, password: &str) -> Result<User> {
         if let Some(user) = self.get_user_by_email(email).await? {
-            return Ok(Some(user));
+            return Ok(user);
         }
         let new_user = User::new(&new_id(10), email, password);
         sqlx::query(
@@ -103,7 +317,34 @@ impl UserService {
         .bind(&new_user.token)
         .execute(&self.pool)
         .await?;
-        Ok(Some(new_user))
+        Ok(new_user)
+    }
+
+    async fn update_password_hash(&self, id: &str, password_hash: &str) -> Result<()> {
+        sqlx::query("UPDATE Users SET password_hash = ? WHERE id = ?")
+            .bind(password_hash)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_role(&self, id: &str, role: Role) -> Result<()> {
+        sqlx::query("UPDATE Users SET role = ? WHERE id = ?")
+            .bind(role)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_token(&self, id: &str, token: &str) -> Result<()> {
+        sqlx::query("UPDATE Users SET token = ? WHERE id = ?")
+            .bind(token)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
     async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
@@ -115,10 +356,227 @@ impl UserService {
     }
 
     async fn get_user_by_token(&self, token: &str) -> Result<Option<User>> {
-        let user = sqlx::query_as::<_, User>("SELECT * FROM Users WHERE token = ?")
-            .bind(token)
-            .fetch_optional(&self.pool)
-            .await?;
+        let user =
+            sqlx::query_as::<_, User>("SELECT * FROM Users WHERE token = ? AND NOT disabled")
+                .bind(token)
+                .fetch_optional(&self.pool)
+                .await?;
+        if user.is_some() {
+            return Ok(user);
+        }
+        let user = sqlx::query_as::<_, User>(
+            "SELECT Users.* FROM Users JOIN Tokens ON Users.id = Tokens.user_id
+       WHERE Tokens.token = ? AND NOT Users.disabled",
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?;
         Ok(user)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::in_memory_pool;
+    use crate::database::items::{new_item_repository, Item};
+
+    #[test]
+    fn password_should_be_hashed_with_argon2() {
+        let user = User::new("id", "email", "password");
+        assert!(user.password_hash.starts_with("$argon2id$"));
+        assert!(!user.has_legacy_password_hash());
+        assert!(user.match_password("password"));
+        assert!(!user.match_password("wrong"));
+        assert_ne!(
+            user.password_hash,
+            User::new("id", "email", "password").password_hash
+        );
+    }
+
+    #[tokio::test]
+    async fn login_should_upgrade_legacy_password_hash() {
+        let pool = in_memory_pool().await;
+        let user_service = UserService::new(pool.clone());
+        let mut user = User::new("id", "email", "password");
+        user.password_hash = User::legacy_hash_password("password");
+        sqlx::query("INSERT INTO Users (id, email, password_hash, token) VALUES(?, ?, ?, ?)")
+            .bind(&user.id)
+            .bind(&user.email)
+            .bind(&user.password_hash)
+            .bind(&user.token)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(user_service.login("email", "wrong").await.is_err());
+        let stored = user_service
+            .get_user_by_email("email")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stored.has_legacy_password_hash());
+
+        user_service.login("email", "password").await.unwrap();
+        let stored = user_service
+            .get_user_by_email("email")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stored.password_hash.starts_with("$argon2id$"));
+        assert!(user_service.login("email", "password").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn named_tokens_should_be_revocable() {
+        let pool = in_memory_pool().await;
+        let user_service = UserService::new(pool.clone());
+        let user = user_service
+            .add_user("email", "password", Role::User)
+            .await
+            .unwrap();
+
+        let token = user_service
+            .create_token(&user.id, "Reeder", None)
+            .await
+            .unwrap();
+        assert!(Token::is_valid(&token.token));
+        assert_eq!(user_service.get_user(&token.token).await.unwrap(), user);
+        assert_eq!(
+            user_service.list_tokens(&user.id).await.unwrap(),
+            vec![token.clone()]
+        );
+
+        user_service
+            .revoke_token(&user.id, &token.id)
+            .await
+            .unwrap();
+        assert!(user_service.get_user(&token.token).await.is_err());
+        assert!(user_service.list_tokens(&user.id).await.unwrap().is_empty());
+        assert!(user_service
+            .revoke_token(&user.id, &token.id)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn disabled_user_should_be_rejected() {
+        let pool = in_memory_pool().await;
+        let user_service = UserService::new(pool.clone());
+        let user = user_service
+            .add_user("email", "password", Role::User)
+            .await
+            .unwrap();
+        assert!(user_service
+            .add_user("email", "password", Role::Admin)
+            .await
+            .is_err());
+
+        user_service
+            .set_user_disabled(&user.id, true)
+            .await
+            .unwrap();
+        assert!(user_service.login("email", "password").await.is_err());
+        assert!(user_service.get_user(&user.token).await.is_err());
+
+        user_service
+            .set_user_disabled(&user.id, false)
+            .await
+            .unwrap();
+        assert!(user_service.login("email", "password").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reset_password_should_sign_out_user() {
+        let pool = in_memory_pool().await;
+        let user_service = UserService::new(pool.clone());
+        let user = user_service
+            .add_user("email", "password", Role::User)
+            .await
+            .unwrap();
+        let token = user_service
+            .create_token(&user.id, "Reeder", None)
+            .await
+            .unwrap();
+
+        user_service
+            .reset_password(&user.id, "new password")
+            .await
+            .unwrap();
+        assert!(user_service.login("email", "password").await.is_err());
+        assert!(user_service.login("email", "new password").await.is_ok());
+        assert!(user_service.get_user(&user.token).await.is_err());
+        assert!(user_service.get_user(&token.token).await.is_err());
+        assert!(user_service
+            .reset_password("unknown", "password")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn delete_user_should_delete_subscriptions_and_items() {
+        let pool = in_memory_pool().await;
+        let user_service = UserService::new(pool.clone());
+        user_service
+            .register_admin("admin", "password")
+            .await
+            .unwrap();
+        let user = user_service
+            .add_user("email", "password", Role::User)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO Subscriptions (user_id, id, url, title, description, feed_url, last_fetch_ms)
+       VALUES(?, 'sub', 'url', 'title', 'description', 'feed_url', 0)",
+        )
+        .bind(&user.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        new_item_repository(pool.clone())
+            .await
+            .unwrap()
+            .insert_items(vec![Item::new_item(
+                &user.id, "sub", "item", "title", "content", "", "author", "url", 0,
+            )])
+            .await
+            .unwrap();
+
+        user_service.delete_user(&user.id).await.unwrap();
+        let items: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM Items")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(items, 0);
+        let entries: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM Entries")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(entries, 0);
+        let subscriptions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM Subscriptions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(subscriptions, 0);
+        let users = user_service.list_users().await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert!(users[0].is_admin());
+        assert!(user_service.delete_user(&user.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn overflowing_token_lifetimes_should_be_rejected() {
+        let user_service = UserService::new(in_memory_pool().await);
+        let user = user_service
+            .add_user("email", "password", Role::User)
+            .await
+            .unwrap();
+
+        assert!(user_service
+            .create_token(&user.id, "Reeder", Some(i64::MAX))
+            .await
+            .is_err());
+        assert!(user_service.list_tokens(&user.id).await.unwrap().is_empty());
+    }
+}
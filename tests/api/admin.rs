@@ -0,0 +1,73 @@
+use crate::data::{AdminUserInfo, AdminUserInfos};
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn configured_user_should_be_admin() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.list_users().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let users = response.json::<AdminUserInfos>().await.unwrap().users;
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].email, app.test_user.email);
+    assert_eq!(users[0].role, "admin");
+}
+
+#[tokio::test]
+async fn admin_should_manage_users() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.add_user("reader@gmail.com", "password").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let user = response.json::<AdminUserInfo>().await.unwrap();
+    assert_eq!(user.role, "user");
+    assert!(!user.disabled);
+    assert_eq!(
+        app.add_user("reader@gmail.com", "password")
+            .await
+            .status()
+            .as_u16(),
+        400
+    );
+
+    let response = app.login("reader@gmail.com", "password").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        app.disable_user(&user.id, true).await.status().as_u16(),
+        200
+    );
+    let response = app.login("reader@gmail.com", "password").await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    assert_eq!(app.delete_user(&user.id).await.status().as_u16(), 200);
+    assert_eq!(app.delete_user(&user.id).await.status().as_u16(), 404);
+    let users = app
+        .list_users()
+        .await
+        .json::<AdminUserInfos>()
+        .await
+        .unwrap();
+    assert_eq!(users.users.len(), 1);
+}
+
+#[tokio::test]
+async fn non_admin_should_not_manage_users() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    app.add_user("reader@gmail.com", "password").await;
+
+    app.test_user.email = "reader@gmail.com".to_string();
+    app.test_user_login().await;
+    assert_eq!(app.list_users().await.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn registration_should_be_closed_by_default() {
+    let app = spawn_app().await;
+
+    let response = app.register("reader@gmail.com", "password").await;
+    assert_eq!(response.status().as_u16(), 403);
+}
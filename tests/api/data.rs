@@ -18,7 +18,7 @@ pub struct Summary {
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Origin {
-    stream_id: String,
+    pub stream_id: String,
     title: String,
     html_url: String,
 }
@@ -67,6 +67,15 @@ pub struct Category {
     pub label: String,
 }
 
+#[derive(Deserialize, Clone)]
+pub struct SubscriptionHealth {
+    pub last_success_ms: i64,
+    pub last_error: Option<String>,
+    pub last_http_status: Option<u16>,
+    pub consecutive_failures: i64,
+    pub disabled: bool,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct Subscription {
     pub id: String,
@@ -75,9 +84,171 @@ pub struct Subscription {
     pub categories: Vec<Category>,
     pub url: String,
     pub feed_url: String,
+    pub health: SubscriptionHealth,
+    pub extract_content: bool,
 }
 
 #[derive(Deserialize)]
 pub struct Subscriptions {
     pub subscriptions: Vec<Subscription>,
 }
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub feed_url: String,
+    pub title: String,
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportResults {
+    pub results: Vec<ImportResult>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Token {
+    pub id: String,
+    pub name: String,
+    pub token: Option<String>,
+    pub created_at_ms: i64,
+    pub expire_at: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Tokens {
+    pub tokens: Vec<Token>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AdminUserInfo {
+    pub id: String,
+    pub email: String,
+    pub role: String,
+    pub disabled: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AdminUserInfos {
+    pub users: Vec<AdminUserInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Tag {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_f: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Tags {
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UnreadCount {
+    pub id: String,
+    pub count: i64,
+    pub newest_item_timestamp_usec: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UnreadCounts {
+    pub max: i64,
+    pub unreadcounts: Vec<UnreadCount>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemRef {
+    pub id: String,
+    pub timestamp_usec: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemIds {
+    pub item_refs: Vec<ItemRef>,
+    pub continuation: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StreamContents {
+    pub id: String,
+    pub title: String,
+    pub items: Vec<ItemContent>,
+    pub continuation: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResults {
+    pub results: Vec<ItemRef>,
+    pub continuation: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<i64>,
+    pub max_items: Option<i64>,
+    pub keep_starred: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Retention {
+    pub subscription_id: String,
+    pub max_age_days: Option<i64>,
+    pub max_items: Option<i64>,
+    pub keep_starred: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Retentions {
+    pub policy: RetentionPolicy,
+    pub overrides: Vec<Retention>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+    pub field: String,
+    pub match_type: String,
+    pub pattern: String,
+    pub action: String,
+    pub tag: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchedItem {
+    pub id: String,
+    pub subscription_id: String,
+    pub title: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DryRun {
+    pub items: Vec<MatchedItem>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorDetail {
+    pub code: u16,
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
@@ -0,0 +1,91 @@
+use crate::data::ErrorResponse;
+use crate::helpers::spawn_app;
+
+async fn assert_error(response: reqwest::Response, code: u16) -> String {
+    assert_eq!(response.status().as_u16(), code);
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(body.error.code, code);
+    body.error.message
+}
+
+#[tokio::test]
+async fn unknown_subscription_should_be_not_found() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app
+        .set_content_extraction("feed/https://blog.com/atom.xml", true)
+        .await;
+    assert_error(response, 404).await;
+}
+
+#[tokio::test]
+async fn bad_input_should_be_bad_request() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.mark_all_as_read("unknown", None).await;
+    let message = assert_error(response, 400).await;
+    assert!(message.contains("unknown"));
+
+    let response = app
+        .get_stream_contents(
+            "user/-/state/com.google/reading-list",
+            &[("c", "not-an-offset")],
+        )
+        .await;
+    assert_error(response, 400).await;
+
+    let response = app.quick_add_subscription("not a url").await;
+    assert_error(response, 400).await;
+
+    let response = app.add_subscription("not a url", None, None).await;
+    assert_error(response, 400).await;
+}
+
+#[tokio::test]
+async fn unreachable_feed_should_be_bad_gateway() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app
+        .quick_add_subscription("https://feeds.invalid/atom.xml")
+        .await;
+    assert_error(response, 502).await;
+
+    let response = app
+        .add_subscription("https://feeds.invalid/atom.xml", None, None)
+        .await;
+    assert_error(response, 502).await;
+}
+
+#[tokio::test]
+async fn database_error_should_be_internal_error() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    sqlx::query("DROP VIEW Items")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let response = app.get_unread_items(None, None).await;
+    let message = assert_error(response, 500).await;
+    // The details are only logged.
+    assert_eq!(message, "Internal error");
+}
+
+#[tokio::test]
+async fn proxy_errors_should_have_the_same_shape() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/proxy/not-a-signature/not-a-url", app.address))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_error(response, 403).await;
+
+    let response = app.get_proxied("http://10.0.0.1/a.png").await;
+    assert_error(response, 400).await;
+}
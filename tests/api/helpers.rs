@@ -1,11 +1,17 @@
+use actix_web::{web, App, HttpResponse, HttpServer};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
 use near_rss::configuration::get_configuration;
-use near_rss::database::users::User;
 use near_rss::Application;
 use reqwest::redirect::Policy;
 use reqwest::Client;
+use sha2::Sha256;
 use sqlx::SqlitePool;
 use uuid::Uuid;
 
+// Test servers sign the proxied urls with this.
+const PROXY_SECRET: &str = "secret";
+
 pub struct TestUser {
     pub email: String,
     pub password: String,
@@ -18,24 +24,6 @@ impl TestUser {
             password: "password".into(),
         }
     }
-
-    pub async fn store(&self, pool: &SqlitePool) {
-        let user = User::new(&Uuid::new_v4().to_string(), &self.email, &self.password);
-        sqlx::query!(
-            r#"
-            INSERT INTO Users
-                (id, email, password_hash, token)
-            VALUES(?, ?, ?, ?)
-            "#,
-            user.id,
-            user.email,
-            user.password_hash,
-            user.token
-        )
-        .execute(pool)
-        .await
-        .expect("Failed to store test user.");
-    }
 }
 
 pub struct TestApp {
@@ -44,6 +32,7 @@ pub struct TestApp {
     pub api_client: Client,
     pub test_user: TestUser,
     pub token: Option<String>,
+    pub pool: SqlitePool,
 }
 
 impl TestApp {
@@ -119,6 +108,31 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn import_subscriptions(&self, opml: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/reader/subscriptions/import", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .body(opml.to_owned())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn export_subscriptions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/reader/subscriptions/export", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn list_subscriptions(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/reader/api/0/subscription/list", self.address))
@@ -131,6 +145,384 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn set_content_extraction(&self, id: &str, enabled: bool) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/subscriptions/extractContent", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .json(&serde_json::json!({
+                "id": id,
+                "enabled": enabled
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_retention(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/api/retention", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn set_retention(&self, retention: serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/retention", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .json(&retention)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn list_web_subscriptions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/api/subscriptions", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn create_token(&self, name: &str, expire_in_days: Option<i64>) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/tokens", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .json(&serde_json::json!({
+                "name": name,
+                "expireInDays": expire_in_days
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn list_tokens(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/api/tokens", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn revoke_token(&self, id: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/api/tokens/{}", self.address, id))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn list_rules(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/api/rules", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn add_rule(&self, rule: serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/rules", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .json(&rule)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn update_rule(&self, id: &str, rule: serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/rules/{}", self.address, id))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .json(&rule)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_rule(&self, id: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/api/rules/{}", self.address, id))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn dry_run_rule(&self, rule: serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/rules/dryRun", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .json(&rule)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn ping(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/reader/ping", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn register(&self, email: &str, password: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/accounts/register", self.address))
+            .form(&serde_json::json!({
+                "Email": email,
+                "Passwd": password
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn list_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/api/admin/users", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn add_user(&self, email: &str, password: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/admin/users", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .json(&serde_json::json!({
+                "email": email,
+                "password": password
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn disable_user(&self, id: &str, disabled: bool) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/admin/users/{}/disabled", self.address, id))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .json(&serde_json::json!({ "disabled": disabled }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_user(&self, id: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!("{}/api/admin/users/{}", self.address, id))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn list_tags(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/reader/api/0/tag/list", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn rename_tag(&self, from: &str, to: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/reader/api/0/rename-tag", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .form(&serde_json::json!({ "s": from, "dest": to }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn disable_tag(&self, tag: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/reader/api/0/disable-tag", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .form(&serde_json::json!({ "s": tag }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn edit_tag(&self, ids: &[&str], add: &[&str], remove: &[&str]) -> reqwest::Response {
+        let form = ids
+            .iter()
+            .map(|id| ("i", *id))
+            .chain(add.iter().map(|tag| ("a", *tag)))
+            .chain(remove.iter().map(|tag| ("r", *tag)))
+            .collect::<Vec<(&str, &str)>>();
+        self.api_client
+            .post(format!("{}/reader/api/0/edit-tag", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .form(&form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_unread_counts(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/reader/api/0/unread-count", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn mark_all_as_read(&self, stream_id: &str, ts: Option<&str>) -> reqwest::Response {
+        let mut form = vec![("s", stream_id)];
+        if let Some(ts) = ts {
+            form.push(("ts", ts));
+        }
+        self.api_client
+            .post(format!("{}/reader/api/0/mark-all-as-read", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .form(&form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_stream_item_ids(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/reader/api/0/stream/items/ids", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn search_item_ids(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/reader/api/0/search/items/ids", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn search(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/api/search", self.address))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_stream_contents(
+        &self,
+        stream_id: &str,
+        query: &[(&str, &str)],
+    ) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/reader/api/0/stream/contents/{}",
+                self.address, stream_id
+            ))
+            .header(
+                "Authorization",
+                format!("GoogleLogin auth={}", self.token.as_deref().unwrap_or("")),
+            )
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn mark_as_read(&self, ids: &[&str]) -> reqwest::Response {
         self.api_client
             .post(format!("{}/api/markAsRead", self.address))
@@ -167,6 +559,23 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    /// Gets the url through the proxy, with a valid signature.
+    pub async fn get_proxied(&self, url: &str) -> reqwest::Response {
+        let mut mac = Hmac::<Sha256>::new_from_slice(PROXY_SECRET.as_bytes()).unwrap();
+        mac.update(url.as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+        self.api_client
+            .get(format!(
+                "{}/proxy/{}/{}",
+                self.address,
+                signature,
+                hex::encode(url)
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_index(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/index.html", self.address))
@@ -207,6 +616,9 @@ pub async fn spawn_app() -> TestApp {
         c.application.port = 0;
         c.application.email = test_user.email.clone();
         c.application.password = test_user.password.clone();
+        // Every test gets its own database.
+        c.database.path = ":memory:".to_string();
+        c.proxy.secret = PROXY_SECRET.to_string();
         c
     };
 
@@ -218,8 +630,6 @@ pub async fn spawn_app() -> TestApp {
     let pool = app.pool.clone();
     let _ = tokio::spawn(app.run_until_stopped());
 
-    configure_database(&pool).await;
-
     let client = Client::builder()
         .redirect(Policy::none())
         .cookie_store(true)
@@ -232,12 +642,80 @@ pub async fn spawn_app() -> TestApp {
         api_client: client,
         test_user,
         token: None,
+        pool,
     }
 }
 
-async fn configure_database(pool: &SqlitePool) {
-    sqlx::migrate!("./migrations")
-        .run(pool)
-        .await
-        .expect("Failed to migrate the database.");
+/// Serves `(path, content type, body)` pages like a website, returns its address.
+pub fn spawn_site(pages: &[(&'static str, &'static str, &'static str)]) -> String {
+    let pages = pages.to_vec();
+    let server = HttpServer::new(move || {
+        pages
+            .iter()
+            .fold(App::new(), |app, &(path, content_type, body)| {
+                app.route(
+                    path,
+                    web::get().to(move || async move {
+                        HttpResponse::Ok().content_type(content_type).body(body)
+                    }),
+                )
+            })
+    })
+    .bind("127.0.0.1:0")
+    .expect("Failed to bind the site");
+    let port = server.addrs()[0].port();
+    tokio::spawn(server.run());
+    format!("http://127.0.0.1:{}", port)
+}
+
+/// Feeds of a local site, so the tests depend neither on the network nor on
+/// what the sites published lately.
+pub struct TestFeeds {
+    pub address: String,
+    pub blog: String,
+    pub news: String,
+}
+
+// Entries are an hour apart from an hour ago, so the retention keeps them.
+fn atom_feed(title: &str, entry_title: &str, num_entries: i64) -> String {
+    let now = Utc::now();
+    let entries = (1..=num_entries)
+        .map(|i| {
+            format!(
+                r#"<entry><title>{} {}</title><id>urn:{}:{}</id><link href="https://{}.com/{}"/><updated>{}</updated><content type="html">{} {}</content></entry>"#,
+                entry_title,
+                i,
+                title,
+                i,
+                title,
+                i,
+                (now - Duration::hours(i)).to_rfc3339(),
+                entry_title,
+                i
+            )
+        })
+        .collect::<String>();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><feed xmlns="http://www.w3.org/2005/Atom"><title>{}</title><id>urn:{}</id><updated>{}</updated>{}</feed>"#,
+        title,
+        title,
+        now.to_rfc3339(),
+        entries
+    )
+}
+
+/// Serves a blog with 3 entries about Rust, and news with 8 entries.
+pub fn spawn_feeds() -> TestFeeds {
+    // The site serves them until the tests end.
+    let blog: &'static str = Box::leak(atom_feed("Blog", "Rust notes", 3).into_boxed_str());
+    let news: &'static str = Box::leak(atom_feed("News", "Market news", 8).into_boxed_str());
+    let address = spawn_site(&[
+        ("/blog.xml", "application/atom+xml", blog),
+        ("/news.xml", "application/atom+xml", news),
+    ]);
+    TestFeeds {
+        blog: format!("{}/blog.xml", address),
+        news: format!("{}/news.xml", address),
+        address,
+    }
 }
@@ -1,10 +1,19 @@
 mod data;
 mod helpers;
 
+mod admin;
+mod errors;
 mod files;
 mod login;
 mod ping;
+mod proxy;
 mod read;
+mod retention;
+mod rules;
+mod search;
+mod stream;
 mod subscriptions;
+mod tags;
+mod tokens;
 mod unread;
 mod user;
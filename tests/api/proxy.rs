@@ -0,0 +1,43 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn unsigned_url_should_be_forbidden() {
+    let app = spawn_app().await;
+
+    let url = hex::encode("http://127.0.0.1/secret.png");
+    let response = app
+        .api_client
+        .get(format!("{}/proxy/{}/{}", app.address, "00".repeat(32), url))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app
+        .api_client
+        .get(format!("{}/proxy/not-a-signature/not-a-url", app.address))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn local_urls_should_not_be_proxied() {
+    let app = spawn_app().await;
+
+    for url in [
+        format!("{}/index.html", app.address),
+        format!("http://localhost:{}/index.html", app.port),
+        format!("http://[::ffff:127.0.0.1]:{}/index.html", app.port),
+        "http://169.254.169.254/latest/meta-data".to_string(),
+        "http://10.0.0.1/a.png".to_string(),
+        "http://100.64.0.1/a.png".to_string(),
+        "http://[fd00::1]/a.png".to_string(),
+        "http://[fe80::1]/a.png".to_string(),
+        "file:///etc/passwd".to_string(),
+    ] {
+        let response = app.get_proxied(&url).await;
+        assert_eq!(response.status().as_u16(), 400, "{}", url);
+    }
+}
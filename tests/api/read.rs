@@ -1,5 +1,5 @@
 use crate::data::Contents;
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_feeds};
 use std::collections::HashSet;
 
 #[tokio::test]
@@ -58,3 +58,68 @@ async fn items_marked_as_read_should_not_return_in_unread_list() {
     assert!(!unread_ids.contains(&id_1));
     assert!(unread_ids.contains(&id_2));
 }
+
+#[tokio::test]
+async fn mark_all_as_read_should_clear_label() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    app.add_subscription(&spawn_feeds().news, None, Some("News"))
+        .await;
+
+    // Nothing is older than the epoch.
+    let response = app.mark_all_as_read("user/-/label/News", Some("0")).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let contents = app
+        .get_unread_items(None, None)
+        .await
+        .json::<Contents>()
+        .await
+        .expect("Failed to deserialize to Contents");
+    assert!(!contents.items.is_empty());
+
+    // Only items strictly older than ts are marked.
+    let oldest_usec = contents
+        .items
+        .iter()
+        .map(|item| item.timestamp_usec.parse::<i64>().unwrap())
+        .min()
+        .unwrap();
+    let unread_count = contents.items.len();
+    for (ts, marked) in [(oldest_usec, 0), (oldest_usec + 1, 1)] {
+        let response = app
+            .mark_all_as_read("user/-/label/News", Some(&ts.to_string()))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        let contents = app
+            .get_unread_items(None, None)
+            .await
+            .json::<Contents>()
+            .await
+            .expect("Failed to deserialize to Contents");
+        assert_eq!(contents.items.len(), unread_count - marked);
+    }
+
+    let response = app.mark_all_as_read("user/-/label/News", None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let contents = app
+        .get_unread_items(None, None)
+        .await
+        .json::<Contents>()
+        .await
+        .expect("Failed to deserialize to Contents");
+    assert!(contents.items.is_empty());
+}
+
+#[tokio::test]
+async fn mark_all_as_read_with_invalid_stream_should_return_400() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.mark_all_as_read("unknown", None).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .mark_all_as_read("user/-/state/com.google/starred", None)
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,100 @@
+use crate::data::{RetentionPolicy, Retentions};
+use crate::helpers::{spawn_app, spawn_feeds};
+use serde_json::json;
+
+#[tokio::test]
+async fn anonymous_get_retention_should_fail() {
+    let app = spawn_app().await;
+
+    let response = app.get_retention().await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn retention_should_default_to_configuration() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.get_retention().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let retentions = response.json::<Retentions>().await.unwrap();
+    assert_eq!(
+        retentions.policy,
+        RetentionPolicy {
+            max_age_days: Some(14),
+            max_items: None,
+            keep_starred: true,
+        }
+    );
+    assert!(retentions.overrides.is_empty());
+}
+
+#[tokio::test]
+async fn set_retention_should_override_user_and_subscription() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let blog = spawn_feeds().blog;
+    app.add_subscription(&blog, None, None).await;
+
+    let response = app
+        .set_retention(json!({"maxAgeDays": 30, "keepStarred": false}))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .set_retention(json!({
+            "subscriptionId": format!("feed/{}", blog),
+            "maxItems": 100
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let retentions = app
+        .get_retention()
+        .await
+        .json::<Retentions>()
+        .await
+        .unwrap();
+    assert_eq!(
+        retentions.policy,
+        RetentionPolicy {
+            max_age_days: Some(30),
+            max_items: None,
+            keep_starred: false,
+        }
+    );
+    assert_eq!(retentions.overrides.len(), 2);
+    assert_eq!(retentions.overrides[0].subscription_id, "");
+    assert_eq!(retentions.overrides[1].max_items, Some(100));
+    assert_eq!(retentions.overrides[1].max_age_days, None);
+}
+
+#[tokio::test]
+async fn null_limits_should_keep_items_forever() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.set_retention(json!({"maxAgeDays": null})).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let retentions = app
+        .get_retention()
+        .await
+        .json::<Retentions>()
+        .await
+        .unwrap();
+    assert_eq!(retentions.policy.max_age_days, None);
+}
+
+#[tokio::test]
+async fn set_invalid_retention_should_fail() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.set_retention(json!({"maxAgeDays": 0})).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .set_retention(json!({"subscriptionId": "feed/unknown", "maxItems": 10}))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
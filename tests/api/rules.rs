@@ -0,0 +1,138 @@
+use crate::data::{DryRun, Rule, Rules};
+use crate::helpers::{spawn_app, spawn_feeds};
+use serde_json::json;
+
+#[tokio::test]
+async fn anonymous_list_rules_should_fail() {
+    let app = spawn_app().await;
+
+    let response = app.list_rules().await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn rules_should_be_added_updated_and_deleted() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app
+        .add_rule(json!({
+            "name": "Ads",
+            "field": "title",
+            "matchType": "substring",
+            "pattern": "sponsored",
+            "action": "read"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let rule = response.json::<Rule>().await.unwrap();
+    assert_eq!(rule.name, "Ads");
+
+    let response = app
+        .update_rule(
+            &rule.id,
+            json!({
+                "name": "Ads",
+                "field": "title",
+                "matchType": "regex",
+                "pattern": "^\\[ad\\]",
+                "action": "tag",
+                "tag": "ads"
+            }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let rules = app.list_rules().await.json::<Rules>().await.unwrap().rules;
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].match_type, "regex");
+    assert_eq!(rules[0].action, "tag");
+    assert_eq!(rules[0].tag.as_deref(), Some("ads"));
+
+    let response = app.delete_rule(&rule.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let rules = app.list_rules().await.json::<Rules>().await.unwrap().rules;
+    assert!(rules.is_empty());
+
+    let response = app.delete_rule(&rule.id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn invalid_rules_should_be_rejected() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app
+        .add_rule(json!({
+            "name": "Broken",
+            "field": "title",
+            "matchType": "regex",
+            "pattern": "(",
+            "action": "drop"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .add_rule(json!({
+            "name": "No tag",
+            "field": "title",
+            "matchType": "substring",
+            "pattern": "rust",
+            "action": "tag"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .update_rule(
+            "unknown",
+            json!({
+                "name": "Unknown",
+                "field": "title",
+                "matchType": "substring",
+                "pattern": "rust",
+                "action": "star"
+            }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn dry_run_should_report_matched_items() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let news = spawn_feeds().news;
+    app.add_subscription(&news, None, Some("News")).await;
+
+    let response = app
+        .dry_run_rule(json!({
+            "name": "News",
+            "field": "label",
+            "matchType": "substring",
+            "pattern": "news",
+            "action": "read"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let dry_run = response.json::<DryRun>().await.unwrap();
+    assert_eq!(dry_run.items.len(), 8);
+    assert!(dry_run
+        .items
+        .iter()
+        .all(|item| item.subscription_id == format!("feed/{}", news)));
+
+    let response = app
+        .dry_run_rule(json!({
+            "name": "Nothing",
+            "field": "feed",
+            "matchType": "regex",
+            "pattern": "^nothing-matches-this$",
+            "action": "drop"
+        }))
+        .await;
+    let dry_run = response.json::<DryRun>().await.unwrap();
+    assert!(dry_run.items.is_empty());
+    // A dry run doesn't save the rule.
+    let rules = app.list_rules().await.json::<Rules>().await.unwrap().rules;
+    assert!(rules.is_empty());
+}
@@ -0,0 +1,61 @@
+use crate::data::{Contents, SearchResults};
+use crate::helpers::{spawn_app, spawn_feeds};
+
+#[tokio::test]
+async fn anonymous_search_should_fail() {
+    let app = spawn_app().await;
+
+    let response = app.search_item_ids(&[("q", "rust")]).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.search(&[("q", "rust")]).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn search_without_query_should_fail() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.search_item_ids(&[]).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app.search(&[("q", " ")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn search_should_find_items_by_title() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let feeds = spawn_feeds();
+    app.add_subscription(&feeds.news, None, Some("News")).await;
+    app.add_subscription(&feeds.blog, None, None).await;
+    let word = "Rust";
+
+    let response = app.search(&[("q", word)]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let contents = response
+        .json::<Contents>()
+        .await
+        .expect("Failed to deserialize to Contents");
+    assert_eq!(contents.items.len(), 3);
+    assert!(contents
+        .items
+        .iter()
+        .all(|item| item.title.starts_with("Rust notes")));
+
+    let response = app.search_item_ids(&[("q", word)]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let results = response
+        .json::<SearchResults>()
+        .await
+        .expect("Failed to deserialize to SearchResults");
+    assert_eq!(results.results.len(), contents.items.len());
+
+    let results = app
+        .search_item_ids(&[("q", word), ("s", "user/-/label/News")])
+        .await
+        .json::<SearchResults>()
+        .await
+        .expect("Failed to deserialize to SearchResults");
+    assert!(results.results.is_empty());
+}
@@ -0,0 +1,177 @@
+use crate::data::{ItemIds, StreamContents};
+use crate::helpers::{spawn_app, spawn_feeds, TestApp};
+
+async fn item_ids(app: &TestApp, query: &[(&str, &str)]) -> Vec<String> {
+    let response = app.get_stream_item_ids(query).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<ItemIds>()
+        .await
+        .expect("Failed to deserialize to ItemIds")
+        .item_refs
+        .into_iter()
+        .map(|item| item.id)
+        .collect()
+}
+
+#[tokio::test]
+async fn anonymous_get_item_ids_should_fail() {
+    let app = spawn_app().await;
+
+    let response = app
+        .get_stream_item_ids(&[("s", "user/-/state/com.google/reading-list")])
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn get_item_ids_should_filter_by_feed_and_label() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let feeds = spawn_feeds();
+    app.add_subscription(&feeds.news, None, Some("News")).await;
+    app.add_subscription(&feeds.blog, None, None).await;
+
+    let all = item_ids(
+        &app,
+        &[("s", "user/-/state/com.google/reading-list"), ("n", "1000")],
+    )
+    .await;
+    let news = item_ids(
+        &app,
+        &[("s", &format!("feed/{}", feeds.news)), ("n", "1000")],
+    )
+    .await;
+    let blogs = item_ids(
+        &app,
+        &[("s", &format!("feed/{}", feeds.blog)), ("n", "1000")],
+    )
+    .await;
+    assert!(!news.is_empty());
+    assert!(!blogs.is_empty());
+    assert_eq!(all.len(), news.len() + blogs.len());
+    let unknown = item_ids(&app, &[("s", "unknown"), ("n", "1000")]).await;
+    assert_eq!(unknown, all);
+
+    let label = item_ids(&app, &[("s", "user/-/label/News"), ("n", "1000")]).await;
+    assert_eq!(label, news);
+
+    let excluded = item_ids(
+        &app,
+        &[
+            ("s", "user/-/state/com.google/reading-list"),
+            ("xt", "user/-/label/News"),
+            ("n", "1000"),
+        ],
+    )
+    .await;
+    assert_eq!(excluded, blogs);
+}
+
+#[tokio::test]
+async fn get_item_ids_should_filter_by_state_and_time() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let feeds = spawn_feeds();
+    app.add_subscription(&feeds.news, None, None).await;
+    let news = item_ids(
+        &app,
+        &[("s", &format!("feed/{}", feeds.news)), ("n", "1000")],
+    )
+    .await;
+    app.mark_as_read(&[news[0].as_str()]).await;
+
+    let read = item_ids(
+        &app,
+        &[
+            ("s", &format!("feed/{}", feeds.news)),
+            ("it", "user/-/state/com.google/read"),
+        ],
+    )
+    .await;
+    assert_eq!(read, vec![news[0].clone()]);
+    let unread = item_ids(
+        &app,
+        &[
+            ("s", &format!("feed/{}", feeds.news)),
+            ("xt", "user/-/state/com.google/read"),
+            ("n", "1000"),
+        ],
+    )
+    .await;
+    assert_eq!(unread.len(), news.len() - 1);
+
+    let none = item_ids(&app, &[("s", &format!("feed/{}", feeds.news)), ("nt", "0")]).await;
+    assert!(none.is_empty());
+}
+
+#[tokio::test]
+async fn get_item_ids_without_stream_should_return_400() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.get_stream_item_ids(&[]).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn get_stream_contents_should_page_with_continuation() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let feeds = spawn_feeds();
+    app.add_subscription(&feeds.news, None, Some("News")).await;
+    app.add_subscription(&feeds.blog, None, None).await;
+
+    let response = app
+        .get_stream_contents("user/-/label/News", &[("n", "5")])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let first_page = response.json::<StreamContents>().await.unwrap();
+    assert_eq!(first_page.id, "user/-/label/News");
+    assert_eq!(first_page.title, "News");
+    assert_eq!(first_page.items.len(), 5);
+    let continuation = first_page.continuation.unwrap();
+
+    let second_page = app
+        .get_stream_contents("user/-/label/News", &[("n", "5"), ("c", &continuation)])
+        .await
+        .json::<StreamContents>()
+        .await
+        .unwrap();
+    assert!(!second_page.items.is_empty());
+    assert!(second_page.items[0].published <= first_page.items[4].published);
+    assert_ne!(second_page.items[0].id, first_page.items[4].id);
+
+    let reading_list = app
+        .get_stream_contents(
+            "user/-/state/com.google/reading-list",
+            &[("xt", "user/-/label/News"), ("n", "1000")],
+        )
+        .await
+        .json::<StreamContents>()
+        .await
+        .unwrap();
+    assert!(!reading_list.items.is_empty());
+    assert!(reading_list
+        .items
+        .iter()
+        .all(|item| item.origin.stream_id == format!("feed/{}", feeds.blog)));
+}
+
+#[tokio::test]
+async fn get_stream_contents_should_decode_the_stream_id_once() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let feed = format!("{}?tag=a%2Fb", spawn_feeds().news);
+    app.add_subscription(&feed, None, None).await;
+
+    // Escaped like any other path segment.
+    let stream_id = format!("feed/{}", feed)
+        .replace('%', "%25")
+        .replace('?', "%3F");
+    let response = app.get_stream_contents(&stream_id, &[]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let contents = response.json::<StreamContents>().await.unwrap();
+    assert_eq!(contents.id, format!("feed/{}", feed));
+    assert!(!contents.items.is_empty());
+}
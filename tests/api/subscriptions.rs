@@ -1,5 +1,5 @@
-use crate::data::Subscriptions;
-use crate::helpers::{spawn_app, TestApp};
+use crate::data::{ImportResults, Subscriptions};
+use crate::helpers::{spawn_app, spawn_feeds, spawn_site, TestApp};
 
 #[tokio::test]
 async fn anonymous_add_subscription_should_fail() {
@@ -73,6 +73,184 @@ async fn quick_add_new_subscription() {
     assert!(has_subscription(&app, "https://blogs.nearsyh.me/atom.xml").await);
 }
 
+const SITE_FEED: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Site</title>
+  <id>urn:site</id>
+  <updated>2022-01-01T00:00:00Z</updated>
+  <entry>
+    <title>Post</title>
+    <id>urn:site:post</id>
+    <link href="/post"/>
+    <updated>2022-01-01T00:00:00Z</updated>
+  </entry>
+</feed>"#;
+
+#[tokio::test]
+async fn quick_add_should_discover_linked_feed_of_site() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let site = spawn_site(&[
+        (
+            "/",
+            "text/html",
+            r#"<html><head><link rel="alternate" type="application/atom+xml" href="/atom.xml"></head></html>"#,
+        ),
+        ("/atom.xml", "application/atom+xml", SITE_FEED),
+    ]);
+
+    let response = app.quick_add_subscription(&site).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(has_subscription(&app, &format!("{}/atom.xml", site)).await);
+}
+
+#[tokio::test]
+async fn quick_add_should_probe_feed_of_site() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let site = spawn_site(&[
+        ("/", "text/html", "<html><head></head></html>"),
+        ("/feed.xml", "application/atom+xml", SITE_FEED),
+    ]);
+
+    let response = app.quick_add_subscription(&site).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(has_subscription(&app, &format!("{}/feed.xml", site)).await);
+}
+
+#[tokio::test]
+async fn anonymous_import_subscriptions_should_fail() {
+    let app = spawn_app().await;
+
+    let response = app.import_subscriptions("<opml></opml>").await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn import_invalid_opml_should_return_400() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.import_subscriptions("<opml><body></opml>").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn import_subscriptions_should_report_each_feed() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let feeds = spawn_feeds();
+    app.add_subscription(&feeds.news, None, None).await;
+
+    let response = app
+        .import_subscriptions(&format!(
+            r#"<opml version="1.0"><body>
+            <outline text="Blogs">
+              <outline text="Nearsyh" xmlUrl="{}" />
+              <outline text="News" xmlUrl="{}" />
+              <outline text="Broken" xmlUrl="{}/not-a-feed.xml" />
+            </outline>
+            </body></opml>"#,
+            feeds.blog, feeds.news, feeds.address
+        ))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let results = response.json::<ImportResults>().await.unwrap().results;
+    assert_eq!(
+        results
+            .iter()
+            .map(|result| result.status.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["added", "skipped", "failed"]
+    );
+    assert!(results[2].error.is_some());
+
+    let subscriptions = app
+        .list_subscriptions()
+        .await
+        .json::<Subscriptions>()
+        .await
+        .unwrap()
+        .subscriptions;
+    let imported = subscriptions
+        .iter()
+        .find(|sub| sub.feed_url == feeds.blog)
+        .unwrap();
+    assert_eq!(imported.title, "Nearsyh");
+    assert_eq!(imported.categories[0].id, "user/-/label/Blogs");
+}
+
+#[tokio::test]
+async fn anonymous_export_subscriptions_should_fail() {
+    let app = spawn_app().await;
+
+    let response = app.export_subscriptions().await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn export_subscriptions_should_group_by_label() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let blog = spawn_feeds().blog;
+    app.add_subscription(&blog, None, Some("Blogs")).await;
+
+    let response = app.export_subscriptions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let opml = response.text().await.unwrap();
+    assert!(opml.contains("<opml version=\"2.0\">"));
+    assert!(opml.contains("<outline text=\"Blogs\" title=\"Blogs\">"));
+    assert!(opml.contains(&format!("xmlUrl=\"{}\"", blog)));
+}
+
+#[tokio::test]
+async fn list_subscriptions_should_report_health() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    app.add_subscription(&spawn_feeds().blog, None, None).await;
+
+    let response = app.list_web_subscriptions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriptions = response.json::<Subscriptions>().await.unwrap();
+    let health = &subscriptions.subscriptions[0].health;
+    assert!(health.last_success_ms > 0);
+    assert_eq!(health.last_http_status, Some(200));
+    assert_eq!(health.last_error, None);
+    assert_eq!(health.consecutive_failures, 0);
+    assert!(!health.disabled);
+}
+
+#[tokio::test]
+async fn set_content_extraction_should_update_subscription() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let blog = spawn_feeds().blog;
+    app.add_subscription(&blog, None, None).await;
+    let id = format!("feed/{}", blog);
+
+    let response = app.set_content_extraction(&id, true).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriptions = app
+        .list_web_subscriptions()
+        .await
+        .json::<Subscriptions>()
+        .await
+        .unwrap();
+    assert!(subscriptions.subscriptions[0].extract_content);
+
+    let response = app.set_content_extraction("feed/unknown", true).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
 async fn has_subscription(app: &TestApp, link: &str) -> bool {
     let response = app.list_subscriptions().await;
     let subscriptions = response.json::<Subscriptions>().await.unwrap();
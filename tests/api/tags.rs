@@ -0,0 +1,111 @@
+use crate::data::{StreamContents, Tags};
+use crate::helpers::{spawn_app, spawn_feeds, TestApp};
+
+async fn tag_ids(app: &TestApp) -> Vec<String> {
+    let response = app.list_tags().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<Tags>()
+        .await
+        .unwrap()
+        .tags
+        .into_iter()
+        .map(|tag| tag.id)
+        .collect()
+}
+
+#[tokio::test]
+async fn anonymous_list_tags_should_fail() {
+    let app = spawn_app().await;
+
+    let response = app.list_tags().await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn list_tags_should_include_labels_and_states() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    app.add_subscription(&spawn_feeds().blog, None, Some("Blogs"))
+        .await;
+
+    let response = app.list_tags().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let tags = response.json::<Tags>().await.unwrap().tags;
+    assert_eq!(tags.len(), 3);
+    assert_eq!(tags[0].id, "user/-/state/com.google/starred");
+    assert_eq!(tags[1].id, "user/-/state/com.google/read");
+    assert_eq!(tags[2].id, "user/-/label/Blogs");
+    assert_eq!(tags[2].type_f.as_deref(), Some("folder"));
+}
+
+#[tokio::test]
+async fn rename_and_disable_tag_should_work() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    app.add_subscription(&spawn_feeds().blog, None, Some("Blogs"))
+        .await;
+
+    let response = app
+        .rename_tag("user/-/label/Blogs", "user/-/label/Friends")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(tag_ids(&app)
+        .await
+        .contains(&"user/-/label/Friends".to_string()));
+
+    let response = app.disable_tag("user/-/label/Friends").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(tag_ids(&app).await.len(), 2);
+
+    let response = app.disable_tag("user/-/state/com.google/read").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn stream_contents(app: &TestApp, stream_id: &str) -> StreamContents {
+    let response = app.get_stream_contents(stream_id, &[("n", "1000")]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json::<StreamContents>().await.unwrap()
+}
+
+#[tokio::test]
+async fn edit_tag_should_label_items() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    app.add_subscription(&spawn_feeds().news, None, None).await;
+    let items = stream_contents(&app, "user/-/state/com.google/reading-list")
+        .await
+        .items;
+    let id = items[0]
+        .id
+        .strip_prefix("tag:google.com,2005:reader/item/")
+        .unwrap();
+
+    let response = app
+        .edit_tag(
+            &[id],
+            &["user/-/label/Foo", "user/-/state/com.google/starred"],
+            &[],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let labeled = stream_contents(&app, "user/-/label/Foo").await.items;
+    assert_eq!(labeled.len(), 1);
+    assert_eq!(labeled[0].id, items[0].id);
+    assert!(labeled[0]
+        .categories
+        .contains(&"user/-/label/Foo".to_string()));
+    assert!(labeled[0]
+        .categories
+        .contains(&"user/-/state/com.google/starred".to_string()));
+    assert!(tag_ids(&app)
+        .await
+        .contains(&"user/-/label/Foo".to_string()));
+
+    let response = app.edit_tag(&[id], &[], &["user/-/label/Foo"]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(stream_contents(&app, "user/-/label/Foo")
+        .await
+        .items
+        .is_empty());
+}
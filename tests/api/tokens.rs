@@ -0,0 +1,44 @@
+use crate::data::{Token, Tokens};
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn created_token_should_work_until_revoked() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    let response = app.create_token("Reeder", Some(30)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let created = response.json::<Token>().await.unwrap();
+    assert_eq!(created.name, "Reeder");
+    assert!(created.expire_at > created.created_at_ms / 1000);
+
+    let response = app.list_tokens().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let tokens = response.json::<Tokens>().await.unwrap().tokens;
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].id, created.id);
+    assert!(tokens[0].token.is_none());
+
+    let login_token = app.token.replace(created.token.clone().unwrap());
+    assert_eq!(app.ping().await.status().as_u16(), 200);
+
+    app.token = login_token;
+    assert_eq!(app.revoke_token(&created.id).await.status().as_u16(), 200);
+    assert_eq!(app.revoke_token(&created.id).await.status().as_u16(), 404);
+
+    app.token = created.token;
+    assert_eq!(app.ping().await.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn token_lifetime_should_be_limited() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+
+    for days in [0, -1, 3651, i64::MAX] {
+        let response = app.create_token("Reeder", Some(days)).await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+    let response = app.create_token("Reeder", Some(3650)).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
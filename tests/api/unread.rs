@@ -1,5 +1,5 @@
-use crate::data::Contents;
-use crate::helpers::spawn_app;
+use crate::data::{Contents, UnreadCounts};
+use crate::helpers::{spawn_app, spawn_feeds};
 
 #[tokio::test]
 async fn anonymous_list_unread_should_fail() {
@@ -67,3 +67,29 @@ async fn list_unread_items_with_offset_should_return_200() {
         .expect("Failed to deserialize to Contents");
     assert_ne!(first_page.items[0].id, second_page.items[0].id);
 }
+
+#[tokio::test]
+async fn unread_counts_should_roll_up_labels() {
+    let mut app = spawn_app().await;
+    app.test_user_login().await;
+    let feeds = spawn_feeds();
+    app.add_subscription(&feeds.news, None, Some("News")).await;
+
+    let response = app.get_unread_counts().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let counts = response.json::<UnreadCounts>().await.unwrap().unreadcounts;
+    let count_of = |id: &str| {
+        counts
+            .iter()
+            .find(|count| count.id == id)
+            .map(|count| count.count)
+    };
+    let feed_count = count_of(&format!("feed/{}", feeds.news)).unwrap();
+    assert_eq!(feed_count, 8);
+    assert_eq!(count_of("user/-/label/News"), Some(feed_count));
+    assert_eq!(
+        count_of("user/-/state/com.google/reading-list"),
+        Some(feed_count)
+    );
+}